@@ -79,10 +79,11 @@ higherHalf:
     add rdi, rax
     add rsi, rax
 
-    ; Enable SSE for floats
+    ; Enable SSE for floats and write protection of read-only pages in the kernel
     mov rax, cr0
     and ax, 0xFFFB
     or ax, 0x2
+    or eax, 1 << 16
     mov cr0, rax
     mov rax, cr4
     or ax, 3 << 9
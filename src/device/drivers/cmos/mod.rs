@@ -15,8 +15,6 @@ enum Register {
     StatusB = 0x0B,
 }
 
-const MONTH_TO_DAYS: [isize; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
-
 fn read_register(register: Register) -> u8 {
     unsafe { crate::critical::enter_local() };
 
@@ -28,22 +26,6 @@ fn read_register(register: Register) -> u8 {
     ret
 }
 
-fn is_leap_year(year: isize) -> bool {
-    if year % 4 == 0 {
-        if year % 100 == 0 {
-            if year % 400 == 0 {
-                true
-            } else {
-                false
-            }
-        } else {
-            true
-        }
-    } else {
-        false
-    }
-}
-
 pub fn initialize() {
     log!("Initializing RTC . . . ");
 
@@ -78,23 +60,8 @@ pub fn initialize() {
     }
 
     year += 2000;
-    month -= 1;
-    day -= 1;
 
-    let mut yday = MONTH_TO_DAYS[month as usize] + day;
-    if is_leap_year(year) && month > 1 {
-        yday += 1;
-    }
-
-    let mut new_epoch = second + minute * 60 + hour * 3600 + yday * 86400;
-
-    for year in 1970..year {
-        if is_leap_year(year) {
-            new_epoch += 366 * 86400;
-        } else {
-            new_epoch += 365 * 86400;
-        }
-    }
+    let new_epoch = time::date_to_epoch(year, month, day, hour, minute, second);
 
     time::set_epoch_time(new_epoch);
 
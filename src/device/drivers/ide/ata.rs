@@ -281,4 +281,8 @@ impl Device for ATA {
             _ => Err(error::Status::InvalidIOCtrl),
         }
     }
+
+    fn sector_size(&self) -> Option<usize> {
+        Some(SECTOR_SIZE)
+    }
 }
@@ -1,53 +1,189 @@
-use super::constants::*;
+use super::{constants::*, controller};
 use crate::{
     device::{self, Device, DeviceReference},
     error,
 };
-use alloc::{boxed::Box, format, string::String};
+use alloc::{boxed::Box, format, string::String, vec::Vec};
 
 pub struct ATAPI {
-    _controller: DeviceReference,
-    _channel: Channel,
-    _drive: Drive,
+    controller: DeviceReference,
+    channel: Channel,
+    drive: Drive,
     _capabilities: u16,
-    size: usize,
 }
 
 const SECTOR_SIZE: usize = 2048;
+const PACKET_SIZE: usize = 12;
+const CAPACITY_SIZE: usize = 8;
+const MAX_TRANSFER_SECTORS: usize = 0xFFFF;
+
+const IOCTRL_GET_SIZE: usize = 0;
+const IOCTRL_EJECT: usize = 1;
 
 impl ATAPI {
     pub fn create(
         channel: Channel,
         drive: Drive,
         capabilities: u16,
-        size: usize,
         _model: String,
     ) -> error::Result<()> {
         let controller = device::get_device(super::IDE_PATH)?;
 
         let path = format!("/ide/{}_{}", channel, drive);
-        let size = size * SECTOR_SIZE;
 
         device::register_device(
             &path,
             DeviceReference::new(Box::new(ATAPI {
-                _controller: controller,
-                _channel: channel,
-                _drive: drive,
+                controller: controller,
+                channel: channel,
+                drive: drive,
                 _capabilities: capabilities,
-                size: size,
             })),
         )
     }
+
+    fn send_packet(&self, packet: &[u8; PACKET_SIZE], buffer: &mut [u8]) -> error::Result<()> {
+        let channel = self.channel.clone() as usize;
+        let mut controller = self.controller.lock();
+
+        // Completion is signalled through the channel's IRQ
+        controller.ioctrl(controller::IOCTRL_SET_CHANNEL_INTERRUPT, channel)?;
+        let result = self.transfer(&mut controller, packet, buffer);
+        controller.ioctrl(controller::IOCTRL_CLEAR_CHANNEL_INTERRUPT, channel)?;
+
+        result
+    }
+
+    fn transfer(
+        &self,
+        controller: &mut Box<dyn Device>,
+        packet: &[u8; PACKET_SIZE],
+        buffer: &mut [u8],
+    ) -> error::Result<()> {
+        let channel = self.channel.clone();
+
+        // Poll
+        while controller.read_register(channel.reg(REGISTER_STATUS))? & STATUS_BUSY != 0 {}
+
+        // Select drive
+        controller.write_register(
+            channel.reg(REGISTER_DRIVE_SELECT),
+            0xA0 | self.drive.select(),
+        )?;
+        controller.ioctrl(controller::IOCTRL_POLL, channel.clone() as usize)?;
+
+        // Use PIO with one sector per data request
+        let byte_count = core::cmp::min(buffer.len(), SECTOR_SIZE);
+        controller.write_register(channel.reg(REGISTER_FEATURES), 0)?;
+        controller.write_register(channel.reg(REGISTER_LBA_1), byte_count & 0xFF)?;
+        controller.write_register(channel.reg(REGISTER_LBA_2), byte_count >> 8)?;
+
+        // Send the packet
+        controller.write_register(channel.reg(REGISTER_COMMAND), COMMAND_PACKET)?;
+        if controller.ioctrl(controller::IOCTRL_ADVANCED_POLL, channel.clone() as usize)? != 0 {
+            return Err(error::Status::IOError);
+        }
+
+        controller.write(channel.reg(REGISTER_DATA), packet)?;
+
+        // Read the data
+        let mut offset = 0;
+        while offset < buffer.len() {
+            controller.ioctrl(controller::IOCTRL_WAIT_INTERRUPT, channel.clone() as usize)?;
+            if controller.ioctrl(controller::IOCTRL_ADVANCED_POLL, channel.clone() as usize)? != 0 {
+                return Err(error::Status::IOError);
+            }
+
+            let length = controller.read_register(channel.reg(REGISTER_LBA_1))?
+                | (controller.read_register(channel.reg(REGISTER_LBA_2))? << 8);
+            if length == 0 || offset + length > buffer.len() {
+                return Err(error::Status::IOError);
+            }
+
+            controller.read(
+                channel.reg(REGISTER_DATA),
+                &mut buffer[offset..offset + length],
+            )?;
+            offset += length;
+        }
+
+        // Wait for the command to complete
+        controller.ioctrl(controller::IOCTRL_WAIT_INTERRUPT, channel.clone() as usize)?;
+        controller.ioctrl(controller::IOCTRL_POLL, channel.clone() as usize)?;
+        let status = controller.read_register(channel.reg(REGISTER_STATUS))?;
+        if status & (STATUS_ERROR | STATUS_DRIVE_FAULT | STATUS_DATA_REQUEST_READY) != 0 {
+            return Err(error::Status::IOError);
+        }
+
+        Ok(())
+    }
+
+    fn read_sectors(&self, lba: usize, count: usize, buffer: &mut [u8]) -> error::Result<()> {
+        let mut packet = [0u8; PACKET_SIZE];
+        packet[0] = COMMAND_ATAPI_READ as u8;
+        packet[2..6].copy_from_slice(&(lba as u32).to_be_bytes());
+        packet[6..10].copy_from_slice(&(count as u32).to_be_bytes());
+
+        self.send_packet(&packet, buffer)
+    }
+
+    fn read_capacity(&self) -> error::Result<usize> {
+        let mut packet = [0u8; PACKET_SIZE];
+        packet[0] = COMMAND_ATAPI_READ_CAPACITY as u8;
+
+        // Drives without media fail the command
+        let mut capacity = [0u8; CAPACITY_SIZE];
+        match self.send_packet(&packet, &mut capacity) {
+            Ok(()) => {}
+            Err(error::Status::IOError) => return Ok(0),
+            Err(status) => return Err(status),
+        }
+
+        let last_lba = u32::from_be_bytes([capacity[0], capacity[1], capacity[2], capacity[3]]);
+        let block_size = u32::from_be_bytes([capacity[4], capacity[5], capacity[6], capacity[7]]);
+        Ok((last_lba as usize + 1) * block_size as usize)
+    }
+
+    fn eject(&self) -> error::Result<usize> {
+        // START STOP UNIT with the load/eject bit set and the start bit clear
+        let mut packet = [0u8; PACKET_SIZE];
+        packet[0] = COMMAND_ATAPI_EJECT as u8;
+        packet[4] = 0x02;
+
+        self.send_packet(&packet, &mut [])?;
+        Ok(0)
+    }
 }
 
 impl Device for ATAPI {
-    fn read(&self, _: usize, _: &mut [u8]) -> error::Result<()> {
-        Err(error::Status::NotImplemented)
+    fn read(&self, lba: usize, buffer: &mut [u8]) -> error::Result<()> {
+        // Whole sectors are read directly into the buffer
+        let whole_sectors = buffer.len() / SECTOR_SIZE;
+        let mut sector = 0;
+        while sector < whole_sectors {
+            let count = core::cmp::min(whole_sectors - sector, MAX_TRANSFER_SECTORS);
+            self.read_sectors(
+                lba + sector,
+                count,
+                &mut buffer[sector * SECTOR_SIZE..(sector + count) * SECTOR_SIZE],
+            )?;
+            sector += count;
+        }
+
+        // A partial sector at the end goes through a bounce buffer
+        let remaining = buffer.len() % SECTOR_SIZE;
+        if remaining != 0 {
+            let mut bounce = Vec::with_capacity(SECTOR_SIZE);
+            bounce.resize(SECTOR_SIZE, 0);
+            self.read_sectors(lba + whole_sectors, 1, bounce.as_mut_slice())?;
+            buffer[whole_sectors * SECTOR_SIZE..].copy_from_slice(&bounce[..remaining]);
+        }
+
+        Ok(())
     }
 
     fn write(&mut self, _: usize, _: &[u8]) -> error::Result<()> {
-        Err(error::Status::NotImplemented)
+        Err(error::Status::ReadOnly)
     }
 
     fn read_register(&mut self, _: usize) -> error::Result<usize> {
@@ -60,8 +196,13 @@ impl Device for ATAPI {
 
     fn ioctrl(&mut self, code: usize, _: usize) -> error::Result<usize> {
         match code {
-            0 => Ok(self.size),
+            IOCTRL_GET_SIZE => self.read_capacity(),
+            IOCTRL_EJECT => self.eject(),
             _ => Err(error::Status::InvalidIOCtrl),
         }
     }
+
+    fn sector_size(&self) -> Option<usize> {
+        Some(SECTOR_SIZE)
+    }
 }
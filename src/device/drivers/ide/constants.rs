@@ -42,6 +42,7 @@ pub const COMMAND_IDENTIFY: usize = 0xEC; // ATA_CMD_IDENTIFY
 // ATAPI Commands
 pub const COMMAND_ATAPI_READ: usize = 0xA8; // ATAPI_CMD_READ
 pub const COMMAND_ATAPI_EJECT: usize = 0x1B; // ATAPI_CMD_EJECT
+pub const COMMAND_ATAPI_READ_CAPACITY: usize = 0x25; // ATAPI_CMD_READ_CAPACITY
 
 // Identification Space
 pub const IDENT_DEVICE_TYPE: usize = 0; // ATA_IDENT_DEVICETYPE
@@ -1,9 +1,12 @@
 use alloc::string::String;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 use super::{ata::ATA, atapi::ATAPI, constants::*};
 use crate::{
     device::{self, inb, outb, Device},
-    error, time,
+    error,
+    process::{self, ThreadQueue},
+    time,
 };
 
 struct ChannelRegisters {
@@ -22,9 +25,17 @@ pub const IOCTRL_POLL: usize = 1;
 pub const IOCTRL_ADVANCED_POLL: usize = 2;
 pub const IOCTRL_SET_CHANNEL_INTERRUPT: usize = 3;
 pub const IOCTRL_CLEAR_CHANNEL_INTERRUPT: usize = 4;
+pub const IOCTRL_WAIT_INTERRUPT: usize = 5;
 
+static CHANNEL_INTERRUPTS: [AtomicBool; 2] = [AtomicBool::new(false), AtomicBool::new(false)];
+static CHANNEL_QUEUES: [ThreadQueue; 2] = [ThreadQueue::new(), ThreadQueue::new()];
+
+// The context is the channel the IRQ belongs to
 unsafe fn irq_handler(context: usize) {
-    let _controller = &mut *(context as *mut IDEController);
+    CHANNEL_INTERRUPTS[context].store(true, Ordering::Release);
+    while let Some(thread) = CHANNEL_QUEUES[context].pop() {
+        process::queue_thread(thread);
+    }
 }
 
 impl IDEController {
@@ -33,13 +44,13 @@ impl IDEController {
             channels: [
                 ChannelRegisters {
                     io: ((bar0 & 0xFFFFFFFC) + 0x1F0 * if bar0 == 0 { 1 } else { 0 }) as u16,
-                    control: ((bar1 & 0xFFFFFFFC) + 0x3F6 * if bar1 == 0 { 1 } else { 0 }) as u16,
+                    control: ((bar1 & 0xFFFFFFFC) + 0x3F4 * if bar1 == 0 { 1 } else { 0 }) as u16,
                     bus_master: ((bar4 & 0xFFFFFFFC) + 0) as u16,
                     n_ien: 2,
                 },
                 ChannelRegisters {
                     io: ((bar2 & 0xFFFFFFFC) + 0x170 * if bar2 == 0 { 1 } else { 0 }) as u16,
-                    control: ((bar3 & 0xFFFFFFFC) + 0x376 * if bar3 == 0 { 1 } else { 0 }) as u16,
+                    control: ((bar3 & 0xFFFFFFFC) + 0x374 * if bar3 == 0 { 1 } else { 0 }) as u16,
                     bus_master: ((bar4 & 0xFFFFFFFC) + 8) as u16,
                     n_ien: 2,
                 },
@@ -49,16 +60,8 @@ impl IDEController {
 
     fn enumerate_drives(&mut self) -> error::Result<usize> {
         // Install IRQ handlers
-        crate::interrupts::irq::install_irq_handler(
-            14,
-            irq_handler,
-            self as *mut IDEController as usize,
-        );
-        crate::interrupts::irq::install_irq_handler(
-            15,
-            irq_handler,
-            self as *mut IDEController as usize,
-        );
+        crate::interrupts::irq::install_irq_handler(14, irq_handler, 0);
+        crate::interrupts::irq::install_irq_handler(15, irq_handler, 1);
 
         // Disable IRQs
         self.write_register(REGISTER_CONTROL, 2)?;
@@ -152,7 +155,7 @@ impl IDEController {
                 if drive_type == DRIVE_TYPE_ATA {
                     ATA::create(channel.clone(), drive, capabilities, size, model)?;
                 } else {
-                    ATAPI::create(channel.clone(), drive, capabilities, size, model)?;
+                    ATAPI::create(channel.clone(), drive, capabilities, model)?;
                 }
             }
         }
@@ -187,6 +190,36 @@ impl IDEController {
         }
     }
 
+    fn set_channel_interrupt(&mut self, channel: usize, enabled: bool) -> error::Result<usize> {
+        if channel >= 2 {
+            return Err(error::Status::InvalidRequestCode);
+        }
+
+        // Forget interrupts from earlier commands
+        self.channels[channel].n_ien = if enabled { 0 } else { 2 };
+        CHANNEL_INTERRUPTS[channel].store(false, Ordering::Release);
+        self.write_register(
+            Channel::from(channel).reg(REGISTER_CONTROL),
+            self.channels[channel].n_ien as usize,
+        )?;
+        Ok(0)
+    }
+
+    fn wait_interrupt(&self, channel: usize) -> error::Result<usize> {
+        if channel >= 2 {
+            return Err(error::Status::InvalidRequestCode);
+        }
+
+        while !CHANNEL_INTERRUPTS[channel].swap(false, Ordering::AcqRel) {
+            process::yield_thread_unless(
+                Some(CHANNEL_QUEUES[channel].into_current_queue()),
+                &|| CHANNEL_INTERRUPTS[channel].load(Ordering::Acquire),
+            );
+        }
+
+        Ok(0)
+    }
+
     fn read_buffer(&self, channel: Channel, register: u16, buffer: &mut [u8]) -> error::Result<()> {
         let channel = channel as usize;
 
@@ -368,22 +401,9 @@ impl Device for IDEController {
             IOCTRL_ENUMERATE => self.enumerate_drives(),
             IOCTRL_POLL => self.polling((argument & 1) as u8, false),
             IOCTRL_ADVANCED_POLL => self.polling((argument & 1) as u8, true),
-            IOCTRL_SET_CHANNEL_INTERRUPT => {
-                if argument >= 2 {
-                    Err(error::Status::InvalidRequestCode)
-                } else {
-                    self.channels[argument].n_ien = 0;
-                    Ok(0)
-                }
-            }
-            IOCTRL_CLEAR_CHANNEL_INTERRUPT => {
-                if argument >= 2 {
-                    Err(error::Status::InvalidRequestCode)
-                } else {
-                    self.channels[argument].n_ien = 2;
-                    Ok(0)
-                }
-            }
+            IOCTRL_SET_CHANNEL_INTERRUPT => self.set_channel_interrupt(argument, true),
+            IOCTRL_CLEAR_CHANNEL_INTERRUPT => self.set_channel_interrupt(argument, false),
+            IOCTRL_WAIT_INTERRUPT => self.wait_interrupt(argument),
             _ => Err(error::Status::InvalidIOCtrl),
         }
     }
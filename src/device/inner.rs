@@ -6,4 +6,25 @@ pub trait Device: Send {
     fn write_register(&mut self, address: usize, value: usize) -> crate::error::Result<()>;
 
     fn ioctrl(&mut self, code: usize, argument: usize) -> crate::error::Result<usize>;
+
+    // Block devices return the size of the sectors their addresses refer to and report their
+    // size in bytes through ioctrl 0
+    fn sector_size(&self) -> Option<usize> {
+        None
+    }
+
+    // Devices which are themselves backed by cached storage bypass the block cache
+    fn cacheable(&self) -> bool {
+        true
+    }
+
+    // Devices which are a window onto another device return where a range lies on it, so the
+    // block cache holds each sector once
+    fn translate(
+        &self,
+        _address: usize,
+        _length: usize,
+    ) -> crate::error::Result<Option<(super::DeviceReference, usize)>> {
+        Ok(None)
+    }
 }
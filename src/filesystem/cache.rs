@@ -0,0 +1,393 @@
+use crate::{device::DeviceReference, error, locks::Mutex, logln, process, time};
+use alloc::{collections::BTreeMap, vec::Vec};
+
+struct Block {
+    device: DeviceReference,
+    data: Vec<u8>,
+    dirty: bool,
+    writing: bool,
+    evict: bool,
+    version: usize,
+    last_used: usize,
+}
+
+struct BlockCache {
+    blocks: BTreeMap<(usize, usize), Block>,
+    usage: BTreeMap<usize, (usize, usize)>,
+    tick: usize,
+    writes: usize,
+    evicting: usize,
+}
+
+// A copy of a dirty block being written to its device without the cache locked
+struct Writeback {
+    device: DeviceReference,
+    key: (usize, usize),
+    data: Vec<u8>,
+    version: usize,
+}
+
+pub const BLOCK_SIZE: usize = 512;
+const MAX_BLOCKS: usize = 4096;
+const FLUSH_INTERVAL: usize = 5000; // Milliseconds
+
+// Device I/O is done with the cache unlocked so one slow device does not stall the others
+static BLOCK_CACHE: Mutex<BlockCache> = Mutex::new(BlockCache::new());
+
+// Addresses are in blocks and buffers must be a multiple of the block size
+pub fn read(device: &DeviceReference, address: usize, buffer: &mut [u8]) -> error::Result<()> {
+    let (drive, address) = resolve(device, address, buffer.len())?;
+    let device = &drive;
+
+    let device_lock = device.lock();
+    if !device_lock.cacheable() {
+        return device_lock.read(address, buffer);
+    }
+    drop(device_lock);
+
+    let count = verify_length(buffer.len())?;
+    let key = device_key(device);
+
+    let mut i = 0;
+    while i < count {
+        let mut cache = BLOCK_CACHE.lock();
+
+        // Copy out cached blocks
+        if cache.get(
+            key,
+            address + i,
+            &mut buffer[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE],
+        ) {
+            i += 1;
+            continue;
+        }
+
+        // Read the run of missing blocks with one request
+        let start = i;
+        while i < count && !cache.blocks.contains_key(&(key, address + i)) {
+            i += 1;
+        }
+
+        let writes = cache.writes;
+        drop(cache);
+
+        let run = &mut buffer[start * BLOCK_SIZE..i * BLOCK_SIZE];
+        device.lock().read(address + start, run)?;
+
+        let writebacks = BLOCK_CACHE
+            .lock()
+            .fill(device, key, address + start, run, writes);
+        write_back(writebacks)?;
+    }
+
+    Ok(())
+}
+
+pub fn write(device: &DeviceReference, address: usize, buffer: &[u8]) -> error::Result<()> {
+    let (drive, address) = resolve(device, address, buffer.len())?;
+    let device = &drive;
+
+    let mut device_lock = device.lock();
+    if !device_lock.cacheable() {
+        return device_lock.write(address, buffer);
+    }
+    drop(device_lock);
+
+    let writebacks = BLOCK_CACHE.lock().write(device, address, buffer)?;
+    write_back(writebacks)
+}
+
+pub fn flush(device: &DeviceReference) -> error::Result<()> {
+    let (device, _) = resolve(device, 0, 0)?;
+    flush_blocks(Some(device_key(&device)))
+}
+
+pub fn flush_all() -> error::Result<()> {
+    flush_blocks(None)
+}
+
+pub fn flush_daemon() -> isize {
+    loop {
+        time::sleep(FLUSH_INTERVAL);
+
+        match flush_all() {
+            Ok(()) => {}
+            Err(status) => logln!("Failed to flush block cache: {}", status),
+        }
+    }
+}
+
+fn flush_blocks(device: Option<usize>) -> error::Result<()> {
+    loop {
+        let (writebacks, writing) = BLOCK_CACHE.lock().collect_dirty(device);
+        write_back(writebacks)?;
+
+        // Blocks already being written may have changed since, so wait and flush them again
+        if !writing {
+            return Ok(());
+        }
+
+        process::yield_thread(None);
+    }
+}
+
+// Every writeback is completed even if an earlier one fails, so no block is left marked as writing
+fn write_back(writebacks: Vec<Writeback>) -> error::Result<()> {
+    let mut result = Ok(());
+    for writeback in writebacks {
+        let status = writeback
+            .device
+            .lock()
+            .write(writeback.key.1, writeback.data.as_slice());
+
+        BLOCK_CACHE.lock().complete(&writeback, status.is_ok());
+        if result.is_ok() {
+            result = status;
+        }
+    }
+
+    result
+}
+
+// Blocks are cached under the device that finally holds them, so partitions share blocks with
+// their drive
+fn resolve(
+    device: &DeviceReference,
+    address: usize,
+    length: usize,
+) -> error::Result<(DeviceReference, usize)> {
+    let mut device = device.clone();
+    let mut address = address;
+    loop {
+        let translated = device.lock().translate(address, length)?;
+        match translated {
+            Some((parent, parent_address)) => {
+                device = parent;
+                address = parent_address;
+            }
+            None => return Ok((device, address)),
+        }
+    }
+}
+
+fn device_key(device: &DeviceReference) -> usize {
+    unsafe { device.as_ptr() as *const u8 as usize }
+}
+
+fn verify_length(length: usize) -> error::Result<usize> {
+    if length % BLOCK_SIZE != 0 {
+        Err(error::Status::InvalidArgument)
+    } else {
+        Ok(length / BLOCK_SIZE)
+    }
+}
+
+impl BlockCache {
+    pub const fn new() -> Self {
+        BlockCache {
+            blocks: BTreeMap::new(),
+            usage: BTreeMap::new(),
+            tick: 0,
+            writes: 0,
+            evicting: 0,
+        }
+    }
+
+    // Inserts blocks read from the device, unless a write may have raced with the read
+    fn fill(
+        &mut self,
+        device: &DeviceReference,
+        key: usize,
+        address: usize,
+        buffer: &mut [u8],
+        writes: usize,
+    ) -> Vec<Writeback> {
+        let mut writebacks = Vec::new();
+        for i in 0..buffer.len() / BLOCK_SIZE {
+            let data = &mut buffer[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE];
+
+            // Blocks cached in the meantime are newer than the device
+            if self.get(key, address + i, data) || self.writes != writes {
+                continue;
+            }
+
+            self.insert(
+                device,
+                key,
+                address + i,
+                data.to_vec(),
+                false,
+                &mut writebacks,
+            );
+        }
+
+        writebacks
+    }
+
+    fn write(
+        &mut self,
+        device: &DeviceReference,
+        address: usize,
+        buffer: &[u8],
+    ) -> error::Result<Vec<Writeback>> {
+        let count = verify_length(buffer.len())?;
+        let key = device_key(device);
+        self.writes += 1;
+
+        let mut writebacks = Vec::new();
+        for i in 0..count {
+            let data = &buffer[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE];
+            match self.blocks.get_mut(&(key, address + i)) {
+                Some(block) => {
+                    block.data.copy_from_slice(data);
+                    block.dirty = true;
+                    block.version += 1;
+                    self.touch(key, address + i);
+                }
+                None => self.insert(
+                    device,
+                    key,
+                    address + i,
+                    data.to_vec(),
+                    true,
+                    &mut writebacks,
+                ),
+            }
+        }
+
+        Ok(writebacks)
+    }
+
+    // Returns the dirty blocks to write and whether any were skipped as they are already being written
+    fn collect_dirty(&mut self, device: Option<usize>) -> (Vec<Writeback>, bool) {
+        let mut writebacks = Vec::new();
+        let mut writing = false;
+        for (key, block) in self.blocks.iter_mut() {
+            if !block.dirty || device.map_or(false, |device| device != key.0) {
+                continue;
+            }
+
+            if block.writing {
+                writing = true;
+                continue;
+            }
+
+            block.writing = true;
+            writebacks.push(Writeback {
+                device: block.device.clone(),
+                key: *key,
+                data: block.data.clone(),
+                version: block.version,
+            });
+        }
+
+        (writebacks, writing)
+    }
+
+    // Blocks written while the writeback was in progress stay dirty
+    fn complete(&mut self, writeback: &Writeback, success: bool) {
+        let block = match self.blocks.get_mut(&writeback.key) {
+            Some(block) => block,
+            None => return,
+        };
+
+        let current = success && block.version == writeback.version;
+        block.writing = false;
+        if current {
+            block.dirty = false;
+        }
+
+        if block.evict {
+            block.evict = false;
+            self.evicting -= 1;
+
+            if current {
+                let last_used = block.last_used;
+                self.blocks.remove(&writeback.key);
+                self.usage.remove(&last_used);
+            }
+        }
+    }
+
+    fn get(&mut self, key: usize, address: usize, buffer: &mut [u8]) -> bool {
+        match self.blocks.get(&(key, address)) {
+            Some(block) => buffer.copy_from_slice(block.data.as_slice()),
+            None => return false,
+        }
+
+        self.touch(key, address);
+        true
+    }
+
+    fn touch(&mut self, key: usize, address: usize) {
+        let tick = self.next_tick();
+        let block = self.blocks.get_mut(&(key, address)).unwrap();
+        self.usage.remove(&block.last_used);
+        block.last_used = tick;
+        self.usage.insert(tick, (key, address));
+    }
+
+    fn insert(
+        &mut self,
+        device: &DeviceReference,
+        key: usize,
+        address: usize,
+        data: Vec<u8>,
+        dirty: bool,
+        writebacks: &mut Vec<Writeback>,
+    ) {
+        let tick = self.next_tick();
+        self.blocks.insert(
+            (key, address),
+            Block {
+                device: device.clone(),
+                data,
+                dirty,
+                writing: false,
+                evict: false,
+                version: 0,
+                last_used: tick,
+            },
+        );
+        self.usage.insert(tick, (key, address));
+
+        self.evict(writebacks);
+    }
+
+    // Evicts the least recently used blocks, dirty blocks stay cached until written back
+    fn evict(&mut self, writebacks: &mut Vec<Writeback>) {
+        while self.blocks.len() - self.evicting > MAX_BLOCKS {
+            let victim = self
+                .usage
+                .iter()
+                .map(|(tick, block_key)| (*tick, *block_key))
+                .find(|(_, block_key)| !self.blocks[block_key].writing);
+
+            let (tick, block_key) = match victim {
+                Some(victim) => victim,
+                None => return,
+            };
+
+            let block = self.blocks.get_mut(&block_key).unwrap();
+            if block.dirty {
+                block.writing = true;
+                block.evict = true;
+                self.evicting += 1;
+                writebacks.push(Writeback {
+                    device: block.device.clone(),
+                    key: block_key,
+                    data: block.data.clone(),
+                    version: block.version,
+                });
+            } else {
+                self.blocks.remove(&block_key);
+                self.usage.remove(&tick);
+            }
+        }
+    }
+
+    fn next_tick(&mut self) -> usize {
+        self.tick += 1;
+        self.tick
+    }
+}
@@ -1,6 +1,6 @@
-use super::{DirectoryReference, Entry};
+use super::{DirectoryReference, Entry, Watcher};
 use crate::map::{Mappable, INVALID_ID};
-use alloc::string::String;
+use alloc::{string::String, sync::Arc};
 
 #[derive(Clone)]
 pub struct Descriptor {
@@ -27,6 +27,12 @@ impl Descriptor {
         self.directory.lock().construct_path_name()
     }
 
+    pub fn watch(&self) -> Arc<Watcher> {
+        let watcher = Watcher::new();
+        self.directory.lock().add_watcher(&watcher);
+        watcher
+    }
+
     pub fn next(&mut self) -> Option<Entry> {
         let directory = self.directory.lock();
 
@@ -1,5 +1,6 @@
 use crate::filesystem::{File, Metadata};
 use alloc::{boxed::Box, string::String, vec::Vec};
+use core::any::Any;
 
 pub trait Directory: Send {
     fn get_children(&self) -> crate::error::Result<Vec<(String, Metadata)>>; // Used to get sub files on initialization
@@ -9,6 +10,23 @@ pub trait Directory: Send {
     fn make_directory(&self, directory_name: &str) -> crate::error::Result<()>;
     fn rename_file(&self, old_name: &str, new_name: &str) -> crate::error::Result<()>;
     fn rename_directory(&self, old_name: &str, new_name: &str) -> crate::error::Result<()>;
+    fn move_entry(
+        &self,
+        name: &str,
+        new_directory: &dyn Directory,
+        new_name: &str,
+    ) -> crate::error::Result<()>; // Both directories are on the same filesystem
     fn remove(&self, name: &str) -> crate::error::Result<()>;
     fn update_metadata(&self, name: &str, new_metadata: Metadata) -> crate::error::Result<()>;
+    fn as_any(&self) -> &dyn Any;
+
+    // Writes anything held by the driver out to its device, called on the root to flush a filesystem
+    fn flush(&self) -> crate::error::Result<()> {
+        Ok(())
+    }
+
+    // Returns the total and free bytes of the filesystem, called on the root
+    fn get_space(&self) -> crate::error::Result<(usize, usize)> {
+        Ok((0, 0))
+    }
 }
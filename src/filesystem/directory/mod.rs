@@ -3,9 +3,11 @@ mod entry;
 mod inner;
 mod owner;
 mod reference;
+mod watch;
 
 pub use descriptor::*;
 pub use entry::*;
 pub use inner::*;
 pub use owner::*;
 pub use reference::*;
+pub use watch::*;
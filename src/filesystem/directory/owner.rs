@@ -1,9 +1,17 @@
-use super::{inner::Directory, DirectoryReference};
+use super::{inner::Directory, watch::*, DirectoryReference};
 use crate::{
-    filesystem::{FileOwner, FileReference, Metadata},
+    filesystem::{mount, FileOwner, FileReference, Metadata, ATTRIBUTE_FIFO},
     locks::Mutex,
+    logln,
+};
+use alloc::{
+    borrow::ToOwned,
+    boxed::Box,
+    format,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
 };
-use alloc::{borrow::ToOwned, boxed::Box, format, string::String, vec::Vec};
 use core::ffi::c_void;
 
 pub enum Parent {
@@ -11,6 +19,7 @@ pub enum Parent {
     Other(DirectoryReference),
 }
 
+#[derive(Clone)]
 pub enum Child {
     File(FileReference),
     Directory(DirectoryReference),
@@ -21,6 +30,7 @@ pub struct DirectoryOwner {
     directory: Box<dyn Directory>,
     children: Vec<(String, Metadata, Option<Child>)>,
     references: usize,
+    watchers: Vec<Weak<Watcher>>,
 }
 
 impl DirectoryOwner {
@@ -36,6 +46,7 @@ impl DirectoryOwner {
             directory,
             children,
             references: 0,
+            watchers: Vec::new(),
         })
     }
 
@@ -82,6 +93,24 @@ impl DirectoryOwner {
         &mut self,
         ptr: *const FileOwner,
         new_metadata: Metadata,
+    ) -> crate::error::Result<()> {
+        self.set_child_metadata(ptr, new_metadata, true)
+    }
+
+    // Only the cached metadata is changed, it is stored by a later update
+    pub fn cache_metadata(
+        &mut self,
+        ptr: *const FileOwner,
+        new_metadata: Metadata,
+    ) -> crate::error::Result<()> {
+        self.set_child_metadata(ptr, new_metadata, false)
+    }
+
+    fn set_child_metadata(
+        &mut self,
+        ptr: *const FileOwner,
+        new_metadata: Metadata,
+        store: bool,
     ) -> crate::error::Result<()> {
         for (name, metadata, child) in &mut self.children {
             match child {
@@ -89,15 +118,23 @@ impl DirectoryOwner {
                 Some(child) => match child {
                     Child::File(file) => {
                         if file.matching_data(ptr as *const _) {
-                            self.directory.update_metadata(name, new_metadata.clone())?;
+                            if store {
+                                self.directory.update_metadata(name, new_metadata.clone())?;
+                            }
                             *metadata = new_metadata;
+                            let name = name.clone();
+                            self.notify(WATCH_EVENT_MODIFIED, &name, "");
                             return Ok(());
                         }
                     }
                     Child::Directory(dir) => {
                         if dir.matching_data(ptr as *const _) {
-                            self.directory.update_metadata(name, new_metadata.clone())?;
+                            if store {
+                                self.directory.update_metadata(name, new_metadata.clone())?;
+                            }
                             *metadata = new_metadata;
+                            let name = name.clone();
+                            self.notify(WATCH_EVENT_MODIFIED, &name, "");
                             return Ok(());
                         }
                     }
@@ -112,6 +149,17 @@ impl DirectoryOwner {
         self.parent = Parent::Root(number);
     }
 
+    pub fn get_drive_number(&self) -> isize {
+        match &self.parent {
+            Parent::Root(number) => *number,
+            Parent::Other(parent) => parent.lock().get_drive_number(),
+        }
+    }
+
+    pub fn set_parent(&mut self, parent: DirectoryReference) {
+        self.parent = Parent::Other(parent);
+    }
+
     pub fn get_name(&self, ptr: *const c_void) -> &str {
         for (name, _, child) in &self.children {
             match child {
@@ -135,15 +183,33 @@ impl DirectoryOwner {
     }
 
     pub fn construct_path_name(&self) -> String {
+        match &self.parent {
+            Parent::Root(fs_number) => match mount::get_mount_path(*fs_number) {
+                Some(mount_path) => mount_path,
+                None => format!(":{}", fs_number),
+            },
+            Parent::Other(parent_lock) => {
+                let parent = parent_lock.lock();
+                let mut path = parent.construct_path_name();
+                if !path.ends_with('/') {
+                    path.push('/');
+                }
+                path.push_str(parent.get_name(self as *const _ as *const _));
+                path
+            }
+        }
+    }
+
+    // Unlike the path name, this does not change as the filesystem is mounted and unmounted
+    pub fn construct_filesystem_path(&self) -> String {
         match &self.parent {
             Parent::Root(fs_number) => format!(":{}", fs_number),
             Parent::Other(parent_lock) => {
                 let parent = parent_lock.lock();
-                format!(
-                    "{}/{}",
-                    parent.construct_path_name(),
-                    parent.get_name(self as *const _ as *const _)
-                )
+                let mut path = parent.construct_filesystem_path();
+                path.push('/');
+                path.push_str(parent.get_name(self as *const _ as *const _));
+                path
             }
         }
     }
@@ -293,6 +359,34 @@ impl DirectoryOwner {
         }
     }
 
+    pub fn add_watcher(&mut self, watcher: &Arc<Watcher>) {
+        self.watchers.push(Arc::downgrade(watcher));
+    }
+
+    // Watchers which have been closed are dropped here
+    fn notify(&mut self, kind: usize, name: &str, new_name: &str) {
+        self.watchers.retain(|watcher| match watcher.upgrade() {
+            Some(watcher) => {
+                watcher.push(WatchEvent::new(kind, name, new_name));
+                true
+            }
+            None => false,
+        });
+    }
+
+    // Open children hold a reference, so this covers anything opened beneath the directory
+    pub fn is_open(&self) -> bool {
+        self.references > 0
+    }
+
+    pub fn flush(&self) -> crate::error::Result<()> {
+        self.directory.flush()
+    }
+
+    pub fn get_space(&self) -> crate::error::Result<(usize, usize)> {
+        self.directory.get_space()
+    }
+
     pub fn close(&mut self, arc_ptr: *const Mutex<DirectoryOwner>) {
         self.references -= 1;
         if self.references == 0 {
@@ -314,6 +408,12 @@ impl DirectoryOwner {
     }
 
     pub fn remove(&mut self, name: &str) -> crate::error::Result<()> {
+        self.remove_entry(name)?;
+        self.notify(WATCH_EVENT_REMOVED, name, "");
+        Ok(())
+    }
+
+    fn remove_entry(&mut self, name: &str) -> crate::error::Result<()> {
         let mut status = crate::error::Status::NoEntry;
         let directory = &self.directory;
         self.children.retain(|(sub_name, metadata, child)| -> bool {
@@ -369,6 +469,193 @@ impl DirectoryOwner {
         }
     }
 
+    pub fn get_child_reference(&self, name: &str) -> crate::error::Result<Option<Child>> {
+        for (sub_name, _, child) in &self.children {
+            if sub_name == name {
+                return Ok(child.clone());
+            }
+        }
+
+        Err(crate::error::Status::NoEntry)
+    }
+
+    pub fn rename(
+        &mut self,
+        old_name: &str,
+        new_name: &str,
+        replace: bool,
+    ) -> crate::error::Result<()> {
+        let is_directory = self.get_metadata(old_name)?.is_directory();
+        if old_name == new_name {
+            return Ok(());
+        }
+
+        // An existing target is only removed once the rename succeeds
+        let aside = self.set_aside_target(new_name, is_directory, replace)?;
+        if let Err(status) = self.rename_entry(old_name, new_name, is_directory) {
+            self.restore_target(aside, new_name, is_directory);
+            return Err(status);
+        }
+
+        self.remove_target(aside, new_name);
+        self.notify(WATCH_EVENT_RENAMED, old_name, new_name);
+        Ok(())
+    }
+
+    fn rename_entry(
+        &mut self,
+        old_name: &str,
+        new_name: &str,
+        is_directory: bool,
+    ) -> crate::error::Result<()> {
+        // Rename on disk
+        if is_directory {
+            self.directory.rename_directory(old_name, new_name)?;
+        } else {
+            self.directory.rename_file(old_name, new_name)?;
+        }
+
+        // Rename the cached entry
+        for (sub_name, _, _) in &mut self.children {
+            if sub_name == old_name {
+                *sub_name = new_name.to_owned();
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn move_child(
+        &mut self,
+        name: &str,
+        open: bool,
+        new_parent: &mut DirectoryOwner,
+        new_name: &str,
+        replace: bool,
+    ) -> crate::error::Result<()> {
+        let index = match self
+            .children
+            .iter()
+            .position(|(sub_name, _, _)| sub_name == name)
+        {
+            Some(index) => index,
+            None => return Err(crate::error::Status::NoEntry),
+        };
+
+        // The child was opened or closed since the caller looked at it
+        if self.children[index].2.is_some() != open {
+            return Err(crate::error::Status::TryAgain);
+        }
+
+        // An existing target is only removed once the move succeeds
+        let is_directory = self.children[index].1.is_directory();
+        let aside = new_parent.set_aside_target(new_name, is_directory, replace)?;
+
+        // Move on disk
+        if let Err(status) =
+            self.directory
+                .move_entry(name, new_parent.directory.as_ref(), new_name)
+        {
+            new_parent.restore_target(aside, new_name, is_directory);
+            return Err(status);
+        }
+
+        // Move the cached entry
+        let (_, metadata, child) = self.children.remove(index);
+        if child.is_some() {
+            self.references -= 1;
+            new_parent.references += 1;
+        }
+        new_parent
+            .children
+            .push((new_name.to_owned(), metadata, child));
+
+        new_parent.remove_target(aside, new_name);
+        self.notify(WATCH_EVENT_REMOVED, name, "");
+        new_parent.notify(WATCH_EVENT_CREATED, new_name, "");
+        Ok(())
+    }
+
+    // Renames an existing target to a temporary name, returning it so the target can be restored
+    fn set_aside_target(
+        &mut self,
+        name: &str,
+        is_directory: bool,
+        replace: bool,
+    ) -> crate::error::Result<Option<String>> {
+        let metadata = match self.get_metadata(name) {
+            Ok(metadata) => metadata,
+            Err(crate::error::Status::NoEntry) => return Ok(None),
+            Err(status) => return Err(status),
+        };
+
+        if !replace {
+            return Err(crate::error::Status::Exists);
+        }
+
+        if metadata.is_directory() != is_directory {
+            return Err(if is_directory {
+                crate::error::Status::NotDirectory
+            } else {
+                crate::error::Status::IsDirectory
+            });
+        }
+
+        // Verify the target can be removed before anything is changed
+        if self.get_child_reference(name)?.is_some() {
+            return Err(crate::error::Status::InUse);
+        }
+
+        if is_directory && self.directory.open_directory(name)?.get_children()?.len() != 0 {
+            return Err(crate::error::Status::NotEmpty);
+        }
+
+        let mut index = 0;
+        let temporary_name = loop {
+            let temporary_name = format!("~rename{}", index);
+            if !self
+                .children
+                .iter()
+                .any(|(sub_name, _, _)| *sub_name == temporary_name)
+            {
+                break temporary_name;
+            }
+            index += 1;
+        };
+
+        self.rename_entry(name, &temporary_name, is_directory)?;
+        Ok(Some(temporary_name))
+    }
+
+    fn restore_target(&mut self, aside: Option<String>, name: &str, is_directory: bool) {
+        if let Some(temporary_name) = aside {
+            if let Err(status) = self.rename_entry(&temporary_name, name, is_directory) {
+                logln!(
+                    "Failed to restore \"{}\" from \"{}\": {}",
+                    name,
+                    temporary_name,
+                    status
+                );
+            }
+        }
+    }
+
+    // The rename has already happened, so a replaced target that cannot be removed is left behind
+    fn remove_target(&mut self, aside: Option<String>, name: &str) {
+        if let Some(temporary_name) = aside {
+            match self.remove_entry(&temporary_name) {
+                Ok(()) => self.notify(WATCH_EVENT_REMOVED, name, ""),
+                Err(status) => logln!(
+                    "Failed to remove replaced \"{}\" left as \"{}\": {}",
+                    name,
+                    temporary_name,
+                    status
+                ),
+            }
+        }
+    }
+
     pub fn create_file(&mut self, filename: &str) -> crate::error::Result<()> {
         // Verify file does not exist
         for (sub_name, _, _) in &self.children {
@@ -380,7 +667,35 @@ impl DirectoryOwner {
         // Create the file
         self.directory.make_file(filename)?;
         self.children
-            .push((filename.to_owned(), Metadata::new(0, false), None));
+            .push((filename.to_owned(), Metadata::new_now(0, false), None));
+        self.notify(WATCH_EVENT_CREATED, filename, "");
+        Ok(())
+    }
+
+    // Named pipes are empty files marked with the FIFO attribute
+    pub fn create_fifo(&mut self, name: &str) -> crate::error::Result<()> {
+        self.create_file(name)?;
+
+        let mut metadata = self.get_metadata(name)?;
+        metadata.set_attributes(metadata.attributes() | ATTRIBUTE_FIFO);
+        if let Err(status) = self.directory.update_metadata(name, metadata.clone()) {
+            if let Err(status) = self.remove_entry(name) {
+                logln!(
+                    "Failed to remove unmarked named pipe \"{}\": {}",
+                    name,
+                    status
+                );
+            }
+            return Err(status);
+        }
+
+        for (sub_name, sub_metadata, _) in &mut self.children {
+            if sub_name == name {
+                *sub_metadata = metadata;
+                break;
+            }
+        }
+
         Ok(())
     }
 
@@ -395,7 +710,8 @@ impl DirectoryOwner {
         // Create the directory
         self.directory.make_directory(directory_name)?;
         self.children
-            .push((directory_name.to_owned(), Metadata::new(0, true), None));
+            .push((directory_name.to_owned(), Metadata::new_now(0, true), None));
+        self.notify(WATCH_EVENT_CREATED, directory_name, "");
         Ok(())
     }
 }
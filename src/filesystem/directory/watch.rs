@@ -0,0 +1,102 @@
+use crate::{
+    locks::Mutex,
+    process::{self, ThreadQueue},
+};
+use alloc::{collections::VecDeque, sync::Arc};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+#[repr(C)]
+#[derive(Clone)]
+pub struct WatchEvent {
+    kind: usize,
+    name: [u8; 256],
+    new_name: [u8; 256],
+}
+
+pub const WATCH_EVENT_CREATED: usize = 0;
+pub const WATCH_EVENT_REMOVED: usize = 1;
+pub const WATCH_EVENT_RENAMED: usize = 2;
+pub const WATCH_EVENT_MODIFIED: usize = 3;
+pub const WATCH_EVENT_OVERFLOW: usize = 4;
+
+// Events past the limit are dropped and replaced by a single overflow event
+const MAX_EVENTS: usize = 256;
+
+// Whether events are pending is kept outside the lock so readers can check it before sleeping
+pub struct Watcher {
+    events: Mutex<VecDeque<WatchEvent>>,
+    pending: AtomicBool,
+    queue: ThreadQueue,
+}
+
+impl WatchEvent {
+    pub fn new(kind: usize, name: &str, new_name: &str) -> Self {
+        let mut event = WatchEvent {
+            kind,
+            name: [0; 256],
+            new_name: [0; 256],
+        };
+
+        copy_string(&mut event.name, name);
+        copy_string(&mut event.new_name, new_name);
+        event
+    }
+}
+
+impl Watcher {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Watcher {
+            events: Mutex::new(VecDeque::new()),
+            pending: AtomicBool::new(false),
+            queue: ThreadQueue::new(),
+        })
+    }
+
+    pub fn push(&self, event: WatchEvent) {
+        let mut events = self.events.lock();
+        if events.len() < MAX_EVENTS - 1 {
+            events.push_back(event);
+        } else if events.len() == MAX_EVENTS - 1 {
+            events.push_back(WatchEvent::new(WATCH_EVENT_OVERFLOW, "", ""));
+        }
+        self.pending.store(events.len() > 0, Ordering::Release);
+        drop(events);
+
+        while let Some(thread) = self.queue.pop() {
+            process::queue_thread(thread)
+        }
+    }
+
+    // Blocks until at least one event is available
+    pub fn read(&self, buffer: &mut [WatchEvent]) -> usize {
+        if buffer.len() == 0 {
+            return 0;
+        }
+
+        loop {
+            let mut events = self.events.lock();
+            if events.len() > 0 {
+                let mut count = 0;
+                while count < buffer.len() {
+                    match events.pop_front() {
+                        Some(event) => buffer[count] = event,
+                        None => break,
+                    }
+                    count += 1;
+                }
+
+                self.pending.store(events.len() > 0, Ordering::Release);
+                return count;
+            }
+
+            let current_queue = Some(self.queue.into_current_queue());
+            drop(events);
+            process::yield_thread_unless(current_queue, &|| self.pending.load(Ordering::Acquire));
+        }
+    }
+}
+
+fn copy_string(destination: &mut [u8], string: &str) {
+    let length = core::cmp::min(string.len(), destination.len() - 1);
+    destination[..length].copy_from_slice(&string.as_bytes()[..length]);
+}
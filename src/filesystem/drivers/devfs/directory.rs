@@ -0,0 +1,108 @@
+use crate::{
+    device, error,
+    filesystem::{self, File, Metadata},
+};
+use alloc::{borrow::ToOwned, boxed::Box, format, string::String, vec::Vec};
+use core::any::Any;
+
+// A container in the device tree, containers with children are directories
+pub struct Directory {
+    path: String,
+}
+
+// The device of a container with children is found inside its directory
+const DEVICE_NODE: &str = "device";
+
+impl Directory {
+    pub fn new(path: String) -> Self {
+        Directory { path }
+    }
+
+    // Returns the path of a child and whether it is a directory
+    fn find(&self, name: &str) -> error::Result<(String, bool)> {
+        let path = format!("{}/{}", self.path, name);
+        match device::get_children(&path) {
+            Ok(children) => Ok((path, !children.is_empty())),
+            Err(error::Status::NoDevice) if name == DEVICE_NODE && self.has_device() => {
+                Ok((self.path.clone(), false))
+            }
+            Err(error::Status::NoDevice) => Err(error::Status::NoEntry),
+            Err(status) => Err(status),
+        }
+    }
+
+    // The root and containers only grouping other devices have no device of their own
+    fn has_device(&self) -> bool {
+        self.path.len() > 0 && device::get_device(&self.path).is_ok()
+    }
+}
+
+impl filesystem::Directory for Directory {
+    fn get_children(&self) -> error::Result<Vec<(String, Metadata)>> {
+        let mut children = Vec::new();
+        for name in device::get_children(&self.path)? {
+            let (_, is_directory) = self.find(&name)?;
+            children.push((name, Metadata::new(0, is_directory, 0, 0, 0, 0)));
+        }
+
+        if self.has_device() && !children.iter().any(|(name, _)| name == DEVICE_NODE) {
+            children.push((DEVICE_NODE.to_owned(), Metadata::new(0, false, 0, 0, 0, 0)));
+        }
+
+        Ok(children)
+    }
+
+    fn open_file(&self, filename: &str) -> error::Result<Box<dyn File>> {
+        let (path, is_directory) = self.find(filename)?;
+        if is_directory {
+            return Err(error::Status::IsDirectory);
+        }
+
+        Ok(Box::new(super::file::File::new(device::get_device(&path)?)))
+    }
+
+    fn open_directory(
+        &self,
+        directory_name: &str,
+    ) -> error::Result<Box<dyn filesystem::Directory>> {
+        let (path, is_directory) = self.find(directory_name)?;
+        if !is_directory {
+            return Err(error::Status::IsFile);
+        }
+
+        Ok(Box::new(Directory::new(path)))
+    }
+
+    fn make_file(&self, _: &str) -> error::Result<()> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn make_directory(&self, _: &str) -> error::Result<()> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn rename_file(&self, _: &str, _: &str) -> error::Result<()> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn rename_directory(&self, _: &str, _: &str) -> error::Result<()> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn move_entry(&self, _: &str, _: &dyn filesystem::Directory, _: &str) -> error::Result<()> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn remove(&self, _: &str) -> error::Result<()> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn update_metadata(&self, _: &str, _: Metadata) -> error::Result<()> {
+        // Devices have no metadata to store
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
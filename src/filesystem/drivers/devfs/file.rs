@@ -0,0 +1,126 @@
+use crate::{device::DeviceReference, error, filesystem::cache};
+use alloc::vec;
+
+// Block devices are addressed in bytes, other devices are passed offsets unchanged
+pub struct File {
+    device: DeviceReference,
+    sector_size: Option<usize>,
+}
+
+impl File {
+    pub fn new(device: DeviceReference) -> Self {
+        let sector_size = device.lock().sector_size();
+        File {
+            device,
+            sector_size,
+        }
+    }
+
+    fn size(&self) -> error::Result<usize> {
+        self.device.lock().ioctrl(0, 0)
+    }
+
+    // Devices with block sized sectors share the block cache with mounted filesystems
+    fn read_sectors(&self, sector: usize, buffer: &mut [u8]) -> error::Result<()> {
+        if self.sector_size == Some(cache::BLOCK_SIZE) {
+            cache::read(&self.device, sector, buffer)
+        } else {
+            self.device.lock().read(sector, buffer)
+        }
+    }
+
+    fn write_sectors(&self, sector: usize, buffer: &[u8]) -> error::Result<()> {
+        if self.sector_size == Some(cache::BLOCK_SIZE) {
+            cache::write(&self.device, sector, buffer)
+        } else {
+            self.device.lock().write(sector, buffer)
+        }
+    }
+}
+
+impl crate::filesystem::File for File {
+    fn write(&mut self, offset: usize, buffer: &[u8]) -> error::Result<isize> {
+        let sector_size = match self.sector_size {
+            Some(sector_size) => sector_size,
+            None => {
+                self.device.lock().write(offset, buffer)?;
+                return Ok(buffer.len() as isize);
+            }
+        };
+
+        let size = self.size()?;
+        if offset >= size {
+            return Err(error::Status::NoSpace);
+        }
+
+        let length = core::cmp::min(buffer.len(), size - offset);
+        let first_sector = offset / sector_size;
+        let end_sector = (offset + length + sector_size - 1) / sector_size;
+        if offset % sector_size == 0 && length % sector_size == 0 {
+            self.write_sectors(first_sector, &buffer[..length])?;
+            return Ok(length as isize);
+        }
+
+        // Partial sectors are read, modified and written back
+        let mut bounce = vec![0u8; (end_sector - first_sector) * sector_size];
+        self.read_sectors(first_sector, &mut bounce)?;
+        let start = offset - first_sector * sector_size;
+        bounce[start..start + length].copy_from_slice(&buffer[..length]);
+        self.write_sectors(first_sector, &bounce)?;
+
+        Ok(length as isize)
+    }
+
+    fn read(&mut self, offset: usize, buffer: &mut [u8]) -> error::Result<isize> {
+        let sector_size = match self.sector_size {
+            Some(sector_size) => sector_size,
+            None => {
+                self.device.lock().read(offset, buffer)?;
+                return Ok(buffer.len() as isize);
+            }
+        };
+
+        let size = self.size()?;
+        if offset >= size {
+            return Ok(-1);
+        }
+
+        let length = core::cmp::min(buffer.len(), size - offset);
+        let first_sector = offset / sector_size;
+        let end_sector = (offset + length + sector_size - 1) / sector_size;
+        if offset % sector_size == 0 && length % sector_size == 0 {
+            self.read_sectors(first_sector, &mut buffer[..length])?;
+        } else {
+            let mut bounce = vec![0u8; (end_sector - first_sector) * sector_size];
+            self.read_sectors(first_sector, &mut bounce)?;
+            let start = offset - first_sector * sector_size;
+            buffer[..length].copy_from_slice(&bounce[start..start + length]);
+        }
+
+        for byte in &mut buffer[length..] {
+            *byte = 0;
+        }
+
+        Ok(length as isize)
+    }
+
+    fn set_length(&mut self, _: usize) -> error::Result<()> {
+        // Devices have a fixed length, writes go straight to the device
+        Ok(())
+    }
+
+    fn get_length(&self) -> usize {
+        match self.sector_size {
+            Some(_) => self.size().unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    // Only the size of block devices is exposed, other codes control the hardware directly
+    fn ioctrl(&mut self, code: usize, _: usize) -> error::Result<usize> {
+        match (code, self.sector_size) {
+            (0, Some(_)) => self.size(),
+            _ => Err(error::Status::InvalidIOCtrl),
+        }
+    }
+}
@@ -0,0 +1,13 @@
+use crate::filesystem::FilesystemStarter;
+use alloc::{boxed::Box, string::String};
+
+mod directory;
+mod file;
+
+pub fn create_devfs_filesystem(volume_name: String) -> FilesystemStarter {
+    FilesystemStarter::new(
+        Box::new(directory::Directory::new(String::new())),
+        volume_name,
+        "devfs",
+    )
+}
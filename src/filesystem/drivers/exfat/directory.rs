@@ -0,0 +1,621 @@
+use super::volume::{
+    read_u16, read_u32, read_u64, write_u16, write_u32, write_u64, Stream, StreamBox, Volume,
+    VolumeBox,
+};
+use crate::{
+    error,
+    filesystem::{self, File, Metadata},
+    time,
+};
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use core::any::Any;
+
+pub struct Directory {
+    stream: StreamBox,
+    volume: VolumeBox,
+}
+
+// A file entry followed by its stream extension and name entries
+struct EntrySet {
+    index: usize,
+    data: Vec<u8>,
+    name: String,
+}
+
+pub const ENTRY_SIZE: usize = 32;
+const NAME_CHARACTERS: usize = 15;
+const MAX_NAME_LENGTH: usize = 255;
+
+pub const ENTRY_END: u8 = 0x00;
+const ENTRY_IN_USE: u8 = 0x80;
+pub const ENTRY_BITMAP: u8 = 0x81;
+pub const ENTRY_UPCASE_TABLE: u8 = 0x82;
+pub const ENTRY_VOLUME_LABEL: u8 = 0x83;
+const ENTRY_FILE: u8 = 0x85;
+const ENTRY_STREAM: u8 = 0xC0;
+const ENTRY_NAME: u8 = 0xC1;
+
+const ATTRIBUTE_DIRECTORY: u16 = 0x10;
+
+// exFAT attribute bits share their values with the filesystem attributes
+const ATTRIBUTE_METADATA_MASK: u16 = (filesystem::ATTRIBUTE_READ_ONLY
+    | filesystem::ATTRIBUTE_HIDDEN
+    | filesystem::ATTRIBUTE_SYSTEM
+    | filesystem::ATTRIBUTE_ARCHIVE
+    | filesystem::ATTRIBUTE_FIFO) as u16;
+
+const STREAM_ALLOCATION_POSSIBLE: u8 = 0x01;
+const STREAM_NO_FAT_CHAIN: u8 = 0x02;
+
+impl Directory {
+    pub fn new(stream: StreamBox, volume: VolumeBox) -> Self {
+        Directory { stream, volume }
+    }
+
+    fn cluster(&self) -> u32 {
+        self.stream.lock().first_cluster
+    }
+
+    fn read_sets(&self, volume: &Volume) -> error::Result<Vec<EntrySet>> {
+        let stream = self.stream.lock().clone();
+        let (_, data) = volume.read_stream(&stream)?;
+        Ok(parse_sets(&data))
+    }
+
+    fn find(&self, volume: &Volume, name: &str) -> error::Result<EntrySet> {
+        let stream = self.stream.lock().clone();
+        let (_, data) = volume.read_stream(&stream)?;
+        find_set(volume, &data, name)
+    }
+
+    // Writes an entry set into the first free run of entries, growing the directory if needed
+    fn add_set(&self, volume: &mut Volume, set: &[u8]) -> error::Result<()> {
+        let count = set.len() / ENTRY_SIZE;
+        loop {
+            let mut stream = self.stream.lock().clone();
+            let (mut chain, data) = volume.read_stream(&stream)?;
+
+            let mut run = 0;
+            for index in 0..data.len() / ENTRY_SIZE {
+                if data[index * ENTRY_SIZE] & ENTRY_IN_USE != 0 {
+                    run = 0;
+                    continue;
+                }
+
+                run += 1;
+                if run == count {
+                    return write_set(volume, &chain, index + 1 - count, set);
+                }
+            }
+
+            let cluster_count = chain.len() + 1;
+            volume.extend_chain(&mut stream, &mut chain, cluster_count)?;
+            stream.length = (cluster_count * volume.cluster_size()) as u64;
+            stream.valid_length = stream.length;
+            *self.stream.lock() = stream;
+            write_stream(volume, &self.stream)?;
+        }
+    }
+
+    fn remove_set(&self, volume: &Volume, set: &EntrySet) -> error::Result<()> {
+        let stream = self.stream.lock().clone();
+        let chain = volume.get_chain(&stream)?;
+
+        // Clearing the in use bit frees every entry of the set
+        let mut data = set.data.clone();
+        for entry in data.chunks_exact_mut(ENTRY_SIZE) {
+            entry[0] &= !ENTRY_IN_USE;
+        }
+
+        write_set(volume, &chain, set.index, &data)
+    }
+
+    fn update_set(
+        &self,
+        volume: &Volume,
+        name: &str,
+        update: impl FnOnce(&mut [u8]),
+    ) -> error::Result<()> {
+        let mut set = self.find(volume, name)?;
+        update(&mut set.data);
+        let checksum = set_checksum(&set.data);
+        write_u16(&mut set.data, 2, checksum);
+
+        let stream = self.stream.lock().clone();
+        let chain = volume.get_chain(&stream)?;
+        write_set(volume, &chain, set.index, &set.data)
+    }
+
+    fn verify_new_name(&self, volume: &Volume, name: &str) -> error::Result<()> {
+        verify_name(name)?;
+        match self.find(volume, name) {
+            Ok(_) => Err(error::Status::Exists),
+            Err(error::Status::NoEntry) => Ok(()),
+            Err(status) => Err(status),
+        }
+    }
+
+    fn rename(&self, old_name: &str, new_name: &str, directory: bool) -> error::Result<()> {
+        let mut volume = self.volume.lock();
+        let set = self.find(&volume, old_name)?;
+        if directory && !set.is_directory() {
+            return Err(error::Status::IsFile);
+        } else if !directory && set.is_directory() {
+            return Err(error::Status::IsDirectory);
+        }
+
+        // Changing only the case of a name finds the entry being renamed
+        verify_name(new_name)?;
+        match self.find(&volume, new_name) {
+            Ok(existing) if existing.index != set.index => return Err(error::Status::Exists),
+            Ok(_) | Err(error::Status::NoEntry) => {}
+            Err(status) => return Err(status),
+        }
+
+        // Adding first never loses the entry, the old set keeps its place until removed
+        let new_set = named_set(&volume, &set.data[..2 * ENTRY_SIZE], new_name);
+        self.add_set(&mut volume, &new_set)?;
+        self.remove_set(&volume, &set)?;
+
+        let cluster = self.cluster();
+        volume.move_stream(cluster, &set.name, cluster, new_name);
+        Ok(())
+    }
+}
+
+impl filesystem::Directory for Directory {
+    fn get_children(&self) -> error::Result<Vec<(String, Metadata)>> {
+        let volume = self.volume.lock();
+        Ok(self
+            .read_sets(&volume)?
+            .into_iter()
+            .map(|set| {
+                let metadata = set.metadata();
+                (set.name, metadata)
+            })
+            .collect())
+    }
+
+    fn open_file(&self, filename: &str) -> error::Result<Box<dyn File>> {
+        let mut volume = self.volume.lock();
+        let set = self.find(&volume, filename)?;
+        if set.is_directory() {
+            return Err(error::Status::IsDirectory);
+        }
+
+        let stream = volume.open_stream(self.cluster(), &set.name, set.stream());
+        Ok(Box::new(super::file::File::new(
+            stream,
+            self.volume.clone(),
+        )))
+    }
+
+    fn open_directory(
+        &self,
+        directory_name: &str,
+    ) -> error::Result<Box<dyn filesystem::Directory>> {
+        let mut volume = self.volume.lock();
+        let set = self.find(&volume, directory_name)?;
+        if !set.is_directory() {
+            return Err(error::Status::IsFile);
+        }
+
+        let stream = volume.open_stream(self.cluster(), &set.name, set.stream());
+        Ok(Box::new(Directory::new(stream, self.volume.clone())))
+    }
+
+    fn make_file(&self, filename: &str) -> error::Result<()> {
+        let mut volume = self.volume.lock();
+        self.verify_new_name(&volume, filename)?;
+
+        // Empty files have no clusters
+        let set = new_set(
+            &volume,
+            filename,
+            filesystem::ATTRIBUTE_ARCHIVE as u16,
+            &Stream::new(0, false, 0, 0),
+        );
+        self.add_set(&mut volume, &set)
+    }
+
+    fn make_directory(&self, directory_name: &str) -> error::Result<()> {
+        let mut volume = self.volume.lock();
+        self.verify_new_name(&volume, directory_name)?;
+
+        // Directories always have a cluster, a zeroed one has no entries
+        let mut stream = Stream::new(0, false, 0, 0);
+        let mut chain = Vec::new();
+        volume.extend_chain(&mut stream, &mut chain, 1)?;
+        stream.length = volume.cluster_size() as u64;
+        stream.valid_length = stream.length;
+
+        let set = new_set(&volume, directory_name, ATTRIBUTE_DIRECTORY, &stream);
+        match self.add_set(&mut volume, &set) {
+            Ok(()) => Ok(()),
+            Err(status) => {
+                volume.truncate_chain(&mut stream, &mut chain, 0)?;
+                Err(status)
+            }
+        }
+    }
+
+    fn rename_file(&self, old_name: &str, new_name: &str) -> error::Result<()> {
+        self.rename(old_name, new_name, false)
+    }
+
+    fn rename_directory(&self, old_name: &str, new_name: &str) -> error::Result<()> {
+        self.rename(old_name, new_name, true)
+    }
+
+    fn move_entry(
+        &self,
+        name: &str,
+        new_directory: &dyn filesystem::Directory,
+        new_name: &str,
+    ) -> error::Result<()> {
+        let new_directory = match new_directory.as_any().downcast_ref::<Directory>() {
+            Some(new_directory) if Arc::ptr_eq(&self.volume, &new_directory.volume) => {
+                new_directory
+            }
+            _ => return Err(error::Status::NotSupported),
+        };
+
+        if Arc::ptr_eq(&self.stream, &new_directory.stream) {
+            let directory = self.find(&self.volume.lock(), name)?.is_directory();
+            return self.rename(name, new_name, directory);
+        }
+
+        let mut volume = self.volume.lock();
+        let set = self.find(&volume, name)?;
+        new_directory.verify_new_name(&volume, new_name)?;
+
+        // exFAT directories have no ".." entry, so only the entry set moves
+        let new_set = named_set(&volume, &set.data[..2 * ENTRY_SIZE], new_name);
+        new_directory.add_set(&mut volume, &new_set)?;
+        self.remove_set(&volume, &set)?;
+
+        volume.move_stream(self.cluster(), &set.name, new_directory.cluster(), new_name);
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> error::Result<()> {
+        let mut volume = self.volume.lock();
+        let set = self.find(&volume, name)?;
+
+        let mut stream = set.stream();
+        if set.is_directory() {
+            let (_, data) = volume.read_stream(&stream)?;
+            if data
+                .chunks_exact(ENTRY_SIZE)
+                .any(|entry| entry[0] & ENTRY_IN_USE != 0)
+            {
+                return Err(error::Status::NotEmpty);
+            }
+        }
+
+        self.remove_set(&volume, &set)?;
+        let mut chain = volume.get_chain(&stream)?;
+        volume.truncate_chain(&mut stream, &mut chain, 0)?;
+        volume.forget_stream(self.cluster(), &set.name);
+        Ok(())
+    }
+
+    fn update_metadata(&self, name: &str, new_metadata: Metadata) -> error::Result<()> {
+        // Lengths are written by the file itself
+        let volume = self.volume.lock();
+        self.update_set(&volume, name, |set| {
+            let attributes = (read_u16(set, 4) & !ATTRIBUTE_METADATA_MASK)
+                | (new_metadata.attributes() as u16 & ATTRIBUTE_METADATA_MASK);
+            write_u16(set, 4, attributes);
+
+            let (modified, modified_10ms) = epoch_to_timestamp(new_metadata.modification_time());
+            let (accessed, _) = epoch_to_timestamp(new_metadata.access_time());
+            write_u32(set, 12, modified);
+            write_u32(set, 16, accessed);
+            set[21] = modified_10ms;
+            set[23] = 0;
+            set[24] = 0;
+        })
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn flush(&self) -> error::Result<()> {
+        self.volume.lock().flush()
+    }
+
+    fn get_space(&self) -> error::Result<(usize, usize)> {
+        self.volume.lock().space()
+    }
+}
+
+impl EntrySet {
+    fn attributes(&self) -> u16 {
+        read_u16(&self.data, 4)
+    }
+
+    fn is_directory(&self) -> bool {
+        self.attributes() & ATTRIBUTE_DIRECTORY != 0
+    }
+
+    fn stream(&self) -> Stream {
+        let stream = &self.data[ENTRY_SIZE..2 * ENTRY_SIZE];
+        Stream::new(
+            read_u32(stream, 20),
+            stream[1] & STREAM_NO_FAT_CHAIN != 0,
+            read_u64(stream, 24),
+            read_u64(stream, 8),
+        )
+    }
+
+    fn metadata(&self) -> Metadata {
+        let set = &self.data;
+        Metadata::new(
+            read_u64(set, ENTRY_SIZE + 24) as usize,
+            self.is_directory(),
+            (self.attributes() & ATTRIBUTE_METADATA_MASK) as usize,
+            timestamp_to_epoch(read_u32(set, 8), set[20], set[22]),
+            timestamp_to_epoch(read_u32(set, 16), 0, set[24]),
+            timestamp_to_epoch(read_u32(set, 12), set[21], set[23]),
+        )
+    }
+}
+
+// Returns every valid file entry set, sets with a bad checksum are skipped
+fn parse_sets(data: &[u8]) -> Vec<EntrySet> {
+    let entry_count = data.len() / ENTRY_SIZE;
+    let mut sets = Vec::new();
+
+    let mut index = 0;
+    while index < entry_count {
+        let entry = &data[index * ENTRY_SIZE..(index + 1) * ENTRY_SIZE];
+        if entry[0] == ENTRY_END {
+            break;
+        }
+
+        let count = entry[1] as usize + 1;
+        if entry[0] != ENTRY_FILE || count < 3 || index + count > entry_count {
+            index += 1;
+            continue;
+        }
+
+        let set = &data[index * ENTRY_SIZE..(index + count) * ENTRY_SIZE];
+        match parse_name(set) {
+            Some(name) if read_u16(set, 2) == set_checksum(set) => {
+                sets.push(EntrySet {
+                    index,
+                    data: set.to_vec(),
+                    name,
+                });
+                index += count;
+            }
+            _ => index += 1,
+        }
+    }
+
+    sets
+}
+
+// Names are compared through the up-case table
+fn find_set(volume: &Volume, data: &[u8], name: &str) -> error::Result<EntrySet> {
+    let name = volume.upcase_name(&name.encode_utf16().collect::<Vec<u16>>());
+    parse_sets(data)
+        .into_iter()
+        .find(|set| volume.upcase_name(&set.name.encode_utf16().collect::<Vec<u16>>()) == name)
+        .ok_or(error::Status::NoEntry)
+}
+
+fn parse_name(set: &[u8]) -> Option<String> {
+    let stream = &set[ENTRY_SIZE..2 * ENTRY_SIZE];
+    if stream[0] != ENTRY_STREAM {
+        return None;
+    }
+
+    let name_length = stream[3] as usize;
+    let mut name = Vec::with_capacity(name_length);
+    for entry in set[2 * ENTRY_SIZE..].chunks_exact(ENTRY_SIZE) {
+        if entry[0] == ENTRY_NAME {
+            for character in 0..NAME_CHARACTERS {
+                name.push(read_u16(entry, 2 + character * 2));
+            }
+        }
+    }
+
+    if name_length == 0 || name.len() < name_length {
+        return None;
+    }
+
+    name.truncate(name_length);
+    Some(
+        core::char::decode_utf16(name.into_iter())
+            .map(|c| c.unwrap_or(core::char::REPLACEMENT_CHARACTER))
+            .collect(),
+    )
+}
+
+fn write_set(volume: &Volume, chain: &[u32], index: usize, set: &[u8]) -> error::Result<()> {
+    let cluster_size = volume.cluster_size();
+    for (entry_index, entry) in set.chunks_exact(ENTRY_SIZE).enumerate() {
+        let position = (index + entry_index) * ENTRY_SIZE;
+        let cluster = chain
+            .get(position / cluster_size)
+            .ok_or(error::Status::CorruptFilesystem)?;
+        volume.write_bytes(
+            volume.cluster_offset(*cluster) + position % cluster_size,
+            entry,
+        )?;
+    }
+
+    Ok(())
+}
+
+// Records the current state of an open stream in its parent directory's entry set
+pub fn write_stream(volume: &Volume, stream: &StreamBox) -> error::Result<()> {
+    // The root directory's length is only kept in memory
+    if Arc::ptr_eq(stream, &volume.root_box()) {
+        return Ok(());
+    }
+
+    let (parent_cluster, name) = match volume.locate_stream(stream) {
+        Some(key) => key,
+        None => return Ok(()),
+    };
+
+    // Open entries always have an open parent
+    let parent = volume
+        .find_directory_stream(parent_cluster)
+        .ok_or(error::Status::CorruptFilesystem)?;
+
+    let stream = stream.lock().clone();
+    let parent_stream = parent.lock().clone();
+    let (chain, data) = volume.read_stream(&parent_stream)?;
+    let mut set = find_set(volume, &data, &name)?;
+
+    write_stream_entry(&mut set.data[ENTRY_SIZE..2 * ENTRY_SIZE], &stream);
+    let checksum = set_checksum(&set.data);
+    write_u16(&mut set.data, 2, checksum);
+
+    write_set(volume, &chain, set.index, &set.data)
+}
+
+fn write_stream_entry(entry: &mut [u8], stream: &Stream) {
+    entry[1] = if stream.no_fat_chain {
+        STREAM_ALLOCATION_POSSIBLE | STREAM_NO_FAT_CHAIN
+    } else {
+        STREAM_ALLOCATION_POSSIBLE
+    };
+    write_u64(entry, 8, stream.valid_length);
+    write_u32(entry, 20, stream.first_cluster);
+    write_u64(entry, 24, stream.length);
+}
+
+fn new_set(volume: &Volume, name: &str, attributes: u16, stream: &Stream) -> Vec<u8> {
+    let mut header = [0u8; 2 * ENTRY_SIZE];
+    header[0] = ENTRY_FILE;
+    write_u16(&mut header, 4, attributes);
+
+    let (now, now_10ms) = epoch_to_timestamp(time::get_epoch_time());
+    for offset in [8, 12, 16].iter() {
+        write_u32(&mut header, *offset, now);
+    }
+    header[20] = now_10ms;
+    header[21] = now_10ms;
+
+    header[ENTRY_SIZE] = ENTRY_STREAM;
+    write_stream_entry(&mut header[ENTRY_SIZE..], stream);
+
+    named_set(volume, &header, name)
+}
+
+// Builds an entry set from a file and stream entry, adding name entries for "name"
+fn named_set(volume: &Volume, header: &[u8], name: &str) -> Vec<u8> {
+    let name: Vec<u16> = name.encode_utf16().collect();
+    let name_entries = (name.len() + NAME_CHARACTERS - 1) / NAME_CHARACTERS;
+
+    let mut set = Vec::with_capacity((2 + name_entries) * ENTRY_SIZE);
+    set.extend_from_slice(header);
+    set.resize((2 + name_entries) * ENTRY_SIZE, 0);
+
+    set[1] = (name_entries + 1) as u8;
+    set[ENTRY_SIZE + 3] = name.len() as u8;
+    write_u16(&mut set, ENTRY_SIZE + 4, name_hash(volume, &name));
+
+    for (index, character) in name.iter().enumerate() {
+        let entry = (2 + index / NAME_CHARACTERS) * ENTRY_SIZE;
+        set[entry] = ENTRY_NAME;
+        write_u16(
+            &mut set,
+            entry + 2 + index % NAME_CHARACTERS * 2,
+            *character,
+        );
+    }
+
+    let checksum = set_checksum(&set);
+    write_u16(&mut set, 2, checksum);
+    set
+}
+
+fn verify_name(name: &str) -> error::Result<()> {
+    if name.is_empty()
+        || name == "."
+        || name == ".."
+        || name.chars().any(|c| c < ' ' || "\"*/:<>?\\|".contains(c))
+    {
+        return Err(error::Status::InvalidArgument);
+    }
+
+    if name.encode_utf16().count() > MAX_NAME_LENGTH {
+        return Err(error::Status::NameTooLong);
+    }
+
+    Ok(())
+}
+
+// The checksum covers every byte of the set except the checksum itself
+fn set_checksum(set: &[u8]) -> u16 {
+    let mut checksum: u16 = 0;
+    for (index, byte) in set.iter().enumerate() {
+        if index == 2 || index == 3 {
+            continue;
+        }
+
+        checksum = checksum.rotate_right(1).wrapping_add(*byte as u16);
+    }
+
+    checksum
+}
+
+fn name_hash(volume: &Volume, name: &[u16]) -> u16 {
+    let mut hash: u16 = 0;
+    for character in volume.upcase_name(name) {
+        for byte in character.to_le_bytes().iter() {
+            hash = hash.rotate_right(1).wrapping_add(*byte as u16);
+        }
+    }
+
+    hash
+}
+
+// Timestamps are FAT dates and times, with an optional offset from UTC in 15 minute intervals
+fn timestamp_to_epoch(timestamp: u32, increment_10ms: u8, utc_offset: u8) -> isize {
+    let date = (timestamp >> 16) as u16;
+    if date == 0 {
+        return 0;
+    }
+
+    let year = 1980 + (date >> 9) as isize;
+    let month = ((date >> 5) & 0x0F) as isize;
+    let day = (date & 0x1F) as isize;
+    if month < 1 || month > 12 || day < 1 {
+        return 0;
+    }
+
+    let hour = ((timestamp >> 11) & 0x1F) as isize;
+    let minute = ((timestamp >> 5) & 0x3F) as isize;
+    let second = ((timestamp & 0x1F) * 2) as isize + increment_10ms as isize / 100;
+
+    let epoch = time::date_to_epoch(year, month, day, hour, minute, second);
+    if utc_offset & 0x80 != 0 {
+        // Sign extend the 7 bit offset
+        epoch - (((utc_offset << 1) as i8) >> 1) as isize * 15 * 60
+    } else {
+        epoch
+    }
+}
+
+// Returns the timestamp and the 10ms increment holding the odd second
+fn epoch_to_timestamp(epoch: isize) -> (u32, u8) {
+    let (year, month, day, hour, minute, second) = time::epoch_to_date(epoch);
+    if year < 1980 {
+        return (0, 0);
+    }
+
+    let year = core::cmp::min(year - 1980, 127);
+    (
+        ((year << 25) | (month << 21) | (day << 16) | (hour << 11) | (minute << 5) | (second / 2))
+            as u32,
+        (second % 2 * 100) as u8,
+    )
+}
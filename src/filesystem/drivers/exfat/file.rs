@@ -0,0 +1,173 @@
+use super::{
+    directory,
+    volume::{Stream, StreamBox, Volume, VolumeBox},
+};
+use crate::error;
+use alloc::vec::Vec;
+
+pub struct File {
+    stream: StreamBox,
+    volume: VolumeBox,
+}
+
+impl File {
+    pub fn new(stream: StreamBox, volume: VolumeBox) -> Self {
+        File { stream, volume }
+    }
+
+    // Stores a changed stream and records it in the parent directory
+    fn store(&self, volume: &Volume, stream: Stream) -> error::Result<()> {
+        *self.stream.lock() = stream;
+        directory::write_stream(volume, &self.stream)
+    }
+}
+
+impl crate::filesystem::File for File {
+    fn write(&mut self, offset: usize, buffer: &[u8]) -> error::Result<isize> {
+        let volume = self.volume.lock();
+        let mut stream = self.stream.lock().clone();
+        if offset + buffer.len() > stream.length as usize {
+            return Err(error::Status::OutOfRange);
+        }
+
+        let chain = volume.get_chain(&stream)?;
+        if stream.valid_length < stream.length {
+            zero_invalid_data(&volume, &mut stream, &chain)?;
+            self.store(&volume, stream)?;
+        }
+
+        let cluster_size = volume.cluster_size();
+        let mut written = 0;
+        while written < buffer.len() {
+            let position = offset + written;
+            let cluster_offset = position % cluster_size;
+            let length = core::cmp::min(cluster_size - cluster_offset, buffer.len() - written);
+
+            let cluster = chain
+                .get(position / cluster_size)
+                .ok_or(error::Status::CorruptFilesystem)?;
+            volume.write_bytes(
+                volume.cluster_offset(*cluster) + cluster_offset,
+                &buffer[written..written + length],
+            )?;
+
+            written += length;
+        }
+
+        Ok(written as isize)
+    }
+
+    fn read(&mut self, offset: usize, buffer: &mut [u8]) -> error::Result<isize> {
+        let volume = self.volume.lock();
+        let stream = self.stream.lock().clone();
+        if offset >= stream.length as usize {
+            return Ok(-1);
+        }
+
+        let chain = volume.get_chain(&stream)?;
+        let cluster_size = volume.cluster_size();
+        let valid_length = stream.valid_length as usize;
+
+        let length = core::cmp::min(buffer.len(), stream.length as usize - offset);
+        let mut read = 0;
+        while read < length {
+            let position = offset + read;
+            let cluster_offset = position % cluster_size;
+            let mut chunk = core::cmp::min(cluster_size - cluster_offset, length - read);
+
+            // Data past the valid length reads back as zeroes
+            if position >= valid_length {
+                for byte in &mut buffer[read..read + chunk] {
+                    *byte = 0;
+                }
+            } else {
+                chunk = core::cmp::min(chunk, valid_length - position);
+                let cluster = chain
+                    .get(position / cluster_size)
+                    .ok_or(error::Status::CorruptFilesystem)?;
+                volume.read_bytes(
+                    volume.cluster_offset(*cluster) + cluster_offset,
+                    &mut buffer[read..read + chunk],
+                )?;
+            }
+
+            read += chunk;
+        }
+
+        for byte in &mut buffer[length..] {
+            *byte = 0;
+        }
+
+        Ok(length as isize)
+    }
+
+    fn set_length(&mut self, new_length: usize) -> error::Result<()> {
+        let mut volume = self.volume.lock();
+        let mut stream = self.stream.lock().clone();
+        let mut chain = volume.get_chain(&stream)?;
+        let cluster_size = volume.cluster_size();
+
+        zero_invalid_data(&volume, &mut stream, &chain)?;
+
+        // Zero the tail of the last partial cluster so its stale bytes never become visible
+        let boundary = core::cmp::min(stream.length as usize, new_length);
+        if boundary % cluster_size != 0 {
+            if let Some(cluster) = chain.get(boundary / cluster_size) {
+                let mut zeroes = Vec::with_capacity(cluster_size - boundary % cluster_size);
+                zeroes.resize(cluster_size - boundary % cluster_size, 0);
+                volume.write_bytes(
+                    volume.cluster_offset(*cluster) + boundary % cluster_size,
+                    zeroes.as_slice(),
+                )?;
+            }
+        }
+
+        let cluster_count = (new_length + cluster_size - 1) / cluster_size;
+        if cluster_count > chain.len() {
+            volume.extend_chain(&mut stream, &mut chain, cluster_count)?;
+        } else {
+            volume.truncate_chain(&mut stream, &mut chain, cluster_count)?;
+        }
+
+        stream.length = new_length as u64;
+        stream.valid_length = stream.length;
+        self.store(&volume, stream)
+    }
+
+    fn get_length(&self) -> usize {
+        self.stream.lock().length as usize
+    }
+
+    fn flush(&mut self) -> error::Result<()> {
+        self.volume.lock().flush()
+    }
+}
+
+// Zeroes the data between the valid length and the length, making all of it valid
+fn zero_invalid_data(volume: &Volume, stream: &mut Stream, chain: &[u32]) -> error::Result<()> {
+    let cluster_size = volume.cluster_size();
+    let mut zeroes = Vec::with_capacity(cluster_size);
+    zeroes.resize(cluster_size, 0);
+
+    let mut position = stream.valid_length as usize;
+    while position < stream.length as usize {
+        let cluster_offset = position % cluster_size;
+        let length = core::cmp::min(
+            cluster_size - cluster_offset,
+            stream.length as usize - position,
+        );
+
+        let cluster = chain
+            .get(position / cluster_size)
+            .ok_or(error::Status::CorruptFilesystem)?;
+        volume.write_bytes(
+            volume.cluster_offset(*cluster) + cluster_offset,
+            &zeroes[..length],
+        )?;
+
+        position += length;
+    }
+
+    stream.valid_length = stream.length;
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use crate::{device::DeviceReference, error, filesystem::FilesystemStarter, locks::Mutex};
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use directory::{ENTRY_BITMAP, ENTRY_END, ENTRY_SIZE, ENTRY_UPCASE_TABLE, ENTRY_VOLUME_LABEL};
+use volume::{read_u16, read_u32, read_u64, Stream, Volume};
+
+mod directory;
+mod file;
+mod volume;
+
+const BOOT_SECTOR_SIZE: usize = 512;
+
+// The boot region is 11 checksummed sectors followed by a sector of checksums
+const BOOT_REGION_SECTORS: usize = 12;
+const BACKUP_BOOT_REGION_SECTOR: usize = 12;
+
+const MAX_CLUSTER_SHIFT: u8 = 25;
+const VOLUME_FLAG_ACTIVE_FAT: u16 = 0x1;
+const MAX_LABEL_LENGTH: usize = 11;
+
+pub fn detect_exfat_filesystem(
+    drive_lock: DeviceReference,
+    size: usize,
+) -> error::Result<Option<FilesystemStarter>> {
+    // Get boot sector
+    let mut boot_sector = [0u8; BOOT_SECTOR_SIZE];
+    drive_lock.lock().read(0, &mut boot_sector)?;
+
+    if &boot_sector[3..11] != b"EXFAT   " || boot_sector[510] != 0x55 || boot_sector[511] != 0xAA {
+        return Ok(None);
+    }
+
+    // Verify the boot sector is sane
+    let bytes_per_sector_shift = boot_sector[108];
+    let sectors_per_cluster_shift = boot_sector[109];
+    if bytes_per_sector_shift < 9
+        || bytes_per_sector_shift > 12
+        || bytes_per_sector_shift + sectors_per_cluster_shift > MAX_CLUSTER_SHIFT
+    {
+        return Ok(None);
+    }
+
+    let bytes_per_sector = 1 << bytes_per_sector_shift;
+    if BOOT_REGION_SECTORS * 2 * bytes_per_sector > size {
+        return Ok(None);
+    }
+
+    // Use the backup boot region if the main one is damaged
+    let boot_region = match read_boot_region(&drive_lock, 0, bytes_per_sector)? {
+        Some(boot_region) => boot_region,
+        None => match read_boot_region(
+            &drive_lock,
+            BACKUP_BOOT_REGION_SECTOR * bytes_per_sector / BOOT_SECTOR_SIZE,
+            bytes_per_sector,
+        )? {
+            Some(boot_region) => boot_region,
+            None => return Ok(None),
+        },
+    };
+
+    let fat_offset = read_u32(&boot_region, 80) as usize * bytes_per_sector;
+    let fat_length = read_u32(&boot_region, 84) as usize * bytes_per_sector;
+    let heap_offset = read_u32(&boot_region, 88) as usize * bytes_per_sector;
+    let cluster_count = read_u32(&boot_region, 92);
+    let root_cluster = read_u32(&boot_region, 96);
+    let volume_flags = read_u16(&boot_region, 106);
+    let cluster_size = bytes_per_sector << sectors_per_cluster_shift;
+    if fat_offset == 0
+        || heap_offset == 0
+        || cluster_count == 0
+        || heap_offset + cluster_count as usize * cluster_size > size
+        || root_cluster < volume::FIRST_CLUSTER
+        || root_cluster >= cluster_count + volume::FIRST_CLUSTER
+    {
+        return Ok(None);
+    }
+
+    // The second FAT and bitmap are used while the active FAT flag is set
+    let active_fat = if volume_flags & VOLUME_FLAG_ACTIVE_FAT != 0 && boot_region[110] == 2 {
+        1
+    } else {
+        0
+    };
+
+    // Create volume
+    let mut volume = Volume::new(
+        drive_lock.clone(),
+        fat_offset + active_fat * fat_length,
+        heap_offset,
+        cluster_size,
+        cluster_count,
+        root_cluster,
+    )?;
+
+    // Find the allocation bitmap, up-case table, and label in the root directory
+    let (_, root) = volume.read_stream(&volume.root())?;
+    let mut bitmap = None;
+    let mut upcase_table = None;
+    let mut volume_name = String::new();
+    for entry in root.chunks_exact(ENTRY_SIZE) {
+        match entry[0] {
+            ENTRY_END => break,
+            ENTRY_BITMAP if (entry[1] & 0x1) as usize == active_fat => {
+                bitmap = Some(entry_stream(entry))
+            }
+            ENTRY_UPCASE_TABLE => upcase_table = Some((entry_stream(entry), read_u32(entry, 4))),
+            ENTRY_VOLUME_LABEL => {
+                let length = core::cmp::min(entry[1] as usize, MAX_LABEL_LENGTH);
+                volume_name =
+                    core::char::decode_utf16((0..length).map(|i| read_u16(entry, 2 + i * 2)))
+                        .map(|c| c.unwrap_or(core::char::REPLACEMENT_CHARACTER))
+                        .collect();
+            }
+            _ => {}
+        }
+    }
+
+    let (bitmap, (upcase_table, upcase_checksum)) = match (bitmap, upcase_table) {
+        (Some(bitmap), Some(upcase_table)) => (bitmap, upcase_table),
+        _ => return Ok(None),
+    };
+
+    volume.set_bitmap(&bitmap)?;
+
+    let (_, mut table) = volume.read_stream(&upcase_table)?;
+    table.truncate(upcase_table.length as usize);
+    if checksum(&table, &[]) != upcase_checksum {
+        return Ok(None);
+    }
+    volume.set_upcase_table(&table);
+
+    let volume = Arc::new(Mutex::new(volume));
+    let root = volume.lock().root_box();
+
+    // Create filesystem starter
+    Ok(Some(FilesystemStarter::new(
+        Box::new(directory::Directory::new(root, volume)),
+        volume_name,
+        "exfat",
+    )))
+}
+
+// Returns the boot region starting at "sector" if its checksum is valid
+fn read_boot_region(
+    drive_lock: &DeviceReference,
+    sector: usize,
+    bytes_per_sector: usize,
+) -> error::Result<Option<Vec<u8>>> {
+    let mut boot_region = Vec::with_capacity(BOOT_REGION_SECTORS * bytes_per_sector);
+    boot_region.resize(BOOT_REGION_SECTORS * bytes_per_sector, 0);
+    drive_lock.lock().read(sector, boot_region.as_mut_slice())?;
+
+    // The volume flags and percent in use change without updating the checksum
+    let checksummed = (BOOT_REGION_SECTORS - 1) * bytes_per_sector;
+    let expected = checksum(&boot_region[..checksummed], &[106, 107, 112]);
+    if boot_region[checksummed..]
+        .chunks_exact(4)
+        .all(|stored| read_u32(stored, 0) == expected)
+    {
+        Ok(Some(boot_region))
+    } else {
+        Ok(None)
+    }
+}
+
+fn checksum(data: &[u8], skipped: &[usize]) -> u32 {
+    let mut checksum: u32 = 0;
+    for (index, byte) in data.iter().enumerate() {
+        if !skipped.contains(&index) {
+            checksum = checksum.rotate_right(1).wrapping_add(*byte as u32);
+        }
+    }
+
+    checksum
+}
+
+// Bitmap and up-case table entries store their data in a FAT chain
+fn entry_stream(entry: &[u8]) -> Stream {
+    let length = read_u64(entry, 24);
+    Stream::new(read_u32(entry, 20), false, length, length)
+}
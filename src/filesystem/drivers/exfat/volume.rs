@@ -0,0 +1,533 @@
+use crate::{
+    device::DeviceReference,
+    error,
+    filesystem::cache::{self, BLOCK_SIZE},
+    locks::Mutex,
+};
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+
+pub type VolumeBox = Arc<Mutex<Volume>>;
+pub type StreamBox = Arc<Mutex<Stream>>;
+
+// The data of a file or directory as recorded in its stream extension entry
+#[derive(Clone)]
+pub struct Stream {
+    pub first_cluster: u32,
+    pub no_fat_chain: bool,
+    pub length: u64,
+    pub valid_length: u64,
+}
+
+pub struct Volume {
+    drive: DeviceReference,
+    fat_offset: usize,
+    heap_offset: usize,
+    cluster_size: usize,
+    cluster_count: u32,
+    bitmap: Vec<u32>,
+    upcase: Vec<u16>,
+    next_free: u32,
+    free_clusters: Option<u32>,
+    root: StreamBox,
+    // Open files and directories by parent cluster and name
+    streams: BTreeMap<(u32, String), StreamBox>,
+}
+
+pub const FIRST_CLUSTER: u32 = 2;
+const END_OF_CHAIN: u32 = 0xFFFFFFFF;
+const BAD_CLUSTER: u32 = 0xFFFFFFF7;
+
+impl Stream {
+    pub fn new(first_cluster: u32, no_fat_chain: bool, length: u64, valid_length: u64) -> Self {
+        Stream {
+            first_cluster,
+            no_fat_chain,
+            length,
+            valid_length,
+        }
+    }
+}
+
+impl Volume {
+    pub fn new(
+        drive: DeviceReference,
+        fat_offset: usize,
+        heap_offset: usize,
+        cluster_size: usize,
+        cluster_count: u32,
+        root_cluster: u32,
+    ) -> error::Result<Self> {
+        let mut volume = Volume {
+            drive,
+            fat_offset,
+            heap_offset,
+            cluster_size,
+            cluster_count,
+            bitmap: Vec::new(),
+            upcase: Vec::new(),
+            next_free: FIRST_CLUSTER,
+            free_clusters: None,
+            root: Arc::new(Mutex::new(Stream::new(root_cluster, false, 0, 0))),
+            streams: BTreeMap::new(),
+        };
+
+        // The root directory has no stream extension, its length comes from its chain
+        let length = (volume.get_chain(&volume.root())?.len() * cluster_size) as u64;
+        volume.root = Arc::new(Mutex::new(Stream::new(root_cluster, false, length, length)));
+
+        Ok(volume)
+    }
+
+    // The bitmap's clusters must hold a bit for every cluster on the volume
+    pub fn set_bitmap(&mut self, bitmap: &Stream) -> error::Result<()> {
+        let length = (self.cluster_count as usize + 7) / 8;
+        if bitmap.length < length as u64 {
+            return Err(error::Status::CorruptFilesystem);
+        }
+
+        let chain = self.get_chain(bitmap)?;
+        if chain.len() * self.cluster_size < length {
+            return Err(error::Status::CorruptFilesystem);
+        }
+
+        self.bitmap = chain;
+        Ok(())
+    }
+
+    // Expands the compressed up-case table, runs of identity mappings are stored as 0xFFFF and a length
+    pub fn set_upcase_table(&mut self, table: &[u8]) {
+        let mut upcase = Vec::with_capacity(0x10000);
+        let mut i = 0;
+        while i + 1 < table.len() && upcase.len() < 0x10000 {
+            let value = read_u16(table, i);
+            i += 2;
+
+            if value == 0xFFFF && i + 1 < table.len() {
+                let count = read_u16(table, i) as usize;
+                i += 2;
+                for _ in 0..count {
+                    if upcase.len() < 0x10000 {
+                        upcase.push(upcase.len() as u16);
+                    }
+                }
+            } else {
+                upcase.push(value);
+            }
+        }
+
+        self.upcase = upcase;
+    }
+
+    pub fn root(&self) -> Stream {
+        self.root.lock().clone()
+    }
+
+    pub fn root_box(&self) -> StreamBox {
+        self.root.clone()
+    }
+
+    pub fn cluster_size(&self) -> usize {
+        self.cluster_size
+    }
+
+    pub fn upcase(&self, character: u16) -> u16 {
+        match self.upcase.get(character as usize) {
+            Some(upper) => *upper,
+            None => character,
+        }
+    }
+
+    pub fn upcase_name(&self, name: &[u16]) -> Vec<u16> {
+        name.iter().map(|c| self.upcase(*c)).collect()
+    }
+
+    // Returns the open stream for an entry, registering it if needed
+    pub fn open_stream(&mut self, parent: u32, name: &str, stream: Stream) -> StreamBox {
+        self.streams
+            .retain(|_, stream| Arc::strong_count(stream) > 1);
+
+        self.streams
+            .entry((parent, String::from(name)))
+            .or_insert_with(|| Arc::new(Mutex::new(stream)))
+            .clone()
+    }
+
+    pub fn move_stream(&mut self, parent: u32, name: &str, new_parent: u32, new_name: &str) {
+        if let Some(stream) = self.streams.remove(&(parent, String::from(name))) {
+            self.streams
+                .insert((new_parent, String::from(new_name)), stream);
+        }
+    }
+
+    pub fn forget_stream(&mut self, parent: u32, name: &str) {
+        self.streams.remove(&(parent, String::from(name)));
+    }
+
+    // Finds the parent cluster and name of an open file or directory
+    pub fn locate_stream(&self, stream: &StreamBox) -> Option<(u32, String)> {
+        self.streams
+            .iter()
+            .find(|(_, other)| Arc::ptr_eq(stream, other))
+            .map(|(key, _)| key.clone())
+    }
+
+    // Finds an open directory by its first cluster
+    pub fn find_directory_stream(&self, first_cluster: u32) -> Option<StreamBox> {
+        if self.root.lock().first_cluster == first_cluster {
+            return Some(self.root.clone());
+        }
+
+        self.streams
+            .values()
+            .find(|stream| stream.lock().first_cluster == first_cluster)
+            .cloned()
+    }
+
+    pub fn flush(&self) -> error::Result<()> {
+        cache::flush(&self.drive)
+    }
+
+    // Returns the total and free bytes, the free clusters are counted on first use
+    pub fn space(&mut self) -> error::Result<(usize, usize)> {
+        let free_clusters = match self.free_clusters {
+            Some(free_clusters) => free_clusters,
+            None => {
+                let free_clusters = self.count_free()?;
+                self.free_clusters = Some(free_clusters);
+                free_clusters
+            }
+        };
+
+        Ok((
+            self.cluster_count as usize * self.cluster_size,
+            free_clusters as usize * self.cluster_size,
+        ))
+    }
+
+    pub fn read_bytes(&self, offset: usize, buffer: &mut [u8]) -> error::Result<()> {
+        let start = offset / BLOCK_SIZE;
+        let end = (offset + buffer.len() + BLOCK_SIZE - 1) / BLOCK_SIZE;
+        let mut blocks = Vec::with_capacity((end - start) * BLOCK_SIZE);
+        blocks.resize((end - start) * BLOCK_SIZE, 0);
+        cache::read(&self.drive, start, blocks.as_mut_slice())?;
+
+        let block_offset = offset - start * BLOCK_SIZE;
+        buffer.copy_from_slice(&blocks[block_offset..block_offset + buffer.len()]);
+        Ok(())
+    }
+
+    pub fn write_bytes(&self, offset: usize, buffer: &[u8]) -> error::Result<()> {
+        let start = offset / BLOCK_SIZE;
+        let end = (offset + buffer.len() + BLOCK_SIZE - 1) / BLOCK_SIZE;
+        let mut blocks = Vec::with_capacity((end - start) * BLOCK_SIZE);
+        blocks.resize((end - start) * BLOCK_SIZE, 0);
+        if offset % BLOCK_SIZE != 0 || buffer.len() % BLOCK_SIZE != 0 {
+            cache::read(&self.drive, start, blocks.as_mut_slice())?;
+        }
+
+        let block_offset = offset - start * BLOCK_SIZE;
+        blocks[block_offset..block_offset + buffer.len()].copy_from_slice(buffer);
+        cache::write(&self.drive, start, blocks.as_slice())
+    }
+
+    pub fn cluster_offset(&self, cluster: u32) -> usize {
+        self.heap_offset + (cluster - FIRST_CLUSTER) as usize * self.cluster_size
+    }
+
+    pub fn read_cluster(&self, cluster: u32, buffer: &mut [u8]) -> error::Result<()> {
+        self.read_bytes(self.cluster_offset(cluster), buffer)
+    }
+
+    pub fn write_cluster(&self, cluster: u32, buffer: &[u8]) -> error::Result<()> {
+        self.write_bytes(self.cluster_offset(cluster), buffer)
+    }
+
+    pub fn get_chain(&self, stream: &Stream) -> error::Result<Vec<u32>> {
+        let mut chain = Vec::new();
+        if stream.first_cluster == 0 {
+            return Ok(chain);
+        }
+
+        // Contiguous streams are not recorded in the FAT
+        if stream.no_fat_chain {
+            let count = (stream.length as usize + self.cluster_size - 1) / self.cluster_size;
+            for i in 0..count as u32 {
+                let cluster = stream.first_cluster + i;
+                self.verify_cluster(cluster)?;
+                chain.push(cluster);
+            }
+
+            return Ok(chain);
+        }
+
+        let mut cluster = stream.first_cluster;
+        loop {
+            self.verify_cluster(cluster)?;
+            chain.push(cluster);
+            if chain.len() > self.cluster_count as usize {
+                return Err(error::Status::CorruptFilesystem);
+            }
+
+            cluster = self.get_fat(cluster)?;
+            if cluster == END_OF_CHAIN {
+                return Ok(chain);
+            }
+        }
+    }
+
+    // Returns the chain of a stream and the contents of its clusters
+    pub fn read_stream(&self, stream: &Stream) -> error::Result<(Vec<u32>, Vec<u8>)> {
+        let chain = self.get_chain(stream)?;
+        let mut data = Vec::with_capacity(chain.len() * self.cluster_size);
+        data.resize(chain.len() * self.cluster_size, 0);
+        for (index, cluster) in chain.iter().enumerate() {
+            let start = index * self.cluster_size;
+            self.read_cluster(*cluster, &mut data[start..start + self.cluster_size])?;
+        }
+
+        Ok((chain, data))
+    }
+
+    // Grows a chain to "count" clusters, keeping it contiguous while possible
+    pub fn extend_chain(
+        &mut self,
+        stream: &mut Stream,
+        chain: &mut Vec<u32>,
+        count: usize,
+    ) -> error::Result<()> {
+        if chain.is_empty() {
+            stream.no_fat_chain = true;
+        }
+
+        while chain.len() < count {
+            let cluster = match chain.last() {
+                Some(last) if stream.no_fat_chain && self.is_free(last + 1)? => last + 1,
+                Some(_) if stream.no_fat_chain => {
+                    // Record the contiguous run in the FAT before fragmenting
+                    for pair in chain.windows(2) {
+                        self.set_fat(pair[0], pair[1])?;
+                    }
+                    self.set_fat(*chain.last().unwrap(), END_OF_CHAIN)?;
+                    stream.no_fat_chain = false;
+                    self.find_free()?
+                }
+                _ => self.find_free()?,
+            };
+
+            self.allocate(cluster)?;
+            if !stream.no_fat_chain {
+                if let Some(last) = chain.last() {
+                    self.set_fat(*last, cluster)?;
+                }
+                self.set_fat(cluster, END_OF_CHAIN)?;
+            }
+
+            if chain.is_empty() {
+                stream.first_cluster = cluster;
+            }
+
+            chain.push(cluster);
+        }
+
+        Ok(())
+    }
+
+    pub fn truncate_chain(
+        &mut self,
+        stream: &mut Stream,
+        chain: &mut Vec<u32>,
+        count: usize,
+    ) -> error::Result<()> {
+        while chain.len() > count {
+            let cluster = chain.pop().unwrap();
+            self.free(cluster)?;
+            if !stream.no_fat_chain {
+                self.set_fat(cluster, 0)?;
+            }
+        }
+
+        match chain.last() {
+            Some(last) if !stream.no_fat_chain => self.set_fat(*last, END_OF_CHAIN),
+            Some(_) => Ok(()),
+            None => {
+                stream.first_cluster = 0;
+                stream.no_fat_chain = false;
+                Ok(())
+            }
+        }
+    }
+
+    fn verify_cluster(&self, cluster: u32) -> error::Result<()> {
+        if cluster < FIRST_CLUSTER || cluster >= self.cluster_count + FIRST_CLUSTER {
+            Err(error::Status::CorruptFilesystem)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn get_fat(&self, cluster: u32) -> error::Result<u32> {
+        let mut entry = [0u8; 4];
+        self.read_bytes(self.fat_offset + cluster as usize * 4, &mut entry)?;
+        match u32::from_le_bytes(entry) {
+            BAD_CLUSTER => Err(error::Status::CorruptFilesystem),
+            next => Ok(next),
+        }
+    }
+
+    fn set_fat(&self, cluster: u32, value: u32) -> error::Result<()> {
+        self.write_bytes(self.fat_offset + cluster as usize * 4, &value.to_le_bytes())
+    }
+
+    // Returns the bitmap location of a cluster as a byte offset and bit
+    // The bitmap was checked to cover every cluster when the volume was opened
+    fn bitmap_location(&self, cluster: u32) -> (usize, u8) {
+        let index = (cluster - FIRST_CLUSTER) as usize;
+        let byte = index / 8;
+        (
+            self.cluster_offset(self.bitmap[byte / self.cluster_size]) + byte % self.cluster_size,
+            1 << (index % 8),
+        )
+    }
+
+    fn is_free(&self, cluster: u32) -> error::Result<bool> {
+        if cluster >= self.cluster_count + FIRST_CLUSTER {
+            return Ok(false);
+        }
+
+        let (offset, bit) = self.bitmap_location(cluster);
+        let mut byte = [0u8];
+        self.read_bytes(offset, &mut byte)?;
+        Ok(byte[0] & bit == 0)
+    }
+
+    fn find_free(&mut self) -> error::Result<u32> {
+        let mut buffer = Vec::with_capacity(self.cluster_size);
+        buffer.resize(self.cluster_size, 0);
+
+        // Search from the last allocation, wrapping around once
+        let start = self.next_free - FIRST_CLUSTER;
+        for pass in 0..2 {
+            let (first, last) = if pass == 0 {
+                (start, self.cluster_count)
+            } else {
+                (0, start)
+            };
+
+            let mut index = first;
+            while index < last {
+                let byte = index as usize / 8;
+                let bitmap_cluster = byte / self.cluster_size;
+                self.read_cluster(self.bitmap[bitmap_cluster], buffer.as_mut_slice())?;
+
+                // Scan the rest of this bitmap cluster
+                let cluster_end =
+                    core::cmp::min(last, ((bitmap_cluster + 1) * self.cluster_size * 8) as u32);
+                while index < cluster_end {
+                    let byte = index as usize / 8 % self.cluster_size;
+                    if buffer[byte] == 0xFF && index % 8 == 0 {
+                        index += 8;
+                        continue;
+                    }
+
+                    if buffer[byte] & (1 << (index % 8)) == 0 {
+                        return Ok(index + FIRST_CLUSTER);
+                    }
+
+                    index += 1;
+                }
+            }
+        }
+
+        Err(error::Status::NoSpace)
+    }
+
+    fn count_free(&self) -> error::Result<u32> {
+        let mut buffer = Vec::with_capacity(self.cluster_size);
+        buffer.resize(self.cluster_size, 0);
+
+        let mut used = 0;
+        for (i, &cluster) in self.bitmap.iter().enumerate() {
+            let first = (i * self.cluster_size * 8) as u32;
+            if first >= self.cluster_count {
+                break;
+            }
+
+            self.read_cluster(cluster, buffer.as_mut_slice())?;
+            let last = core::cmp::min(self.cluster_count, first + (self.cluster_size * 8) as u32);
+            for index in first..last {
+                let byte = (index - first) as usize / 8;
+                if buffer[byte] & (1 << (index % 8)) != 0 {
+                    used += 1;
+                }
+            }
+        }
+
+        Ok(self.cluster_count - used)
+    }
+
+    // Marks a cluster as used and zeroes it
+    fn allocate(&mut self, cluster: u32) -> error::Result<()> {
+        let (offset, bit) = self.bitmap_location(cluster);
+        let mut byte = [0u8];
+        self.read_bytes(offset, &mut byte)?;
+        byte[0] |= bit;
+        self.write_bytes(offset, &byte)?;
+
+        if let Some(free_clusters) = &mut self.free_clusters {
+            *free_clusters -= 1;
+        }
+
+        self.next_free = if cluster + 1 >= self.cluster_count + FIRST_CLUSTER {
+            FIRST_CLUSTER
+        } else {
+            cluster + 1
+        };
+
+        let mut zeroes = Vec::with_capacity(self.cluster_size);
+        zeroes.resize(self.cluster_size, 0);
+        self.write_cluster(cluster, zeroes.as_slice())
+    }
+
+    fn free(&mut self, cluster: u32) -> error::Result<()> {
+        self.verify_cluster(cluster)?;
+
+        let (offset, bit) = self.bitmap_location(cluster);
+        let mut byte = [0u8];
+        self.read_bytes(offset, &mut byte)?;
+        byte[0] &= !bit;
+        self.write_bytes(offset, &byte)?;
+
+        if let Some(free_clusters) = &mut self.free_clusters {
+            *free_clusters += 1;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn read_u16(buffer: &[u8], offset: usize) -> u16 {
+    (buffer[offset] as u16) | ((buffer[offset + 1] as u16) << 8)
+}
+
+pub fn read_u32(buffer: &[u8], offset: usize) -> u32 {
+    (buffer[offset] as u32)
+        | ((buffer[offset + 1] as u32) << 8)
+        | ((buffer[offset + 2] as u32) << 16)
+        | ((buffer[offset + 3] as u32) << 24)
+}
+
+pub fn read_u64(buffer: &[u8], offset: usize) -> u64 {
+    read_u32(buffer, offset) as u64 | ((read_u32(buffer, offset + 4) as u64) << 32)
+}
+
+pub fn write_u16(buffer: &mut [u8], offset: usize, value: u16) {
+    buffer[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+pub fn write_u32(buffer: &mut [u8], offset: usize, value: u32) {
+    buffer[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+pub fn write_u64(buffer: &mut [u8], offset: usize, value: u64) {
+    buffer[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
@@ -0,0 +1,592 @@
+use super::{
+    inode::{Inode, MODE_DIRECTORY, MODE_FIFO, MODE_REGULAR, MODE_TYPE_MASK, MODE_WRITE},
+    volume::{read_u16, read_u32, write_u16, write_u32, Volume, VolumeBox},
+};
+use crate::{
+    error,
+    filesystem::{self, File, Metadata},
+    time,
+};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::any::Any;
+
+pub struct Directory {
+    inode: u32,
+    volume: VolumeBox,
+}
+
+struct Entry {
+    block_index: usize,
+    offset: usize,
+    previous: Option<usize>,
+    inode: u32,
+}
+
+const ENTRY_HEADER_SIZE: usize = 8;
+const MAX_NAME_LENGTH: usize = 255;
+
+const FILE_TYPE_REGULAR: u8 = 1;
+const FILE_TYPE_DIRECTORY: u8 = 2;
+const FILE_TYPE_FIFO: u8 = 5;
+
+const DEFAULT_FILE_MODE: u16 = MODE_REGULAR | 0o644;
+const DEFAULT_DIRECTORY_MODE: u16 = MODE_DIRECTORY | 0o755;
+const OWNER_WRITE: u16 = 0o200;
+
+impl Directory {
+    pub fn new(inode: u32, volume: VolumeBox) -> Self {
+        Directory { inode, volume }
+    }
+
+    fn find(&self, volume: &Volume, name: &str) -> error::Result<Entry> {
+        let directory = volume.read_inode(self.inode)?;
+        for block_index in 0..directory.size() / volume.block_size() {
+            let block = read_directory_block(volume, &directory, block_index)?;
+
+            let mut offset = 0;
+            let mut previous = None;
+            while offset < block.len() {
+                let (inode, record_length, entry_name) = parse_entry(&block, offset)?;
+                if inode != 0 && entry_name == name.as_bytes() {
+                    return Ok(Entry {
+                        block_index,
+                        offset,
+                        previous,
+                        inode,
+                    });
+                }
+
+                previous = Some(offset);
+                offset += record_length;
+            }
+        }
+
+        Err(error::Status::NoEntry)
+    }
+
+    fn add_entry(
+        &self,
+        volume: &mut Volume,
+        name: &str,
+        inode: u32,
+        file_type: u8,
+    ) -> error::Result<()> {
+        let mut directory = volume.read_inode(self.inode)?;
+        let needed = entry_length(name.len());
+        let block_count = directory.size() / volume.block_size();
+
+        // Look for an unused entry or slack at the end of an entry
+        for block_index in 0..block_count {
+            let mut block = read_directory_block(volume, &directory, block_index)?;
+
+            let mut offset = 0;
+            while offset < block.len() {
+                let (entry_inode, record_length, entry_name) = parse_entry(&block, offset)?;
+                let used = if entry_inode == 0 {
+                    0
+                } else {
+                    entry_length(entry_name.len())
+                };
+
+                if record_length - used >= needed {
+                    if used != 0 {
+                        write_u16(&mut block, offset + 4, used as u16);
+                    }
+
+                    write_entry(
+                        volume,
+                        &mut block,
+                        offset + used,
+                        record_length - used,
+                        inode,
+                        name,
+                        file_type,
+                    );
+
+                    let block_number = directory.get_block(volume, block_index)?;
+                    volume.write_block(block_number, block.as_slice())?;
+                    return touch_directory(volume, self.inode, directory);
+                }
+
+                offset += record_length;
+            }
+        }
+
+        // Append a new block
+        let group = volume.group_of_inode(self.inode);
+        let block_number = directory.get_or_allocate_block(volume, block_count, group)?;
+        let mut block = Vec::with_capacity(volume.block_size());
+        block.resize(volume.block_size(), 0);
+        write_entry(
+            volume,
+            &mut block,
+            0,
+            volume.block_size(),
+            inode,
+            name,
+            file_type,
+        );
+        volume.write_block(block_number, block.as_slice())?;
+
+        directory.set_size((block_count + 1) * volume.block_size());
+        touch_directory(volume, self.inode, directory)
+    }
+
+    fn remove_entry(&self, volume: &Volume, entry: &Entry) -> error::Result<()> {
+        let directory = volume.read_inode(self.inode)?;
+        let block_number = directory.get_block(volume, entry.block_index)?;
+        let mut block = read_directory_block(volume, &directory, entry.block_index)?;
+
+        match entry.previous {
+            // Merge into the previous entry
+            Some(previous) => {
+                let record_length =
+                    read_u16(&block, previous + 4) + read_u16(&block, entry.offset + 4);
+                write_u16(&mut block, previous + 4, record_length);
+            }
+            // The first entry of a block is marked unused instead
+            None => write_u32(&mut block, entry.offset, 0),
+        }
+
+        volume.write_block(block_number, block.as_slice())?;
+        touch_directory(volume, self.inode, directory)
+    }
+
+    fn verify_new_name(&self, volume: &Volume, name: &str) -> error::Result<()> {
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return Err(error::Status::InvalidArgument);
+        }
+
+        if name.len() > MAX_NAME_LENGTH {
+            return Err(error::Status::NameTooLong);
+        }
+
+        match self.find(volume, name) {
+            Ok(_) => Err(error::Status::Exists),
+            Err(error::Status::NoEntry) => Ok(()),
+            Err(status) => Err(status),
+        }
+    }
+
+    fn verify_empty(&self, volume: &Volume, inode: &Inode) -> error::Result<()> {
+        for block_index in 0..inode.size() / volume.block_size() {
+            let block = read_directory_block(volume, inode, block_index)?;
+
+            let mut offset = 0;
+            while offset < block.len() {
+                let (entry_inode, record_length, name) = parse_entry(&block, offset)?;
+                if entry_inode != 0 && name != b"." && name != b".." {
+                    return Err(error::Status::NotEmpty);
+                }
+
+                offset += record_length;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rename(&self, old_name: &str, new_name: &str, directory: bool) -> error::Result<()> {
+        let mut volume = self.volume.lock();
+        volume.verify_writable()?;
+
+        let entry = self.find(&volume, old_name)?;
+        let inode = volume.read_inode(entry.inode)?;
+        if directory && !inode.is_directory() {
+            return Err(error::Status::IsFile);
+        } else if !directory && inode.is_directory() {
+            return Err(error::Status::IsDirectory);
+        }
+
+        self.verify_new_name(&volume, new_name)?;
+
+        // Adding the new entry can move the old one's neighbours, so look it up again
+        self.add_entry(&mut volume, new_name, entry.inode, file_type_of(&inode))?;
+        let entry = self.find(&volume, old_name)?;
+        self.remove_entry(&volume, &entry)
+    }
+}
+
+impl filesystem::Directory for Directory {
+    fn get_children(&self) -> error::Result<Vec<(String, Metadata)>> {
+        let volume = self.volume.lock();
+        let directory = volume.read_inode(self.inode)?;
+
+        let mut children = Vec::new();
+        for block_index in 0..directory.size() / volume.block_size() {
+            let block = read_directory_block(&volume, &directory, block_index)?;
+
+            let mut offset = 0;
+            while offset < block.len() {
+                let (inode, record_length, name) = parse_entry(&block, offset)?;
+                offset += record_length;
+                if inode == 0 || name == b"." || name == b".." {
+                    continue;
+                }
+
+                let name = String::from_utf8_lossy(name).to_string();
+                let attributes = if name.starts_with('.') {
+                    filesystem::ATTRIBUTE_HIDDEN
+                } else {
+                    0
+                };
+
+                children.push((name, volume.read_inode(inode)?.metadata(attributes)));
+            }
+        }
+
+        Ok(children)
+    }
+
+    fn open_file(&self, filename: &str) -> error::Result<Box<dyn File>> {
+        let mut volume = self.volume.lock();
+        let entry = self.find(&volume, filename)?;
+        let inode = volume.read_inode(entry.inode)?;
+        if inode.is_directory() {
+            Err(error::Status::IsDirectory)
+        } else if !inode.is_regular() && !inode.is_fifo() {
+            Err(error::Status::NotSupported)
+        } else {
+            Ok(Box::new(super::file::File::new(
+                entry.inode,
+                volume.open_size(entry.inode, inode.size()),
+                self.volume.clone(),
+            )))
+        }
+    }
+
+    fn open_directory(
+        &self,
+        directory_name: &str,
+    ) -> error::Result<Box<dyn filesystem::Directory>> {
+        let volume = self.volume.lock();
+        let entry = self.find(&volume, directory_name)?;
+        if !volume.read_inode(entry.inode)?.is_directory() {
+            return Err(error::Status::IsFile);
+        }
+
+        Ok(Box::new(Directory::new(entry.inode, self.volume.clone())))
+    }
+
+    fn make_file(&self, filename: &str) -> error::Result<()> {
+        let mut volume = self.volume.lock();
+        volume.verify_writable()?;
+        self.verify_new_name(&volume, filename)?;
+
+        let group = volume.group_of_inode(self.inode);
+        let inode_number = volume.allocate_inode(group, false)?;
+
+        let now = time::get_epoch_time();
+        let mut inode = volume.new_inode();
+        inode.set_mode(DEFAULT_FILE_MODE);
+        inode.set_links(1);
+        inode.set_access_time(now);
+        inode.set_change_time(now);
+        inode.set_modification_time(now);
+        volume.write_inode(inode_number, &inode)?;
+
+        match self.add_entry(&mut volume, filename, inode_number, FILE_TYPE_REGULAR) {
+            Ok(()) => Ok(()),
+            Err(status) => {
+                volume.free_inode(inode_number, false)?;
+                Err(status)
+            }
+        }
+    }
+
+    fn make_directory(&self, directory_name: &str) -> error::Result<()> {
+        let mut volume = self.volume.lock();
+        volume.verify_writable()?;
+        self.verify_new_name(&volume, directory_name)?;
+
+        let group = volume.group_of_inode(self.inode);
+        let inode_number = volume.allocate_inode(group, true)?;
+
+        let now = time::get_epoch_time();
+        let mut inode = volume.new_inode();
+        inode.set_mode(DEFAULT_DIRECTORY_MODE);
+        inode.set_links(2);
+        inode.set_access_time(now);
+        inode.set_change_time(now);
+        inode.set_modification_time(now);
+
+        // Create the "." and ".." entries
+        let block_number = match inode.get_or_allocate_block(&mut volume, 0, group) {
+            Ok(block_number) => block_number,
+            Err(status) => {
+                volume.free_inode(inode_number, true)?;
+                return Err(status);
+            }
+        };
+
+        let block_size = volume.block_size();
+        let mut block = Vec::with_capacity(block_size);
+        block.resize(block_size, 0);
+        let dot_length = entry_length(1);
+        write_entry(
+            &volume,
+            &mut block,
+            0,
+            dot_length,
+            inode_number,
+            ".",
+            FILE_TYPE_DIRECTORY,
+        );
+        write_entry(
+            &volume,
+            &mut block,
+            dot_length,
+            block_size - dot_length,
+            self.inode,
+            "..",
+            FILE_TYPE_DIRECTORY,
+        );
+        volume.write_block(block_number, block.as_slice())?;
+
+        inode.set_size(block_size);
+        volume.write_inode(inode_number, &inode)?;
+
+        match self.add_entry(
+            &mut volume,
+            directory_name,
+            inode_number,
+            FILE_TYPE_DIRECTORY,
+        ) {
+            Ok(()) => adjust_links(&volume, self.inode, 1),
+            Err(status) => {
+                inode.truncate_blocks(&mut volume, 0)?;
+                volume.free_inode(inode_number, true)?;
+                Err(status)
+            }
+        }
+    }
+
+    fn rename_file(&self, old_name: &str, new_name: &str) -> error::Result<()> {
+        self.rename(old_name, new_name, false)
+    }
+
+    fn rename_directory(&self, old_name: &str, new_name: &str) -> error::Result<()> {
+        self.rename(old_name, new_name, true)
+    }
+
+    fn move_entry(
+        &self,
+        name: &str,
+        new_directory: &dyn filesystem::Directory,
+        new_name: &str,
+    ) -> error::Result<()> {
+        let new_directory = match new_directory.as_any().downcast_ref::<Directory>() {
+            Some(new_directory) if Arc::ptr_eq(&self.volume, &new_directory.volume) => {
+                new_directory
+            }
+            _ => return Err(error::Status::NotSupported),
+        };
+
+        let mut volume = self.volume.lock();
+        volume.verify_writable()?;
+
+        let entry = self.find(&volume, name)?;
+        new_directory.verify_new_name(&volume, new_name)?;
+
+        let mut inode = volume.read_inode(entry.inode)?;
+        new_directory.add_entry(&mut volume, new_name, entry.inode, file_type_of(&inode))?;
+        let entry = self.find(&volume, name)?;
+        self.remove_entry(&volume, &entry)?;
+
+        if inode.is_directory() && self.inode != new_directory.inode {
+            // Point ".." at the new parent
+            let block_number = inode.get_block(&volume, 0)?;
+            let mut block = read_directory_block(&volume, &inode, 0)?;
+            let (_, record_length, _) = parse_entry(&block, 0)?;
+            let (_, _, parent_name) = parse_entry(&block, record_length)?;
+            if parent_name != b".." {
+                return Err(error::Status::CorruptFilesystem);
+            }
+
+            write_u32(&mut block, record_length, new_directory.inode);
+            volume.write_block(block_number, block.as_slice())?;
+
+            inode.set_change_time(time::get_epoch_time());
+            volume.write_inode(entry.inode, &inode)?;
+
+            adjust_links(&volume, self.inode, -1)?;
+            adjust_links(&volume, new_directory.inode, 1)?;
+        }
+
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> error::Result<()> {
+        let mut volume = self.volume.lock();
+        volume.verify_writable()?;
+
+        let entry = self.find(&volume, name)?;
+        let mut inode = volume.read_inode(entry.inode)?;
+        let directory = inode.is_directory();
+        if directory {
+            self.verify_empty(&volume, &inode)?;
+        }
+
+        self.remove_entry(&volume, &entry)?;
+
+        // Directories are referenced by their own "." entry as well
+        let links = if directory {
+            adjust_links(&volume, self.inode, -1)?;
+            0
+        } else {
+            inode.links().saturating_sub(1)
+        };
+
+        let now = time::get_epoch_time();
+        inode.set_links(links);
+        inode.set_change_time(now);
+        if links == 0 {
+            inode.truncate_blocks(&mut volume, 0)?;
+            inode.set_size(0);
+            inode.set_deletion_time(now);
+            volume.write_inode(entry.inode, &inode)?;
+            volume.free_inode(entry.inode, directory)
+        } else {
+            volume.write_inode(entry.inode, &inode)
+        }
+    }
+
+    fn update_metadata(&self, name: &str, new_metadata: Metadata) -> error::Result<()> {
+        // File sizes are tracked by the file itself
+        let volume = self.volume.lock();
+        let entry = self.find(&volume, name)?;
+        if volume.verify_writable().is_err() {
+            return Ok(());
+        }
+
+        let mut inode = volume.read_inode(entry.inode)?;
+        inode.set_access_time(new_metadata.access_time());
+        inode.set_modification_time(new_metadata.modification_time());
+
+        let mode = inode.mode();
+        if new_metadata.attributes() & filesystem::ATTRIBUTE_READ_ONLY != 0 {
+            inode.set_mode(mode & !MODE_WRITE);
+        } else if mode & MODE_WRITE == 0 {
+            inode.set_mode(mode | OWNER_WRITE);
+        }
+
+        // Named pipes are files with the FIFO type
+        let fifo = new_metadata.attributes() & filesystem::ATTRIBUTE_FIFO != 0;
+        if (inode.is_regular() && fifo) || (inode.is_fifo() && !fifo) {
+            let file_type = if fifo { MODE_FIFO } else { MODE_REGULAR };
+            inode.set_mode((inode.mode() & !MODE_TYPE_MASK) | file_type);
+
+            if volume.file_type() {
+                let directory = volume.read_inode(self.inode)?;
+                let mut block = read_directory_block(&volume, &directory, entry.block_index)?;
+                block[entry.offset + 7] = file_type_of(&inode);
+                let block_number = directory.get_block(&volume, entry.block_index)?;
+                volume.write_block(block_number, block.as_slice())?;
+            }
+        }
+
+        volume.write_inode(entry.inode, &inode)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn flush(&self) -> error::Result<()> {
+        self.volume.lock().flush()
+    }
+
+    fn get_space(&self) -> error::Result<(usize, usize)> {
+        Ok(self.volume.lock().space())
+    }
+}
+
+fn read_directory_block(volume: &Volume, inode: &Inode, index: usize) -> error::Result<Vec<u8>> {
+    // Directories never contain holes
+    let block_number = inode.get_block(volume, index)?;
+    if block_number == 0 {
+        return Err(error::Status::CorruptFilesystem);
+    }
+
+    let mut block = Vec::with_capacity(volume.block_size());
+    block.resize(volume.block_size(), 0);
+    volume.read_block(block_number, block.as_mut_slice())?;
+    Ok(block)
+}
+
+// Returns the inode, record length, and name of the entry at "offset"
+fn parse_entry(block: &[u8], offset: usize) -> error::Result<(u32, usize, &[u8])> {
+    if offset + ENTRY_HEADER_SIZE > block.len() {
+        return Err(error::Status::CorruptFilesystem);
+    }
+
+    let inode = read_u32(block, offset);
+    let record_length = read_u16(block, offset + 4) as usize;
+    let name_length = block[offset + 6] as usize;
+    if record_length < ENTRY_HEADER_SIZE
+        || record_length % 4 != 0
+        || offset + record_length > block.len()
+        || ENTRY_HEADER_SIZE + name_length > record_length
+    {
+        return Err(error::Status::CorruptFilesystem);
+    }
+
+    let name_start = offset + ENTRY_HEADER_SIZE;
+    Ok((
+        inode,
+        record_length,
+        &block[name_start..name_start + name_length],
+    ))
+}
+
+fn write_entry(
+    volume: &Volume,
+    block: &mut [u8],
+    offset: usize,
+    record_length: usize,
+    inode: u32,
+    name: &str,
+    file_type: u8,
+) {
+    write_u32(block, offset, inode);
+    write_u16(block, offset + 4, record_length as u16);
+    block[offset + 6] = name.len() as u8;
+
+    // Without the file type feature this byte is the high byte of the name length
+    block[offset + 7] = if volume.file_type() { file_type } else { 0 };
+
+    let name_start = offset + ENTRY_HEADER_SIZE;
+    block[name_start..name_start + name.len()].copy_from_slice(name.as_bytes());
+}
+
+fn entry_length(name_length: usize) -> usize {
+    (ENTRY_HEADER_SIZE + name_length + 3) & !3
+}
+
+fn file_type_of(inode: &Inode) -> u8 {
+    if inode.is_directory() {
+        FILE_TYPE_DIRECTORY
+    } else if inode.is_fifo() {
+        FILE_TYPE_FIFO
+    } else {
+        FILE_TYPE_REGULAR
+    }
+}
+
+fn touch_directory(volume: &Volume, inode_number: u32, mut inode: Inode) -> error::Result<()> {
+    let now = time::get_epoch_time();
+    inode.set_change_time(now);
+    inode.set_modification_time(now);
+    volume.write_inode(inode_number, &inode)
+}
+
+fn adjust_links(volume: &Volume, inode_number: u32, difference: isize) -> error::Result<()> {
+    let mut inode = volume.read_inode(inode_number)?;
+    inode.set_links((inode.links() as isize + difference) as u16);
+    volume.write_inode(inode_number, &inode)
+}
@@ -0,0 +1,142 @@
+use super::volume::VolumeBox;
+use crate::error;
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+// The size is shared with other open links to the inode and only changed with the volume locked
+pub struct File {
+    inode: u32,
+    size: Arc<AtomicUsize>,
+    volume: VolumeBox,
+}
+
+impl File {
+    pub fn new(inode: u32, size: Arc<AtomicUsize>, volume: VolumeBox) -> Self {
+        File {
+            inode,
+            size,
+            volume,
+        }
+    }
+}
+
+impl crate::filesystem::File for File {
+    fn write(&mut self, offset: usize, buffer: &[u8]) -> error::Result<isize> {
+        let mut volume = self.volume.lock();
+        if offset + buffer.len() > self.size.load(Ordering::Acquire) {
+            return Err(error::Status::OutOfRange);
+        }
+
+        volume.verify_writable()?;
+
+        let block_size = volume.block_size();
+        let group = volume.group_of_inode(self.inode);
+        let mut inode = volume.read_inode(self.inode)?;
+        let mut block = Vec::with_capacity(block_size);
+        block.resize(block_size, 0);
+
+        let mut written = 0;
+        while written < buffer.len() {
+            let position = offset + written;
+            let block_offset = position % block_size;
+            let length = core::cmp::min(block_size - block_offset, buffer.len() - written);
+
+            // Holes are filled in as they are written to
+            let block_number =
+                inode.get_or_allocate_block(&mut volume, position / block_size, group)?;
+            if length != block_size {
+                volume.read_block(block_number, block.as_mut_slice())?;
+            }
+
+            block[block_offset..block_offset + length]
+                .copy_from_slice(&buffer[written..written + length]);
+            volume.write_block(block_number, block.as_slice())?;
+
+            written += length;
+        }
+
+        volume.write_inode(self.inode, &inode)?;
+        Ok(written as isize)
+    }
+
+    fn read(&mut self, offset: usize, buffer: &mut [u8]) -> error::Result<isize> {
+        let volume = self.volume.lock();
+        let size = self.size.load(Ordering::Acquire);
+        if offset >= size {
+            return Ok(-1);
+        }
+
+        let block_size = volume.block_size();
+        let inode = volume.read_inode(self.inode)?;
+        let mut block = Vec::with_capacity(block_size);
+        block.resize(block_size, 0);
+
+        let length = core::cmp::min(buffer.len(), size - offset);
+        let mut read = 0;
+        while read < length {
+            let position = offset + read;
+            let block_offset = position % block_size;
+            let chunk = core::cmp::min(block_size - block_offset, length - read);
+
+            // Holes read back as zeroes
+            match inode.get_block(&volume, position / block_size)? {
+                0 => {
+                    for byte in &mut block {
+                        *byte = 0;
+                    }
+                }
+                block_number => volume.read_block(block_number, block.as_mut_slice())?,
+            }
+
+            buffer[read..read + chunk].copy_from_slice(&block[block_offset..block_offset + chunk]);
+            read += chunk;
+        }
+
+        for byte in &mut buffer[length..] {
+            *byte = 0;
+        }
+
+        Ok(length as isize)
+    }
+
+    fn set_length(&mut self, new_length: usize) -> error::Result<()> {
+        let mut volume = self.volume.lock();
+        volume.verify_writable()?;
+
+        let block_size = volume.block_size();
+        let mut inode = volume.read_inode(self.inode)?;
+        let size = self.size.load(Ordering::Acquire);
+
+        // Zero the tail of the last partial block so its stale bytes never become visible
+        let boundary = core::cmp::min(size, new_length);
+        if boundary % block_size != 0 {
+            let block_number = inode.get_block(&volume, boundary / block_size)?;
+            if block_number != 0 {
+                let mut block = Vec::with_capacity(block_size);
+                block.resize(block_size, 0);
+                volume.read_block(block_number, block.as_mut_slice())?;
+                for byte in &mut block[boundary % block_size..] {
+                    *byte = 0;
+                }
+                volume.write_block(block_number, block.as_slice())?;
+            }
+        }
+
+        if new_length < size {
+            inode.truncate_blocks(&mut volume, (new_length + block_size - 1) / block_size)?;
+        }
+
+        inode.set_size(new_length);
+        volume.write_inode(self.inode, &inode)?;
+        self.size.store(new_length, Ordering::Release);
+        Ok(())
+    }
+
+    fn get_length(&self) -> usize {
+        self.size.load(Ordering::Acquire)
+    }
+
+    fn flush(&mut self) -> error::Result<()> {
+        self.volume.lock().flush()
+    }
+}
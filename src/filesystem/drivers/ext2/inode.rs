@@ -0,0 +1,323 @@
+use super::volume::{read_u16, read_u32, write_u16, write_u32, Volume};
+use crate::{
+    error,
+    filesystem::{self, Metadata},
+};
+use alloc::vec::Vec;
+
+pub struct Inode {
+    data: Vec<u8>,
+}
+
+pub const MODE_TYPE_MASK: u16 = 0xF000;
+pub const MODE_FIFO: u16 = 0x1000;
+pub const MODE_DIRECTORY: u16 = 0x4000;
+pub const MODE_REGULAR: u16 = 0x8000;
+pub const MODE_WRITE: u16 = 0o222;
+
+const DIRECT_BLOCKS: usize = 12;
+
+impl Inode {
+    pub fn new(data: Vec<u8>) -> Self {
+        Inode { data }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+
+    pub fn mode(&self) -> u16 {
+        read_u16(&self.data, 0)
+    }
+
+    pub fn set_mode(&mut self, mode: u16) {
+        write_u16(&mut self.data, 0, mode)
+    }
+
+    pub fn is_directory(&self) -> bool {
+        self.mode() & MODE_TYPE_MASK == MODE_DIRECTORY
+    }
+
+    pub fn is_regular(&self) -> bool {
+        self.mode() & MODE_TYPE_MASK == MODE_REGULAR
+    }
+
+    pub fn is_fifo(&self) -> bool {
+        self.mode() & MODE_TYPE_MASK == MODE_FIFO
+    }
+
+    // Regular files keep the high 32 bits of their size in the directory ACL field
+    pub fn size(&self) -> usize {
+        let low = read_u32(&self.data, 4) as usize;
+        if self.is_regular() {
+            low | ((read_u32(&self.data, 108) as usize) << 32)
+        } else {
+            low
+        }
+    }
+
+    pub fn set_size(&mut self, size: usize) {
+        write_u32(&mut self.data, 4, size as u32);
+        if self.is_regular() {
+            write_u32(&mut self.data, 108, (size >> 32) as u32);
+        }
+    }
+
+    pub fn access_time(&self) -> isize {
+        read_u32(&self.data, 8) as isize
+    }
+
+    pub fn set_access_time(&mut self, time: isize) {
+        write_u32(&mut self.data, 8, time as u32)
+    }
+
+    pub fn change_time(&self) -> isize {
+        read_u32(&self.data, 12) as isize
+    }
+
+    pub fn set_change_time(&mut self, time: isize) {
+        write_u32(&mut self.data, 12, time as u32)
+    }
+
+    pub fn modification_time(&self) -> isize {
+        read_u32(&self.data, 16) as isize
+    }
+
+    pub fn set_modification_time(&mut self, time: isize) {
+        write_u32(&mut self.data, 16, time as u32)
+    }
+
+    pub fn set_deletion_time(&mut self, time: isize) {
+        write_u32(&mut self.data, 20, time as u32)
+    }
+
+    pub fn links(&self) -> u16 {
+        read_u16(&self.data, 26)
+    }
+
+    pub fn set_links(&mut self, links: u16) {
+        write_u16(&mut self.data, 26, links)
+    }
+
+    // Counted in 512 byte sectors
+    pub fn sectors(&self) -> u32 {
+        read_u32(&self.data, 28)
+    }
+
+    pub fn set_sectors(&mut self, sectors: u32) {
+        write_u32(&mut self.data, 28, sectors)
+    }
+
+    pub fn block(&self, index: usize) -> u32 {
+        read_u32(&self.data, 40 + index * 4)
+    }
+
+    pub fn set_block(&mut self, index: usize, block: u32) {
+        write_u32(&mut self.data, 40 + index * 4, block)
+    }
+
+    pub fn metadata(&self, mut attributes: usize) -> Metadata {
+        if self.mode() & MODE_WRITE == 0 {
+            attributes |= filesystem::ATTRIBUTE_READ_ONLY;
+        }
+
+        if self.is_fifo() {
+            attributes |= filesystem::ATTRIBUTE_FIFO;
+        }
+
+        Metadata::new(
+            self.size(),
+            self.is_directory(),
+            attributes,
+            self.change_time(),
+            self.access_time(),
+            self.modification_time(),
+        )
+    }
+
+    // Returns 0 for holes
+    pub fn get_block(&self, volume: &Volume, index: usize) -> error::Result<u32> {
+        if index < DIRECT_BLOCKS {
+            return Ok(self.block(index));
+        }
+
+        let (level, mut index) = locate(volume, index)?;
+        let per_block = volume.block_size() / 4;
+        let mut divisor = per_block.pow(level as u32 - 1);
+        let mut block = self.block(DIRECT_BLOCKS + level - 1);
+        for _ in 0..level {
+            if block == 0 {
+                return Ok(0);
+            }
+
+            block = read_pointer(volume, block, index / divisor)?;
+            index %= divisor;
+            divisor = core::cmp::max(divisor / per_block, 1);
+        }
+
+        Ok(block)
+    }
+
+    pub fn get_or_allocate_block(
+        &mut self,
+        volume: &mut Volume,
+        index: usize,
+        group: usize,
+    ) -> error::Result<u32> {
+        if index < DIRECT_BLOCKS {
+            if self.block(index) == 0 {
+                let block = self.allocate(volume, group)?;
+                self.set_block(index, block);
+            }
+
+            return Ok(self.block(index));
+        }
+
+        let (level, mut index) = locate(volume, index)?;
+        let per_block = volume.block_size() / 4;
+        let mut divisor = per_block.pow(level as u32 - 1);
+
+        if self.block(DIRECT_BLOCKS + level - 1) == 0 {
+            let block = self.allocate(volume, group)?;
+            self.set_block(DIRECT_BLOCKS + level - 1, block);
+        }
+
+        let mut block = self.block(DIRECT_BLOCKS + level - 1);
+        for _ in 0..level {
+            let mut next = read_pointer(volume, block, index / divisor)?;
+            if next == 0 {
+                next = self.allocate(volume, group)?;
+                write_pointer(volume, block, index / divisor, next)?;
+            }
+
+            block = next;
+            index %= divisor;
+            divisor = core::cmp::max(divisor / per_block, 1);
+        }
+
+        Ok(block)
+    }
+
+    // Frees every data block from "keep" onwards along with unneeded indirect blocks
+    pub fn truncate_blocks(&mut self, volume: &mut Volume, keep: usize) -> error::Result<()> {
+        for index in keep..DIRECT_BLOCKS {
+            let block = self.block(index);
+            if block != 0 {
+                self.free(volume, block)?;
+                self.set_block(index, 0);
+            }
+        }
+
+        let per_block = volume.block_size() / 4;
+        let mut start = DIRECT_BLOCKS;
+        let mut span = per_block;
+        for level in 1..=3 {
+            let root = self.block(DIRECT_BLOCKS + level - 1);
+            let first = keep.saturating_sub(start);
+            if root != 0 && first < span && self.truncate_tree(volume, root, level, first)? {
+                self.set_block(DIRECT_BLOCKS + level - 1, 0);
+            }
+
+            start += span;
+            span *= per_block;
+        }
+
+        Ok(())
+    }
+
+    // Returns true if "block" was freed
+    fn truncate_tree(
+        &mut self,
+        volume: &mut Volume,
+        block: u32,
+        level: usize,
+        first: usize,
+    ) -> error::Result<bool> {
+        let per_block = volume.block_size() / 4;
+        let span = per_block.pow(level as u32 - 1);
+
+        let mut pointers = Vec::with_capacity(volume.block_size());
+        pointers.resize(volume.block_size(), 0);
+        volume.read_block(block, pointers.as_mut_slice())?;
+
+        let mut modified = false;
+        for i in 0..per_block {
+            let child = read_u32(&pointers, i * 4);
+            let child_first = first.saturating_sub(i * span);
+            if child == 0 || child_first >= span {
+                continue;
+            }
+
+            let freed = if level == 1 {
+                self.free(volume, child)?;
+                true
+            } else {
+                self.truncate_tree(volume, child, level - 1, child_first)?
+            };
+
+            if freed {
+                write_u32(&mut pointers, i * 4, 0);
+                modified = true;
+            }
+        }
+
+        if first == 0 {
+            self.free(volume, block)?;
+            return Ok(true);
+        }
+
+        if modified {
+            volume.write_block(block, pointers.as_slice())?;
+        }
+
+        Ok(false)
+    }
+
+    fn allocate(&mut self, volume: &mut Volume, group: usize) -> error::Result<u32> {
+        let block = volume.allocate_block(group)?;
+        self.set_sectors(self.sectors() + (volume.block_size() / 512) as u32);
+        Ok(block)
+    }
+
+    fn free(&mut self, volume: &mut Volume, block: u32) -> error::Result<()> {
+        volume.free_block(block)?;
+        self.set_sectors(
+            self.sectors()
+                .saturating_sub((volume.block_size() / 512) as u32),
+        );
+        Ok(())
+    }
+}
+
+// Returns the level of indirection and the index within that level
+fn locate(volume: &Volume, index: usize) -> error::Result<(usize, usize)> {
+    let per_block = volume.block_size() / 4;
+    let mut index = index - DIRECT_BLOCKS;
+    let mut span = per_block;
+    for level in 1..=3 {
+        if index < span {
+            return Ok((level, index));
+        }
+
+        index -= span;
+        span *= per_block;
+    }
+
+    Err(error::Status::OutOfRange)
+}
+
+fn read_pointer(volume: &Volume, block: u32, index: usize) -> error::Result<u32> {
+    let mut buffer = [0u8; 4];
+    volume.read_bytes(
+        block as usize * volume.block_size() + index * 4,
+        &mut buffer,
+    )?;
+    Ok(u32::from_le_bytes(buffer))
+}
+
+fn write_pointer(volume: &Volume, block: u32, index: usize, value: u32) -> error::Result<()> {
+    volume.write_bytes(
+        block as usize * volume.block_size() + index * 4,
+        &value.to_le_bytes(),
+    )
+}
@@ -0,0 +1,104 @@
+use crate::{device::DeviceReference, error, filesystem::FilesystemStarter, locks::Mutex};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+mod directory;
+mod file;
+mod inode;
+mod volume;
+
+const SUPERBLOCK_SECTOR: usize = 2;
+const SUPERBLOCK_SIZE: usize = 1024;
+
+const EXT2_MAGIC: u16 = 0xEF53;
+const ROOT_INODE: u32 = 2;
+
+const INCOMPAT_FILETYPE: u32 = 0x2;
+const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+const RO_COMPAT_LARGE_FILE: u32 = 0x2;
+
+pub fn detect_ext2_filesystem(
+    drive_lock: DeviceReference,
+    _: usize,
+) -> error::Result<Option<FilesystemStarter>> {
+    // Get superblock
+    let mut superblock = Vec::with_capacity(SUPERBLOCK_SIZE);
+    superblock.resize(SUPERBLOCK_SIZE, 0);
+    drive_lock
+        .lock()
+        .read(SUPERBLOCK_SECTOR, superblock.as_mut_slice())?;
+
+    if volume::read_u16(&superblock, 56) != EXT2_MAGIC {
+        return Ok(None);
+    }
+
+    // Verify the superblock is sane, directory record lengths must fit in 16 bits
+    let log_block_size = volume::read_u32(&superblock, 24);
+    let blocks_count = volume::read_u32(&superblock, 4);
+    let first_data_block = volume::read_u32(&superblock, 20);
+    let blocks_per_group = volume::read_u32(&superblock, 32);
+    let inodes_per_group = volume::read_u32(&superblock, 40);
+    if log_block_size > 5
+        || blocks_per_group == 0
+        || inodes_per_group == 0
+        || blocks_count <= first_data_block
+    {
+        return Ok(None);
+    }
+
+    // Each group's bitmaps are a single block
+    let bits_per_block = 8 * (1024 << log_block_size);
+    if blocks_per_group > bits_per_block || inodes_per_group > bits_per_block {
+        return Ok(None);
+    }
+
+    // Revision 0 volumes have no feature flags
+    let (incompatible, read_only_compatible) = if volume::read_u32(&superblock, 76) >= 1 {
+        (
+            volume::read_u32(&superblock, 96),
+            volume::read_u32(&superblock, 100),
+        )
+    } else {
+        (0, 0)
+    };
+
+    if incompatible & !INCOMPAT_FILETYPE != 0 {
+        return Ok(None);
+    }
+
+    // Inodes must hold the fixed fields and evenly fill blocks
+    let inode_size = if volume::read_u32(&superblock, 76) >= 1 {
+        volume::read_u16(&superblock, 88) as usize
+    } else {
+        128
+    };
+    if !inode_size.is_power_of_two() || inode_size < 128 || inode_size > 1024 << log_block_size {
+        return Ok(None);
+    }
+
+    // Unknown read-only compatible features still allow reading
+    let read_only = read_only_compatible & !(RO_COMPAT_SPARSE_SUPER | RO_COMPAT_LARGE_FILE) != 0;
+
+    let volume_name = String::from_utf8_lossy(&superblock[120..136])
+        .trim_end_matches('\0')
+        .to_string();
+
+    // Create volume
+    let volume = Arc::new(Mutex::new(volume::Volume::new(
+        drive_lock.clone(),
+        superblock,
+        incompatible & INCOMPAT_FILETYPE != 0,
+        read_only,
+    )?));
+
+    // Create filesystem starter
+    Ok(Some(FilesystemStarter::new(
+        Box::new(directory::Directory::new(ROOT_INODE, volume)),
+        volume_name,
+        "ext2",
+    )))
+}
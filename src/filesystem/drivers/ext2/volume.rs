@@ -0,0 +1,400 @@
+use super::inode::Inode;
+use crate::{
+    device::DeviceReference,
+    error,
+    filesystem::cache::{self, BLOCK_SIZE},
+    locks::Mutex,
+};
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::sync::atomic::AtomicUsize;
+
+pub type VolumeBox = Arc<Mutex<Volume>>;
+
+pub struct Volume {
+    drive: DeviceReference,
+    superblock: Vec<u8>,
+    group_descriptors: Vec<u8>,
+    group_descriptor_block: u32,
+    group_count: usize,
+    block_size: usize,
+    blocks_count: u32,
+    blocks_per_group: u32,
+    inodes_per_group: u32,
+    inode_size: usize,
+    first_data_block: u32,
+    file_type: bool,
+    read_only: bool,
+    // Files opened through different links to an inode share its size
+    open_sizes: Vec<(u32, Weak<AtomicUsize>)>,
+}
+
+const SUPERBLOCK_OFFSET: usize = 1024;
+const SUPERBLOCK_SIZE: usize = 1024;
+const GROUP_DESCRIPTOR_SIZE: usize = 32;
+
+const SUPERBLOCK_FREE_BLOCKS: usize = 12;
+const SUPERBLOCK_FREE_INODES: usize = 16;
+
+const GROUP_BLOCK_BITMAP: usize = 0;
+const GROUP_INODE_BITMAP: usize = 4;
+const GROUP_INODE_TABLE: usize = 8;
+const GROUP_FREE_BLOCKS: usize = 12;
+const GROUP_FREE_INODES: usize = 14;
+const GROUP_USED_DIRECTORIES: usize = 16;
+
+impl Volume {
+    pub fn new(
+        drive: DeviceReference,
+        superblock: Vec<u8>,
+        file_type: bool,
+        read_only: bool,
+    ) -> error::Result<Self> {
+        let block_size = 1024 << read_u32(&superblock, 24);
+        let blocks_count = read_u32(&superblock, 4);
+        let first_data_block = read_u32(&superblock, 20);
+        let blocks_per_group = read_u32(&superblock, 32);
+        let inodes_per_group = read_u32(&superblock, 40);
+        let inode_size = if read_u32(&superblock, 76) >= 1 {
+            read_u16(&superblock, 88) as usize
+        } else {
+            128
+        };
+
+        let group_count =
+            ((blocks_count - first_data_block + blocks_per_group - 1) / blocks_per_group) as usize;
+
+        let mut volume = Volume {
+            drive,
+            superblock,
+            group_descriptors: Vec::new(),
+            group_descriptor_block: first_data_block + 1,
+            group_count,
+            block_size,
+            blocks_count,
+            blocks_per_group,
+            inodes_per_group,
+            inode_size,
+            first_data_block,
+            file_type,
+            read_only,
+            open_sizes: Vec::new(),
+        };
+
+        // Read the block group descriptor table
+        let table_blocks = (group_count * GROUP_DESCRIPTOR_SIZE + block_size - 1) / block_size;
+        let mut group_descriptors = Vec::with_capacity(table_blocks * block_size);
+        group_descriptors.resize(table_blocks * block_size, 0);
+        for i in 0..table_blocks {
+            volume.read_block(
+                volume.group_descriptor_block + i as u32,
+                &mut group_descriptors[i * block_size..(i + 1) * block_size],
+            )?;
+        }
+        volume.group_descriptors = group_descriptors;
+
+        Ok(volume)
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn file_type(&self) -> bool {
+        self.file_type
+    }
+
+    pub fn open_size(&mut self, inode: u32, size: usize) -> Arc<AtomicUsize> {
+        self.open_sizes.retain(|(_, size)| size.strong_count() > 0);
+        if let Some(size) = self
+            .open_sizes
+            .iter()
+            .find(|(number, _)| *number == inode)
+            .and_then(|(_, size)| size.upgrade())
+        {
+            return size;
+        }
+
+        let size = Arc::new(AtomicUsize::new(size));
+        self.open_sizes.push((inode, Arc::downgrade(&size)));
+        size
+    }
+
+    pub fn verify_writable(&self) -> error::Result<()> {
+        if self.read_only {
+            Err(error::Status::ReadOnlyFilesystem)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn group_of_inode(&self, inode: u32) -> usize {
+        ((inode - 1) / self.inodes_per_group) as usize
+    }
+
+    pub fn flush(&self) -> error::Result<()> {
+        cache::flush(&self.drive)
+    }
+
+    // Returns the total and free bytes
+    pub fn space(&self) -> (usize, usize) {
+        (
+            self.blocks_count as usize * self.block_size,
+            read_u32(&self.superblock, SUPERBLOCK_FREE_BLOCKS) as usize * self.block_size,
+        )
+    }
+
+    pub fn read_block(&self, block: u32, buffer: &mut [u8]) -> error::Result<()> {
+        cache::read(
+            &self.drive,
+            block as usize * (self.block_size / BLOCK_SIZE),
+            buffer,
+        )
+    }
+
+    pub fn write_block(&self, block: u32, buffer: &[u8]) -> error::Result<()> {
+        cache::write(
+            &self.drive,
+            block as usize * (self.block_size / BLOCK_SIZE),
+            buffer,
+        )
+    }
+
+    // Reads bytes at any offset through the sectors containing them
+    pub fn read_bytes(&self, offset: usize, buffer: &mut [u8]) -> error::Result<()> {
+        let start = offset / BLOCK_SIZE;
+        let end = (offset + buffer.len() + BLOCK_SIZE - 1) / BLOCK_SIZE;
+        let mut sectors = Vec::with_capacity((end - start) * BLOCK_SIZE);
+        sectors.resize((end - start) * BLOCK_SIZE, 0);
+        cache::read(&self.drive, start, sectors.as_mut_slice())?;
+
+        let sector_offset = offset - start * BLOCK_SIZE;
+        buffer.copy_from_slice(&sectors[sector_offset..sector_offset + buffer.len()]);
+        Ok(())
+    }
+
+    pub fn write_bytes(&self, offset: usize, buffer: &[u8]) -> error::Result<()> {
+        let start = offset / BLOCK_SIZE;
+        let end = (offset + buffer.len() + BLOCK_SIZE - 1) / BLOCK_SIZE;
+        let mut sectors = Vec::with_capacity((end - start) * BLOCK_SIZE);
+        sectors.resize((end - start) * BLOCK_SIZE, 0);
+        cache::read(&self.drive, start, sectors.as_mut_slice())?;
+
+        let sector_offset = offset - start * BLOCK_SIZE;
+        sectors[sector_offset..sector_offset + buffer.len()].copy_from_slice(buffer);
+        cache::write(&self.drive, start, sectors.as_slice())
+    }
+
+    pub fn read_inode(&self, inode: u32) -> error::Result<Inode> {
+        let mut data = Vec::with_capacity(self.inode_size);
+        data.resize(self.inode_size, 0);
+        self.read_bytes(self.inode_offset(inode)?, data.as_mut_slice())?;
+        Ok(Inode::new(data))
+    }
+
+    pub fn write_inode(&self, inode: u32, data: &Inode) -> error::Result<()> {
+        self.write_bytes(self.inode_offset(inode)?, data.as_slice())
+    }
+
+    pub fn new_inode(&self) -> Inode {
+        let mut data = Vec::with_capacity(self.inode_size);
+        data.resize(self.inode_size, 0);
+        Inode::new(data)
+    }
+
+    // Allocated blocks are zeroed
+    pub fn allocate_block(&mut self, group_hint: usize) -> error::Result<u32> {
+        self.verify_writable()?;
+
+        for i in 0..self.group_count {
+            let group = (group_hint + i) % self.group_count;
+            if self.read_group_u16(group, GROUP_FREE_BLOCKS) == 0 {
+                continue;
+            }
+
+            let group_start = self.first_data_block + group as u32 * self.blocks_per_group;
+            let limit = core::cmp::min(self.blocks_per_group, self.blocks_count - group_start);
+            let bitmap = self.read_group_u32(group, GROUP_BLOCK_BITMAP);
+            let index = match self.allocate_bit(bitmap, limit)? {
+                Some(index) => index,
+                None => continue,
+            };
+
+            self.adjust_free_count(group, GROUP_FREE_BLOCKS, SUPERBLOCK_FREE_BLOCKS, -1)?;
+
+            let block = group_start + index;
+            let mut zeroes = Vec::with_capacity(self.block_size);
+            zeroes.resize(self.block_size, 0);
+            self.write_block(block, zeroes.as_slice())?;
+
+            return Ok(block);
+        }
+
+        Err(error::Status::NoSpace)
+    }
+
+    pub fn free_block(&mut self, block: u32) -> error::Result<()> {
+        if block < self.first_data_block || block >= self.blocks_count {
+            return Err(error::Status::CorruptFilesystem);
+        }
+
+        let group = ((block - self.first_data_block) / self.blocks_per_group) as usize;
+        let index = (block - self.first_data_block) % self.blocks_per_group;
+        let bitmap = self.read_group_u32(group, GROUP_BLOCK_BITMAP);
+        self.free_bit(bitmap, index)?;
+
+        self.adjust_free_count(group, GROUP_FREE_BLOCKS, SUPERBLOCK_FREE_BLOCKS, 1)
+    }
+
+    pub fn allocate_inode(&mut self, group_hint: usize, directory: bool) -> error::Result<u32> {
+        self.verify_writable()?;
+
+        for i in 0..self.group_count {
+            let group = (group_hint + i) % self.group_count;
+            if self.read_group_u16(group, GROUP_FREE_INODES) == 0 {
+                continue;
+            }
+
+            let bitmap = self.read_group_u32(group, GROUP_INODE_BITMAP);
+            let index = match self.allocate_bit(bitmap, self.inodes_per_group)? {
+                Some(index) => index,
+                None => continue,
+            };
+
+            self.adjust_free_count(group, GROUP_FREE_INODES, SUPERBLOCK_FREE_INODES, -1)?;
+            if directory {
+                self.adjust_used_directories(group, 1)?;
+            }
+
+            return Ok(group as u32 * self.inodes_per_group + index + 1);
+        }
+
+        Err(error::Status::NoSpace)
+    }
+
+    pub fn free_inode(&mut self, inode: u32, directory: bool) -> error::Result<()> {
+        let group = self.group_of_inode(inode);
+        let index = (inode - 1) % self.inodes_per_group;
+        let bitmap = self.read_group_u32(group, GROUP_INODE_BITMAP);
+        self.free_bit(bitmap, index)?;
+
+        self.adjust_free_count(group, GROUP_FREE_INODES, SUPERBLOCK_FREE_INODES, 1)?;
+        if directory {
+            self.adjust_used_directories(group, -1)?;
+        }
+
+        Ok(())
+    }
+
+    fn inode_offset(&self, inode: u32) -> error::Result<usize> {
+        if inode == 0 || self.group_of_inode(inode) >= self.group_count {
+            return Err(error::Status::CorruptFilesystem);
+        }
+
+        let group = self.group_of_inode(inode);
+        let index = ((inode - 1) % self.inodes_per_group) as usize;
+        let table = self.read_group_u32(group, GROUP_INODE_TABLE) as usize;
+        Ok(table * self.block_size + index * self.inode_size)
+    }
+
+    fn allocate_bit(&self, bitmap: u32, limit: u32) -> error::Result<Option<u32>> {
+        let mut buffer = Vec::with_capacity(self.block_size);
+        buffer.resize(self.block_size, 0);
+        self.read_block(bitmap, buffer.as_mut_slice())?;
+
+        for index in 0..limit {
+            let byte = (index / 8) as usize;
+            let bit = 1 << (index % 8);
+            if buffer[byte] & bit == 0 {
+                buffer[byte] |= bit;
+                self.write_block(bitmap, buffer.as_slice())?;
+                return Ok(Some(index));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn free_bit(&self, bitmap: u32, index: u32) -> error::Result<()> {
+        let mut buffer = Vec::with_capacity(self.block_size);
+        buffer.resize(self.block_size, 0);
+        self.read_block(bitmap, buffer.as_mut_slice())?;
+
+        let byte = (index / 8) as usize;
+        let bit = 1 << (index % 8);
+        if buffer[byte] & bit == 0 {
+            return Err(error::Status::CorruptFilesystem);
+        }
+
+        buffer[byte] &= !bit;
+        self.write_block(bitmap, buffer.as_slice())
+    }
+
+    fn adjust_free_count(
+        &mut self,
+        group: usize,
+        group_field: usize,
+        superblock_field: usize,
+        difference: isize,
+    ) -> error::Result<()> {
+        let count = self.read_group_u16(group, group_field) as isize + difference;
+        self.write_group_u16(group, group_field, count as u16)?;
+
+        let count = read_u32(&self.superblock, superblock_field) as isize + difference;
+        write_u32(&mut self.superblock, superblock_field, count as u32);
+        self.write_bytes(SUPERBLOCK_OFFSET, &self.superblock[..SUPERBLOCK_SIZE])
+    }
+
+    fn adjust_used_directories(&mut self, group: usize, difference: isize) -> error::Result<()> {
+        let count = self.read_group_u16(group, GROUP_USED_DIRECTORIES) as isize + difference;
+        self.write_group_u16(group, GROUP_USED_DIRECTORIES, count as u16)
+    }
+
+    fn read_group_u16(&self, group: usize, field: usize) -> u16 {
+        read_u16(
+            &self.group_descriptors,
+            group * GROUP_DESCRIPTOR_SIZE + field,
+        )
+    }
+
+    fn read_group_u32(&self, group: usize, field: usize) -> u32 {
+        read_u32(
+            &self.group_descriptors,
+            group * GROUP_DESCRIPTOR_SIZE + field,
+        )
+    }
+
+    fn write_group_u16(&mut self, group: usize, field: usize, value: u16) -> error::Result<()> {
+        let offset = group * GROUP_DESCRIPTOR_SIZE + field;
+        write_u16(&mut self.group_descriptors, offset, value);
+
+        // Write back the block containing the descriptor
+        let block_index = offset / self.block_size;
+        self.write_block(
+            self.group_descriptor_block + block_index as u32,
+            &self.group_descriptors
+                [block_index * self.block_size..(block_index + 1) * self.block_size],
+        )
+    }
+}
+
+pub fn read_u16(buffer: &[u8], offset: usize) -> u16 {
+    (buffer[offset] as u16) | ((buffer[offset + 1] as u16) << 8)
+}
+
+pub fn read_u32(buffer: &[u8], offset: usize) -> u32 {
+    (buffer[offset] as u32)
+        | ((buffer[offset + 1] as u32) << 8)
+        | ((buffer[offset + 2] as u32) << 16)
+        | ((buffer[offset + 3] as u32) << 24)
+}
+
+pub fn write_u16(buffer: &mut [u8], offset: usize, value: u16) {
+    buffer[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+pub fn write_u32(buffer: &mut [u8], offset: usize, value: u32) {
+    buffer[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
@@ -0,0 +1,398 @@
+use super::fat::{Cluster, ClusterState, FAT};
+use crate::error;
+use alloc::{vec, vec::Vec};
+
+// Counts of each problem found, repairs were made if requested
+#[repr(C)]
+#[derive(Default)]
+pub struct CheckReport {
+    lost_clusters: usize,
+    cross_linked_chains: usize,
+    bad_chains: usize,
+    size_mismatches: usize,
+    invalid_long_names: usize,
+}
+
+struct Checker<'a> {
+    fat: &'a mut FAT,
+    repair: bool,
+    used: Vec<u64>,
+    report: CheckReport,
+}
+
+// Contents of a directory and where they were read from, no clusters means the root region
+struct DirectoryData {
+    clusters: Vec<Cluster>,
+    data: Vec<u8>,
+    modified: bool,
+}
+
+const ENTRY_SIZE: usize = 32;
+
+const ATTRIBUTE_VOLUME_ID: u8 = 0x08;
+const ATTRIBUTE_DIRECTORY: u8 = 0x10;
+const ATTRIBUTE_LONG_FILE_NAME: u8 = 0x0F;
+
+const ENTRY_END: u8 = 0x00;
+const ENTRY_FREE: u8 = 0xE5;
+const LAST_LONG_ENTRY: u8 = 0x40;
+
+impl CheckReport {
+    pub fn problems(&self) -> usize {
+        self.lost_clusters
+            + self.cross_linked_chains
+            + self.bad_chains
+            + self.size_mismatches
+            + self.invalid_long_names
+    }
+}
+
+pub fn check(
+    fat: &mut FAT,
+    root_directory_cluster: Cluster,
+    repair: bool,
+) -> error::Result<CheckReport> {
+    let words = (fat.cluster_top() as usize + 63) / 64;
+    let mut checker = Checker {
+        fat,
+        repair,
+        used: vec![0; words],
+        report: CheckReport::default(),
+    };
+
+    // Walk every directory tree from the root
+    let mut directories = Vec::new();
+    let root_directory = if root_directory_cluster == 0 {
+        checker.read_root_region()?
+    } else {
+        let (chain, _) = checker.walk_chain(root_directory_cluster)?;
+        checker.read_directory(chain)?
+    };
+    directories.push(root_directory);
+
+    while let Some(mut directory) = directories.pop() {
+        let subdirectories = checker.check_directory(&mut directory)?;
+        checker.write_directory(&directory)?;
+
+        for chain in subdirectories {
+            directories.push(checker.read_directory(chain)?);
+        }
+    }
+
+    checker.check_lost_clusters()?;
+
+    if repair {
+        checker.fat.recount_free_clusters()?;
+        checker.fat.flush()?;
+    }
+
+    Ok(checker.report)
+}
+
+impl<'a> Checker<'a> {
+    fn is_used(&self, cluster: Cluster) -> bool {
+        self.used[cluster as usize / 64] & (1 << (cluster % 64)) != 0
+    }
+
+    fn set_used(&mut self, cluster: Cluster, used: bool) {
+        if used {
+            self.used[cluster as usize / 64] |= 1 << (cluster % 64);
+        } else {
+            self.used[cluster as usize / 64] &= !(1 << (cluster % 64));
+        }
+    }
+
+    // Follows a chain and claims its clusters, stopping at an invalid or already claimed link
+    fn walk_chain(&mut self, first_cluster: Cluster) -> error::Result<(Vec<Cluster>, bool)> {
+        let mut chain = Vec::new();
+        let mut cluster = first_cluster;
+        let complete = loop {
+            if cluster < 2 || cluster >= self.fat.cluster_top() {
+                self.report.bad_chains += 1;
+                break false;
+            }
+
+            if self.is_used(cluster) {
+                self.report.cross_linked_chains += 1;
+                break false;
+            }
+
+            self.set_used(cluster, true);
+            chain.push(cluster);
+
+            cluster = match self.fat.get_next_cluster(cluster)? {
+                ClusterState::End => break true,
+                ClusterState::Some(next_cluster) => next_cluster,
+                ClusterState::Free => {
+                    self.report.bad_chains += 1;
+                    break false;
+                }
+            };
+        };
+
+        // Broken chains end at the last valid cluster
+        if !complete && self.repair {
+            if let Some(last_cluster) = chain.last() {
+                self.fat
+                    .set_next_cluster(*last_cluster, ClusterState::End)?;
+            }
+        }
+
+        Ok((chain, complete))
+    }
+
+    fn read_root_region(&mut self) -> error::Result<DirectoryData> {
+        let mut data = vec![0; self.fat.root_region_size()];
+        self.fat.read_root_region(&mut data)?;
+
+        Ok(DirectoryData {
+            clusters: Vec::new(),
+            data,
+            modified: false,
+        })
+    }
+
+    fn read_directory(&mut self, clusters: Vec<Cluster>) -> error::Result<DirectoryData> {
+        let bytes_per_cluster = self.fat.bytes_per_cluster();
+        let mut data = vec![0; clusters.len() * bytes_per_cluster];
+        for (i, cluster) in clusters.iter().enumerate() {
+            self.fat.read_cluster(
+                *cluster,
+                &mut data[i * bytes_per_cluster..(i + 1) * bytes_per_cluster],
+            )?;
+        }
+
+        Ok(DirectoryData {
+            clusters,
+            data,
+            modified: false,
+        })
+    }
+
+    fn write_directory(&mut self, directory: &DirectoryData) -> error::Result<()> {
+        if !directory.modified {
+            return Ok(());
+        }
+
+        if directory.clusters.len() == 0 {
+            return self.fat.write_root_region(&directory.data);
+        }
+
+        let bytes_per_cluster = self.fat.bytes_per_cluster();
+        for (i, cluster) in directory.clusters.iter().enumerate() {
+            self.fat.write_cluster(
+                *cluster,
+                &directory.data[i * bytes_per_cluster..(i + 1) * bytes_per_cluster],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // Checks each entry, returning the cluster chains of the subdirectories
+    fn check_directory(
+        &mut self,
+        directory: &mut DirectoryData,
+    ) -> error::Result<Vec<Vec<Cluster>>> {
+        let mut subdirectories = Vec::new();
+
+        // Long name entries waiting for their short entry
+        let mut long_entries: Vec<usize> = Vec::new();
+        let mut next_order = 0;
+        let mut checksum = 0;
+
+        for offset in (0..directory.data.len()).step_by(ENTRY_SIZE) {
+            let mut entry = [0; ENTRY_SIZE];
+            entry.copy_from_slice(&directory.data[offset..offset + ENTRY_SIZE]);
+            if entry[0] == ENTRY_END {
+                break;
+            }
+
+            if entry[0] == ENTRY_FREE {
+                self.invalid_long_name(directory, &mut long_entries);
+                continue;
+            }
+
+            if entry[11] & 0x3F == ATTRIBUTE_LONG_FILE_NAME {
+                let order = entry[0];
+                if order & LAST_LONG_ENTRY != 0 {
+                    // A new name starts, so any unfinished one is invalid
+                    self.invalid_long_name(directory, &mut long_entries);
+                    next_order = order & !LAST_LONG_ENTRY;
+                    checksum = entry[13];
+                } else if long_entries.len() == 0 || entry[13] != checksum {
+                    long_entries.push(offset);
+                    self.invalid_long_name(directory, &mut long_entries);
+                    continue;
+                }
+
+                if order & !LAST_LONG_ENTRY != next_order || next_order == 0 {
+                    long_entries.push(offset);
+                    self.invalid_long_name(directory, &mut long_entries);
+                    continue;
+                }
+
+                long_entries.push(offset);
+                next_order -= 1;
+                continue;
+            }
+
+            // The long name must be complete and match the short name
+            if long_entries.len() > 0 {
+                if next_order != 0 || short_name_checksum(&entry[0..11]) != checksum {
+                    self.invalid_long_name(directory, &mut long_entries);
+                }
+                long_entries.clear();
+            }
+
+            if entry[11] & ATTRIBUTE_VOLUME_ID != 0 || entry[0] == b'.' {
+                continue;
+            }
+
+            if let Some(chain) = self.check_entry(directory, offset)? {
+                subdirectories.push(chain);
+            }
+        }
+
+        self.invalid_long_name(directory, &mut long_entries);
+        Ok(subdirectories)
+    }
+
+    fn invalid_long_name(&mut self, directory: &mut DirectoryData, long_entries: &mut Vec<usize>) {
+        if long_entries.len() == 0 {
+            return;
+        }
+
+        self.report.invalid_long_names += 1;
+        if self.repair {
+            for offset in long_entries.iter() {
+                directory.data[*offset] = ENTRY_FREE;
+            }
+            directory.modified = true;
+        }
+
+        long_entries.clear();
+    }
+
+    // Returns the cluster chain if the entry is a directory
+    fn check_entry(
+        &mut self,
+        directory: &mut DirectoryData,
+        offset: usize,
+    ) -> error::Result<Option<Vec<Cluster>>> {
+        let entry = &directory.data[offset..offset + ENTRY_SIZE];
+        let is_directory = entry[11] & ATTRIBUTE_DIRECTORY != 0;
+        let first_cluster = (read_u16(entry, 26) as u32) | ((read_u16(entry, 20) as u32) << 16);
+        let size = read_u32(entry, 28) as usize;
+
+        let mut chain = if first_cluster == 0 {
+            Vec::new()
+        } else {
+            self.walk_chain(first_cluster)?.0
+        };
+
+        if is_directory {
+            // Directories always have at least one cluster
+            if chain.len() == 0 {
+                if first_cluster == 0 {
+                    self.report.bad_chains += 1;
+                }
+
+                if self.repair {
+                    directory.data[offset] = ENTRY_FREE;
+                    directory.modified = true;
+                }
+                return Ok(None);
+            }
+
+            return Ok(Some(chain));
+        }
+
+        // Files have exactly enough clusters to hold their size
+        let bytes_per_cluster = self.fat.bytes_per_cluster();
+        let expected_clusters = (size + bytes_per_cluster - 1) / bytes_per_cluster;
+        if chain.len() == expected_clusters {
+            if chain.len() == 0 && first_cluster != 0 && self.repair {
+                write_first_cluster(&mut directory.data[offset..offset + ENTRY_SIZE], 0);
+                directory.modified = true;
+            }
+            return Ok(None);
+        }
+
+        self.report.size_mismatches += 1;
+        if !self.repair {
+            return Ok(None);
+        }
+
+        if chain.len() > expected_clusters {
+            // Free the clusters past the end of the file
+            for cluster in chain.drain(expected_clusters..) {
+                self.fat.set_next_cluster(cluster, ClusterState::Free)?;
+                self.set_used(cluster, false);
+            }
+
+            match chain.last() {
+                Some(last_cluster) => self
+                    .fat
+                    .set_next_cluster(*last_cluster, ClusterState::End)?,
+                None => write_first_cluster(&mut directory.data[offset..offset + ENTRY_SIZE], 0),
+            }
+        } else {
+            // Shrink the file to the data it actually has
+            let new_size = (chain.len() * bytes_per_cluster) as u32;
+            directory.data[offset + 28..offset + 32].copy_from_slice(&new_size.to_le_bytes());
+            if chain.len() == 0 {
+                write_first_cluster(&mut directory.data[offset..offset + ENTRY_SIZE], 0);
+            }
+        }
+
+        directory.modified = true;
+        Ok(None)
+    }
+
+    // Allocated clusters which no chain reached
+    fn check_lost_clusters(&mut self) -> error::Result<()> {
+        for cluster in 2..self.fat.cluster_top() {
+            if self.is_used(cluster) {
+                continue;
+            }
+
+            match self.fat.get_next_cluster(cluster)? {
+                ClusterState::Free => {}
+                // Bad cluster markers are left alone
+                ClusterState::Some(next_cluster) if next_cluster >= self.fat.cluster_top() => {}
+                _ => {
+                    self.report.lost_clusters += 1;
+                    if self.repair {
+                        self.fat.set_next_cluster(cluster, ClusterState::Free)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn short_name_checksum(short_name: &[u8]) -> u8 {
+    let mut sum: u8 = 0;
+    for c in short_name {
+        sum = if sum & 1 == 0 { 0u8 } else { 0x80u8 }
+            .wrapping_add(sum.wrapping_shr(1))
+            .wrapping_add(*c);
+    }
+    sum
+}
+
+fn write_first_cluster(entry: &mut [u8], cluster: Cluster) {
+    entry[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+    entry[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+}
+
+fn read_u16(buffer: &[u8], offset: usize) -> u16 {
+    (buffer[offset] as u16) | ((buffer[offset + 1] as u16) << 8)
+}
+
+fn read_u32(buffer: &[u8], offset: usize) -> u32 {
+    (read_u16(buffer, offset) as u32) | ((read_u16(buffer, offset + 2) as u32) << 16)
+}
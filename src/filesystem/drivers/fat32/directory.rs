@@ -1,16 +1,18 @@
 use self::entry::DirectoryIterator;
 
-use super::fat::FATBox;
+use super::fat::{Cluster, FATBox};
 use crate::{
     error,
     filesystem::{self, File, Metadata},
 };
 use alloc::{borrow::ToOwned, boxed::Box, string::String, vec::Vec};
+use core::any::Any;
 
 mod entry;
 
 pub struct Directory {
     first_cluster: u32,
+    root: bool,
     fat: FATBox,
 }
 
@@ -18,10 +20,28 @@ impl Directory {
     pub fn new(first_cluster: u32, fat: FATBox) -> Self {
         Directory {
             first_cluster: first_cluster,
+            root: false,
             fat: fat,
         }
     }
 
+    pub fn new_root(first_cluster: u32, fat: FATBox) -> Self {
+        Directory {
+            first_cluster: first_cluster,
+            root: true,
+            fat: fat,
+        }
+    }
+
+    // ".." entries refer to the root directory as cluster 0
+    fn parent_cluster(&self) -> Cluster {
+        if self.root {
+            0
+        } else {
+            self.first_cluster
+        }
+    }
+
     fn create_iterator(&self) -> error::Result<DirectoryIterator> {
         DirectoryIterator::new(self.first_cluster, self.fat.clone())
     }
@@ -49,6 +69,80 @@ impl Directory {
 
         Err(error::Status::NoEntry)
     }
+
+    fn relink(
+        &self,
+        name: &str,
+        directory: Option<bool>,
+        new_directory: &Directory,
+        new_name: &str,
+    ) -> error::Result<()> {
+        // Locate the entry
+        let mut iter = self.create_iterator()?;
+        let mut entry = loop {
+            match iter.next()? {
+                Some(entry) => {
+                    if entry.name() == name {
+                        break entry;
+                    }
+                }
+                None => return Err(error::Status::NoEntry),
+            }
+        };
+
+        match directory {
+            Some(true) if !entry.is_directory() => return Err(error::Status::IsFile),
+            Some(false) if entry.is_directory() => return Err(error::Status::IsDirectory),
+            _ => {}
+        }
+
+        let is_directory = entry.is_directory();
+        let first_cluster = entry.first_cluster();
+
+        // Create the new entry before removing the old one
+        entry.set_name(new_name.to_owned());
+        let mut new_iter = new_directory.create_iterator()?;
+        new_iter.create(entry)?;
+        new_iter.flush_buffer()?;
+
+        // Remove the old entry
+        let mut iter = self.create_iterator()?;
+        while let Some(entry) = iter.next()? {
+            if entry.name() == name {
+                iter.remove()?;
+                iter.flush_buffer()?;
+                break;
+            }
+        }
+
+        // Point ".." at the new parent
+        if is_directory && new_directory.first_cluster != self.first_cluster {
+            new_directory.set_parent_cluster(first_cluster)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_parent_cluster(&self, directory_cluster: Cluster) -> error::Result<()> {
+        let fat = self.fat.lock();
+        let mut buffer = Vec::with_capacity(fat.bytes_per_cluster());
+        buffer.resize(fat.bytes_per_cluster(), 0);
+        fat.read_cluster(directory_cluster, buffer.as_mut_slice())?;
+
+        let entry_size = core::mem::size_of::<entry::DiskDirectoryEntry>();
+        let mut dot_dot_entry =
+            entry::DiskDirectoryEntry::from_slice(&buffer[entry_size..entry_size * 2]);
+        if dot_dot_entry.filename[0] != b'.' || dot_dot_entry.filename[1] != b'.' {
+            return Err(error::Status::CorruptFilesystem);
+        }
+
+        let parent_cluster = self.parent_cluster();
+        dot_dot_entry.first_cluster_high = (parent_cluster.wrapping_shr(16) & 0xFFFF) as u16;
+        dot_dot_entry.first_cluster_low = (parent_cluster & 0xFFFF) as u16;
+        buffer[entry_size..entry_size * 2].copy_from_slice(&dot_dot_entry.to_slice());
+
+        fat.write_cluster(directory_cluster, buffer.as_slice())
+    }
 }
 
 impl filesystem::Directory for Directory {
@@ -56,10 +150,7 @@ impl filesystem::Directory for Directory {
         let mut children = Vec::new();
         let mut iter = self.create_iterator()?;
         while let Some(entry) = iter.next()? {
-            children.push((
-                entry.name().to_owned(),
-                Metadata::new(entry.file_size(), entry.is_directory()),
-            ))
+            children.push((entry.name().to_owned(), entry.metadata()))
         }
 
         Ok(children)
@@ -140,6 +231,7 @@ impl filesystem::Directory for Directory {
             file_size: 0,
         };
 
+        let parent_cluster = self.parent_cluster();
         let dot_dot_entry = entry::DiskDirectoryEntry {
             filename: [
                 b'.', b'.', b' ', b' ', b' ', b' ', b' ', b' ', b' ', b' ', b' ',
@@ -150,10 +242,10 @@ impl filesystem::Directory for Directory {
             creation_time: 0,
             creation_date: 0,
             last_accessed_date: 0,
-            first_cluster_high: (self.first_cluster.wrapping_shr(16) & 0xFFFF) as u16,
+            first_cluster_high: (parent_cluster.wrapping_shr(16) & 0xFFFF) as u16,
             last_modification_time: 0,
             last_modification_date: 0,
-            first_cluster_low: (self.first_cluster & 0xFFFF) as u16,
+            first_cluster_low: (parent_cluster & 0xFFFF) as u16,
             file_size: 0,
         };
 
@@ -183,12 +275,24 @@ impl filesystem::Directory for Directory {
         iter.flush_buffer()
     }
 
-    fn rename_file(&self, _: &str, _: &str) -> error::Result<()> {
-        Err(error::Status::NotImplemented)
+    fn rename_file(&self, old_name: &str, new_name: &str) -> error::Result<()> {
+        self.relink(old_name, Some(false), self, new_name)
+    }
+
+    fn rename_directory(&self, old_name: &str, new_name: &str) -> error::Result<()> {
+        self.relink(old_name, Some(true), self, new_name)
     }
 
-    fn rename_directory(&self, _: &str, _: &str) -> error::Result<()> {
-        Err(error::Status::NotImplemented)
+    fn move_entry(
+        &self,
+        name: &str,
+        new_directory: &dyn filesystem::Directory,
+        new_name: &str,
+    ) -> error::Result<()> {
+        match new_directory.as_any().downcast_ref::<Directory>() {
+            Some(new_directory) => self.relink(name, None, new_directory, new_name),
+            None => Err(error::Status::NotSupported),
+        }
     }
 
     fn remove(&self, name: &str) -> error::Result<()> {
@@ -203,7 +307,7 @@ impl filesystem::Directory for Directory {
                 return if entry.is_directory() {
                     Err(error::Status::IsDirectory)
                 } else {
-                    entry.set_file_size(new_metadata.size());
+                    entry.set_metadata(new_metadata);
                     iter.write_metadata(entry)?;
                     iter.flush_buffer()?;
                     Ok(())
@@ -213,4 +317,16 @@ impl filesystem::Directory for Directory {
 
         Err(error::Status::NoEntry)
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn flush(&self) -> error::Result<()> {
+        self.fat.lock().flush()
+    }
+
+    fn get_space(&self) -> error::Result<(usize, usize)> {
+        self.fat.lock().space()
+    }
 }
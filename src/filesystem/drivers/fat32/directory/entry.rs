@@ -1,6 +1,10 @@
 #![allow(dead_code)]
 use super::super::fat::{Cluster, FATBox};
-use crate::{error, filesystem::drivers::fat32::fat::ClusterState};
+use crate::{
+    error,
+    filesystem::{self, drivers::fat32::fat::ClusterState, Metadata},
+    time,
+};
 use alloc::{borrow::ToOwned, string::String, vec::Vec};
 
 #[repr(C)]
@@ -37,6 +41,7 @@ struct Buffer {
     buffer: Vec<u8>,
     current_cluster_index: usize,
     cluster_chain: Vec<u32>,
+    root_region: bool,
     modified: bool,
     fat: FATBox,
 }
@@ -52,6 +57,10 @@ pub struct DirectoryEntry {
     directory: bool,
     first_cluster: Cluster,
     file_size: usize,
+    attributes: u8,
+    creation_time: isize,
+    access_time: isize,
+    modification_time: isize,
 }
 
 const ATTRIBUTE_READ_ONLY: u8 = 0x01;
@@ -63,6 +72,13 @@ const ATTRIBUTE_ARCHIVE: u8 = 0x20;
 const ATTRIBUTE_LONG_FILE_NAME: u8 =
     ATTRIBUTE_READ_ONLY | ATTRIBUTE_HIDDEN | ATTRIBUTE_SYSTEM | ATTRIBUTE_VOLUME_ID;
 
+// FAT attribute bits share their values with the filesystem attributes
+const ATTRIBUTE_METADATA_MASK: u8 = (filesystem::ATTRIBUTE_READ_ONLY
+    | filesystem::ATTRIBUTE_HIDDEN
+    | filesystem::ATTRIBUTE_SYSTEM
+    | filesystem::ATTRIBUTE_ARCHIVE
+    | filesystem::ATTRIBUTE_FIFO) as u8;
+
 impl DiskDirectoryEntry {
     pub fn from_slice(slice: &[u8]) -> DiskDirectoryEntry {
         DiskDirectoryEntry {
@@ -139,6 +155,24 @@ impl Buffer {
     pub fn new(first_cluster: u32, fat_lock: FATBox) -> error::Result<Self> {
         let mut fat = fat_lock.lock();
 
+        // The fixed root directory region is read as a single unit
+        if fat.is_root_region(first_cluster) {
+            let mut buffer = Vec::with_capacity(fat.root_region_size());
+            buffer.resize(fat.root_region_size(), 0);
+            fat.read_root_region(buffer.as_mut_slice())?;
+
+            drop(fat);
+
+            return Ok(Buffer {
+                buffer,
+                current_cluster_index: 0,
+                cluster_chain: Vec::new(),
+                root_region: true,
+                modified: false,
+                fat: fat_lock,
+            });
+        }
+
         // Create the buffer
         let mut buffer = Vec::with_capacity(fat.bytes_per_cluster());
         unsafe { buffer.set_len(buffer.capacity()) };
@@ -155,6 +189,7 @@ impl Buffer {
             buffer,
             current_cluster_index: 0,
             cluster_chain,
+            root_region: false,
             modified: false,
             fat: fat_lock,
         })
@@ -165,6 +200,11 @@ impl Buffer {
             return Ok(());
         }
 
+        // The fixed root directory region cannot grow
+        if self.root_region {
+            return Err(error::Status::NoSpace);
+        }
+
         if new_cluster_index == self.cluster_chain.len() {
             // Allocate new cluster
             let new_cluster = self.fat.lock().allocate_cluster()?;
@@ -212,8 +252,15 @@ impl Buffer {
         Ok(())
     }
 
+    pub fn is_past_end(&self, cluster_index: usize) -> bool {
+        self.root_region && cluster_index > 0
+    }
+
     pub fn flush_buffer(&mut self) -> error::Result<()> {
-        if self.modified {
+        if self.modified && self.root_region {
+            self.fat.lock().write_root_region(self.buffer.as_slice())?;
+            self.modified = false;
+        } else if self.modified {
             self.fat.lock().write_cluster(
                 self.cluster_chain[self.current_cluster_index],
                 self.buffer.as_slice(),
@@ -227,10 +274,19 @@ impl Buffer {
 
 impl DirectoryIterator {
     pub fn new(first_cluster: u32, fat: FATBox) -> error::Result<Self> {
+        let cluster_top = {
+            let fat = fat.lock();
+            if fat.is_root_region(first_cluster) {
+                fat.root_region_size()
+            } else {
+                fat.bytes_per_cluster()
+            }
+        };
+
         Ok(DirectoryIterator {
-            buffer: Buffer::new(first_cluster, fat.clone())?,
+            buffer: Buffer::new(first_cluster, fat)?,
             current_index: None,
-            cluster_top: fat.lock().bytes_per_cluster(),
+            cluster_top,
         })
     }
 
@@ -267,6 +323,9 @@ impl DirectoryIterator {
             self.increament_index();
 
             let (cluster_index, offset) = self.get_cluster_index_and_offset();
+            if self.buffer.is_past_end(cluster_index) {
+                return Ok(None);
+            }
 
             self.buffer.set_current_cluster_index(cluster_index)?;
             self.buffer.read(offset, &mut entry_buffer)?;
@@ -369,12 +428,20 @@ impl DirectoryIterator {
                 continue;
             }
 
-            return Ok(Some(DirectoryEntry::new(
+            let mut directory_entry = DirectoryEntry::new(
                 filename,
                 entry.attributes & ATTRIBUTE_DIRECTORY != 0,
                 (entry.first_cluster_low as u32) | ((entry.first_cluster_high as u32) << 16),
                 entry.file_size as usize,
-            )));
+            );
+            directory_entry.attributes = entry.attributes & ATTRIBUTE_METADATA_MASK;
+            directory_entry.creation_time = fat_to_epoch(entry.creation_date, entry.creation_time)
+                + (entry.creation_tenths / 100) as isize;
+            directory_entry.access_time = fat_to_epoch(entry.last_accessed_date, 0);
+            directory_entry.modification_time =
+                fat_to_epoch(entry.last_modification_date, entry.last_modification_time);
+
+            return Ok(Some(directory_entry));
         }
     }
 
@@ -388,6 +455,8 @@ impl DirectoryIterator {
         entry.file_size = new_entry.file_size as u32;
         entry.first_cluster_low = (new_entry.first_cluster & 0xFFFF) as u16;
         entry.first_cluster_high = (new_entry.first_cluster.wrapping_shr(16) & 0xFFFF) as u16;
+        entry.attributes = (entry.attributes & !ATTRIBUTE_METADATA_MASK) | new_entry.attributes;
+        new_entry.write_times(&mut entry);
 
         let buffer = entry.to_slice();
         self.buffer.write(offset, &buffer)?;
@@ -402,8 +471,10 @@ impl DirectoryIterator {
         // Check next entry
         self.increament_index();
         let (next_cluster_index, next_offset) = self.get_cluster_index_and_offset();
-        self.buffer.set_current_cluster_index(next_cluster_index)?;
-        self.buffer.read(next_offset, &mut read_buffer)?;
+        if !self.buffer.is_past_end(next_cluster_index) {
+            self.buffer.set_current_cluster_index(next_cluster_index)?;
+            self.buffer.read(next_offset, &mut read_buffer)?;
+        }
 
         // Initialize the write buffer
         if read_buffer[0] != 0 {
@@ -501,14 +572,30 @@ impl DirectoryIterator {
 
 impl DirectoryEntry {
     pub fn new(name: String, directory: bool, first_cluster: u32, file_size: usize) -> Self {
+        let now = time::get_epoch_time();
         DirectoryEntry {
             name,
             directory,
             first_cluster,
             file_size,
+            attributes: 0,
+            creation_time: now,
+            access_time: now,
+            modification_time: now,
         }
     }
 
+    fn write_times(&self, entry: &mut DiskDirectoryEntry) {
+        let (creation_date, creation_time) = epoch_to_fat(self.creation_time);
+        entry.creation_date = creation_date;
+        entry.creation_time = creation_time;
+        entry.creation_tenths = ((self.creation_time & 1) * 100) as u8;
+        entry.last_accessed_date = epoch_to_fat(self.access_time).0;
+        let (modification_date, modification_time) = epoch_to_fat(self.modification_time);
+        entry.last_modification_date = modification_date;
+        entry.last_modification_time = modification_time;
+    }
+
     pub fn to_disk_entries(self) -> error::Result<(DiskDirectoryEntry, Vec<LongDirectoryEntry>)> {
         // Check to see if long file name is nescessary
         let is_long_filename = if self.directory {
@@ -611,12 +698,12 @@ impl DirectoryEntry {
         };
 
         // Generate disk directory entry
-        let disk_entry = DiskDirectoryEntry {
+        let mut disk_entry = DiskDirectoryEntry {
             filename: short_name,
             attributes: if self.directory {
-                ATTRIBUTE_DIRECTORY
+                ATTRIBUTE_DIRECTORY | self.attributes
             } else {
-                0
+                self.attributes
             },
             reserved: 0,
             creation_tenths: 0,
@@ -629,6 +716,7 @@ impl DirectoryEntry {
             first_cluster_low: (self.first_cluster & 0xFFFF) as u16,
             file_size: self.file_size as u32,
         };
+        self.write_times(&mut disk_entry);
 
         // Generate long directory entries
         let mut long_entries = Vec::new();
@@ -725,4 +813,60 @@ impl DirectoryEntry {
     pub fn first_cluster(&self) -> Cluster {
         self.first_cluster
     }
+
+    pub fn set_name(&mut self, new_name: String) {
+        self.name = new_name;
+    }
+
+    pub fn metadata(&self) -> Metadata {
+        Metadata::new(
+            self.file_size,
+            self.directory,
+            self.attributes as usize,
+            self.creation_time,
+            self.access_time,
+            self.modification_time,
+        )
+    }
+
+    pub fn set_metadata(&mut self, metadata: Metadata) {
+        self.file_size = metadata.size();
+        self.attributes = metadata.attributes() as u8 & ATTRIBUTE_METADATA_MASK;
+        self.creation_time = metadata.creation_time();
+        self.access_time = metadata.access_time();
+        self.modification_time = metadata.modification_time();
+    }
+}
+
+fn fat_to_epoch(date: u16, fat_time: u16) -> isize {
+    if date == 0 {
+        return 0;
+    }
+
+    let year = 1980 + (date >> 9) as isize;
+    let month = ((date >> 5) & 0x0F) as isize;
+    let day = (date & 0x1F) as isize;
+    if month < 1 || month > 12 || day < 1 {
+        return 0;
+    }
+
+    let hour = (fat_time >> 11) as isize;
+    let minute = ((fat_time >> 5) & 0x3F) as isize;
+    let second = ((fat_time & 0x1F) * 2) as isize;
+
+    time::date_to_epoch(year, month, day, hour, minute, second)
+}
+
+// Returns (date, time)
+fn epoch_to_fat(epoch: isize) -> (u16, u16) {
+    let (year, month, day, hour, minute, second) = time::epoch_to_date(epoch);
+    if year < 1980 {
+        return (0, 0);
+    }
+
+    let year = core::cmp::min(year - 1980, 127);
+    (
+        ((year << 9) | (month << 5) | day) as u16,
+        ((hour << 11) | (minute << 5) | (second / 2)) as u16,
+    )
 }
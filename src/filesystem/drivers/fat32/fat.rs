@@ -1,9 +1,16 @@
-use crate::{device::DeviceReference, error, locks::Mutex};
+use crate::{device::DeviceReference, error, filesystem::cache, locks::Mutex};
 use alloc::{sync::Arc, vec::Vec};
 
 pub type FATBox = Arc<Mutex<FAT>>;
 pub type Cluster = u32;
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FATType {
+    FAT12,
+    FAT16,
+    FAT32,
+}
+
 pub enum ClusterState {
     Free,
     Some(Cluster),
@@ -12,28 +19,39 @@ pub enum ClusterState {
 
 pub struct FAT {
     drive: DeviceReference,
+    fat_type: FATType,
     sectors_per_cluster: u32,
     bytes_per_cluster: usize,
     num_fats: usize,
     fat_size: usize,
     first_fat_sector: usize,
     first_data_sector: u32,
+    root_directory_sector: usize,
+    root_directory_sectors: usize,
     bytes_per_sector: usize,
+    cluster_top: u32,
     buffer: Vec<u8>,
     buffer_modified: bool,
     buffer_sector_offset: usize,
     next_free_cluster: u32,
+    free_clusters: Option<u32>,
+    fs_info_sector: Option<usize>,
 }
 
 impl FAT {
     pub fn new(
         drive: DeviceReference,
+        fat_type: FATType,
         sectors_per_cluster: u8,
         reserved_sector_count: u16,
         num_fats: u8,
         fat_size: u32,
         bytes_per_sector: u16,
+        root_directory_sectors: u32,
+        cluster_count: u32,
     ) -> Self {
+        let root_directory_sector = reserved_sector_count as u32 + ((num_fats as u32) * fat_size);
+
         let mut buffer = Vec::with_capacity(bytes_per_sector as usize);
         for _ in 0..buffer.capacity() {
             buffer.push(0);
@@ -41,26 +59,103 @@ impl FAT {
 
         FAT {
             drive,
+            fat_type,
             sectors_per_cluster: sectors_per_cluster as u32,
             bytes_per_cluster: (sectors_per_cluster as usize) * (bytes_per_sector as usize),
             first_fat_sector: reserved_sector_count as usize,
             num_fats: num_fats as usize,
             fat_size: fat_size as usize,
             bytes_per_sector: bytes_per_sector as usize,
-            first_data_sector: reserved_sector_count as u32 + ((num_fats as u32) * fat_size),
+            first_data_sector: root_directory_sector + root_directory_sectors,
+            root_directory_sector: root_directory_sector as usize,
+            root_directory_sectors: root_directory_sectors as usize,
+            cluster_top: cluster_count + 2,
             buffer,
             buffer_modified: false,
             buffer_sector_offset: 0xFFFFFFFF,
             next_free_cluster: 0xFFFFFFFF,
+            free_clusters: None,
+            fs_info_sector: None,
+        }
+    }
+
+    // FAT32 volumes keep a free cluster count in the FSInfo sector, 0xFFFFFFFF if unknown
+    pub fn set_fs_info(&mut self, sector: usize, free_clusters: u32) {
+        self.fs_info_sector = Some(sector);
+        if free_clusters <= self.cluster_top - 2 {
+            self.free_clusters = Some(free_clusters);
         }
     }
 
+    // Returns the total and free bytes, the free clusters are counted if unknown
+    pub fn space(&mut self) -> error::Result<(usize, usize)> {
+        let free_clusters = match self.free_clusters {
+            Some(free_clusters) => free_clusters,
+            None => {
+                let mut free_clusters = 0;
+                for cluster in 2..self.cluster_top {
+                    if let ClusterState::Free = self.get_next_cluster(cluster)? {
+                        free_clusters += 1;
+                    }
+                }
+
+                self.free_clusters = Some(free_clusters);
+                self.write_fs_info()?;
+                free_clusters
+            }
+        };
+
+        Ok((
+            (self.cluster_top - 2) as usize * self.bytes_per_cluster,
+            free_clusters as usize * self.bytes_per_cluster,
+        ))
+    }
+
+    // Used after the FAT has been changed directly
+    pub fn recount_free_clusters(&mut self) -> error::Result<()> {
+        self.free_clusters = None;
+        self.next_free_cluster = 0xFFFFFFFF;
+        self.space().map(|_| ())
+    }
+
+    fn adjust_free_clusters(&mut self, difference: i32) -> error::Result<()> {
+        let free_clusters = match self.free_clusters {
+            Some(free_clusters) => free_clusters as i64 + difference as i64,
+            None => return Ok(()),
+        };
+
+        // A stale count left by another system is recounted when next needed
+        self.free_clusters = if free_clusters < 0 || free_clusters > (self.cluster_top - 2) as i64 {
+            None
+        } else {
+            Some(free_clusters as u32)
+        };
+
+        self.write_fs_info()
+    }
+
+    fn write_fs_info(&self) -> error::Result<()> {
+        let sector = match self.fs_info_sector {
+            Some(sector) => sector,
+            None => return Ok(()),
+        };
+
+        let mut fs_info = Vec::with_capacity(self.bytes_per_sector);
+        fs_info.resize(self.bytes_per_sector, 0);
+        cache::read(&self.drive, sector, fs_info.as_mut_slice())?;
+
+        let free_clusters = self.free_clusters.unwrap_or(0xFFFFFFFF);
+        fs_info[0x1E8..0x1EC].copy_from_slice(&free_clusters.to_le_bytes());
+        fs_info[0x1EC..0x1F0].copy_from_slice(&self.next_free_cluster.to_le_bytes());
+        cache::write(&self.drive, sector, fs_info.as_slice())
+    }
+
+    // Writes the buffered sector to every FAT through the block cache
     fn flush_buffer(&mut self) -> error::Result<()> {
         if self.buffer_modified {
-            let mut drive = self.drive.lock();
             let mut sector = self.buffer_sector_offset + self.first_fat_sector;
             for _ in 0..self.num_fats {
-                drive.write(sector, self.buffer.as_slice())?;
+                cache::write(&self.drive, sector, self.buffer.as_slice())?;
                 sector += self.fat_size;
             }
             self.buffer_modified = false;
@@ -69,6 +164,11 @@ impl FAT {
         Ok(())
     }
 
+    pub fn flush(&mut self) -> error::Result<()> {
+        self.flush_buffer()?;
+        cache::flush(&self.drive)
+    }
+
     fn set_buffer_sector(&mut self, new_sector_offset: usize) -> error::Result<()> {
         if self.buffer_sector_offset == new_sector_offset {
             return Ok(());
@@ -77,25 +177,60 @@ impl FAT {
         self.flush_buffer()?;
 
         self.buffer_sector_offset = new_sector_offset;
-        self.drive.lock().read(
+        cache::read(
+            &self.drive,
             new_sector_offset + self.first_fat_sector,
             self.buffer.as_mut_slice(),
         )
     }
 
-    fn get_next_cluster(&mut self, cluster: Cluster) -> error::Result<ClusterState> {
-        let sector_offset = (cluster as usize * 4) / self.bytes_per_sector;
-        let offset = (cluster as usize * 4) % self.bytes_per_sector;
+    fn read_byte(&mut self, offset: usize) -> error::Result<u8> {
+        self.set_buffer_sector(offset / self.bytes_per_sector)?;
+        Ok(self.buffer[offset % self.bytes_per_sector])
+    }
+
+    fn write_byte(&mut self, offset: usize, value: u8) -> error::Result<()> {
+        self.set_buffer_sector(offset / self.bytes_per_sector)?;
+        self.buffer[offset % self.bytes_per_sector] = value;
+        self.buffer_modified = true;
+        Ok(())
+    }
 
-        self.set_buffer_sector(sector_offset)?;
+    pub fn get_next_cluster(&mut self, cluster: Cluster) -> error::Result<ClusterState> {
+        let (next_cluster, end) = match self.fat_type {
+            FATType::FAT12 => {
+                // Entries are 12 bits and may cross a sector boundary
+                let offset = cluster as usize + (cluster as usize / 2);
+                let value =
+                    (self.read_byte(offset)? as u32) | ((self.read_byte(offset + 1)? as u32) << 8);
+                let next_cluster = if cluster & 1 == 0 {
+                    value & 0x0FFF
+                } else {
+                    value >> 4
+                };
+
+                (next_cluster, 0x0FF8)
+            }
+            FATType::FAT16 => {
+                let offset = cluster as usize * 2;
+                let next_cluster =
+                    (self.read_byte(offset)? as u32) | ((self.read_byte(offset + 1)? as u32) << 8);
 
-        let next_cluster = ((self.buffer[offset] as u32)
-            | ((self.buffer[offset + 1] as u32) << 8)
-            | ((self.buffer[offset + 2] as u32) << 16)
-            | ((self.buffer[offset + 3] as u32) << 24))
-            & 0x0FFFFFFF;
+                (next_cluster, 0xFFF8)
+            }
+            FATType::FAT32 => {
+                let offset = cluster as usize * 4;
+                let next_cluster = ((self.read_byte(offset)? as u32)
+                    | ((self.read_byte(offset + 1)? as u32) << 8)
+                    | ((self.read_byte(offset + 2)? as u32) << 16)
+                    | ((self.read_byte(offset + 3)? as u32) << 24))
+                    & 0x0FFFFFFF;
+
+                (next_cluster, 0x0FFFFFF8)
+            }
+        };
 
-        Ok(if next_cluster >= 0x0FFFFFF8 {
+        Ok(if next_cluster >= end {
             ClusterState::End
         } else if next_cluster == 0 {
             ClusterState::Free
@@ -112,22 +247,49 @@ impl FAT {
         let next_cluster = match next_cluster {
             ClusterState::Free => 0,
             ClusterState::Some(next_cluster) => next_cluster,
-            ClusterState::End => 0x0FFFFFFF,
+            ClusterState::End => match self.fat_type {
+                FATType::FAT12 => 0x0FFF,
+                FATType::FAT16 => 0xFFFF,
+                FATType::FAT32 => 0x0FFFFFFF,
+            },
         };
 
-        let sector_offset = (cluster as usize * 4) / self.bytes_per_sector;
-        let offset = (cluster as usize * 4) % self.bytes_per_sector;
-
-        self.set_buffer_sector(sector_offset)?;
-
-        self.buffer[offset + 0] = (next_cluster.wrapping_shr(0) & 0xFF) as u8;
-        self.buffer[offset + 1] = (next_cluster.wrapping_shr(8) & 0xFF) as u8;
-        self.buffer[offset + 2] = (next_cluster.wrapping_shr(16) & 0xFF) as u8;
-        self.buffer[offset + 3] = (next_cluster.wrapping_shr(24) & 0xFF) as u8;
-
-        self.buffer_modified = true;
+        match self.fat_type {
+            FATType::FAT12 => {
+                let offset = cluster as usize + (cluster as usize / 2);
+                if cluster & 1 == 0 {
+                    let high = self.read_byte(offset + 1)?;
+                    self.write_byte(offset, (next_cluster & 0xFF) as u8)?;
+                    self.write_byte(
+                        offset + 1,
+                        (high & 0xF0) | (next_cluster.wrapping_shr(8) & 0x0F) as u8,
+                    )?;
+                } else {
+                    let low = self.read_byte(offset)?;
+                    self.write_byte(offset, (low & 0x0F) | ((next_cluster & 0x0F) << 4) as u8)?;
+                    self.write_byte(offset + 1, (next_cluster.wrapping_shr(4) & 0xFF) as u8)?;
+                }
+            }
+            FATType::FAT16 => {
+                let offset = cluster as usize * 2;
+                self.write_byte(offset + 0, (next_cluster.wrapping_shr(0) & 0xFF) as u8)?;
+                self.write_byte(offset + 1, (next_cluster.wrapping_shr(8) & 0xFF) as u8)?;
+            }
+            FATType::FAT32 => {
+                // The high 4 bits are reserved
+                let offset = cluster as usize * 4;
+                let high = self.read_byte(offset + 3)? & 0xF0;
+                self.write_byte(offset + 0, (next_cluster.wrapping_shr(0) & 0xFF) as u8)?;
+                self.write_byte(offset + 1, (next_cluster.wrapping_shr(8) & 0xFF) as u8)?;
+                self.write_byte(offset + 2, (next_cluster.wrapping_shr(16) & 0xFF) as u8)?;
+                self.write_byte(
+                    offset + 3,
+                    high | (next_cluster.wrapping_shr(24) & 0x0F) as u8,
+                )?;
+            }
+        }
 
-        Ok(())
+        self.flush_buffer()
     }
 
     fn find_next_free_cluster(&mut self) -> error::Result<Cluster> {
@@ -135,9 +297,7 @@ impl FAT {
             self.next_free_cluster = 2;
         }
 
-        let top = ((self.fat_size * self.bytes_per_sector) / 4) as u32;
-
-        while self.next_free_cluster < top {
+        while self.next_free_cluster < self.cluster_top {
             match self.get_next_cluster(self.next_free_cluster)? {
                 ClusterState::Free => return Ok(self.next_free_cluster),
                 _ => self.next_free_cluster += 1,
@@ -150,6 +310,7 @@ impl FAT {
     pub fn allocate_cluster(&mut self) -> error::Result<u32> {
         let cluster = self.find_next_free_cluster()?;
         self.set_next_cluster(cluster, ClusterState::End)?;
+        self.adjust_free_clusters(-1)?;
         Ok(cluster)
     }
 
@@ -162,7 +323,7 @@ impl FAT {
         if cluster < self.next_free_cluster {
             self.next_free_cluster = cluster;
         }
-        Ok(())
+        self.adjust_free_clusters(1)
     }
 
     pub fn get_cluster_chain(&mut self, first_cluster: Cluster) -> error::Result<Vec<Cluster>> {
@@ -203,8 +364,6 @@ impl FAT {
             cluster = new_cluster;
         }
 
-        self.flush_buffer()?;
-
         Ok(())
     }
 
@@ -257,21 +416,43 @@ impl FAT {
     }
 
     pub fn read_cluster(&self, cluster: u32, buffer: &mut [u8]) -> error::Result<()> {
-        self.drive
-            .lock()
-            .read(self.cluster_to_sector(cluster), buffer)
+        cache::read(&self.drive, self.cluster_to_sector(cluster), buffer)
     }
 
     pub fn write_cluster(&self, cluster: u32, buffer: &[u8]) -> error::Result<()> {
-        self.drive
-            .lock()
-            .write(self.cluster_to_sector(cluster), buffer)
+        cache::write(&self.drive, self.cluster_to_sector(cluster), buffer)
     }
 
     pub fn bytes_per_cluster(&self) -> usize {
         self.bytes_per_cluster
     }
 
+    pub fn fat_type(&self) -> FATType {
+        self.fat_type
+    }
+
+    // Valid clusters are from 2 up to but not including this
+    pub fn cluster_top(&self) -> Cluster {
+        self.cluster_top
+    }
+
+    // FAT12 and FAT16 keep the root directory in a fixed region referred to as cluster 0
+    pub fn is_root_region(&self, cluster: Cluster) -> bool {
+        cluster == 0 && self.fat_type != FATType::FAT32
+    }
+
+    pub fn root_region_size(&self) -> usize {
+        self.root_directory_sectors * self.bytes_per_sector
+    }
+
+    pub fn read_root_region(&self, buffer: &mut [u8]) -> error::Result<()> {
+        cache::read(&self.drive, self.root_directory_sector, buffer)
+    }
+
+    pub fn write_root_region(&self, buffer: &[u8]) -> error::Result<()> {
+        cache::write(&self.drive, self.root_directory_sector, buffer)
+    }
+
     fn cluster_to_sector(&self, cluster: u32) -> usize {
         (((cluster - 2) * self.sectors_per_cluster) + self.first_data_sector) as usize
     }
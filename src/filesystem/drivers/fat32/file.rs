@@ -206,4 +206,8 @@ impl crate::filesystem::File for File {
     fn get_length(&self) -> usize {
         self.file_size
     }
+
+    fn flush(&mut self) -> error::Result<()> {
+        self.fat.lock().flush()
+    }
 }
@@ -0,0 +1,175 @@
+use super::SECTOR_SIZE;
+use crate::{device::DeviceReference, error, filesystem::cache, time};
+use alloc::{vec, vec::Vec};
+
+const RESERVED_SECTOR_COUNT: u32 = 32;
+const NUM_FATS: u32 = 2;
+const FS_INFO_SECTOR: u32 = 1;
+const BACKUP_BOOT_SECTOR: u32 = 6;
+const ROOT_DIRECTORY_CLUSTER: u32 = 2;
+const MEDIA_DESCRIPTOR: u8 = 0xF8;
+
+const MINIMUM_CLUSTERS: u32 = 65525;
+const MAXIMUM_CLUSTERS: u32 = 0x0FFFFFF5;
+
+// Sectors zeroed per write
+const ZERO_CHUNK_SECTORS: usize = 64;
+
+const INVALID_LABEL_CHARACTERS: &[u8] = b"\"*+,./:;<=>?[\\]|";
+
+// Lays out a volume using the whole drive, data on it is not preserved
+pub fn format(drive: &DeviceReference, volume_name: &str) -> error::Result<()> {
+    let volume_label = create_volume_label(volume_name)?;
+
+    let size = drive.lock().ioctrl(0, 0)?;
+    let total_sectors = core::cmp::min(size / SECTOR_SIZE, u32::MAX as usize) as u32;
+
+    // Cluster sizes follow the table from the FAT specification
+    let sectors_per_cluster: u32 = match total_sectors {
+        0..=532480 => 1,
+        532481..=16777216 => 8,
+        16777217..=33554432 => 16,
+        33554433..=67108864 => 32,
+        _ => 64,
+    };
+
+    if total_sectors <= RESERVED_SECTOR_COUNT {
+        return Err(error::Status::NoSpace);
+    }
+
+    let fat_entries_per_sector = ((256 * sectors_per_cluster) + NUM_FATS) / 2;
+    let fat_size = (total_sectors - RESERVED_SECTOR_COUNT + fat_entries_per_sector - 1)
+        / fat_entries_per_sector;
+    let first_data_sector = RESERVED_SECTOR_COUNT + NUM_FATS * fat_size;
+    if total_sectors <= first_data_sector {
+        return Err(error::Status::NoSpace);
+    }
+
+    // Smaller volumes would be detected as FAT12 or FAT16
+    let cluster_count = (total_sectors - first_data_sector) / sectors_per_cluster;
+    if cluster_count < MINIMUM_CLUSTERS {
+        return Err(error::Status::NoSpace);
+    }
+
+    if cluster_count >= MAXIMUM_CLUSTERS {
+        return Err(error::Status::TooBig);
+    }
+
+    // Clear the reserved region, the FATs and the root directory
+    zero_sectors(drive, 0, first_data_sector as usize)?;
+    zero_sectors(
+        drive,
+        first_data_sector as usize,
+        sectors_per_cluster as usize,
+    )?;
+
+    // Reserved entries hold the media descriptor, then the root directory is a single cluster
+    let mut fat = vec![0u8; SECTOR_SIZE];
+    fat[0..4].copy_from_slice(&(0x0FFFFF00 | MEDIA_DESCRIPTOR as u32).to_le_bytes());
+    fat[4..8].copy_from_slice(&0x0FFFFFFFu32.to_le_bytes());
+    fat[8..12].copy_from_slice(&0x0FFFFFFFu32.to_le_bytes());
+    for i in 0..NUM_FATS {
+        cache::write(drive, (RESERVED_SECTOR_COUNT + i * fat_size) as usize, &fat)?;
+    }
+
+    // The label is also kept as an entry in the root directory
+    if volume_label != *b"NO NAME    " {
+        let mut root_directory = vec![0u8; SECTOR_SIZE];
+        root_directory[0..11].copy_from_slice(&volume_label);
+        root_directory[11] = 0x08;
+        cache::write(drive, first_data_sector as usize, &root_directory)?;
+    }
+
+    let fs_info = create_fs_info(cluster_count - 1);
+    cache::write(drive, FS_INFO_SECTOR as usize, &fs_info)?;
+    cache::write(
+        drive,
+        (BACKUP_BOOT_SECTOR + FS_INFO_SECTOR) as usize,
+        &fs_info,
+    )?;
+
+    let bpb = create_bpb(total_sectors, sectors_per_cluster, fat_size, &volume_label);
+    cache::write(drive, BACKUP_BOOT_SECTOR as usize, &bpb)?;
+    cache::write(drive, 0, &bpb)?;
+
+    cache::flush(drive)
+}
+
+fn create_volume_label(volume_name: &str) -> error::Result<[u8; 11]> {
+    if volume_name.len() > 11 {
+        return Err(error::Status::NameTooLong);
+    }
+
+    if volume_name.len() == 0 {
+        return Ok(*b"NO NAME    ");
+    }
+
+    let mut volume_label = [b' '; 11];
+    for (i, c) in volume_name.bytes().enumerate() {
+        if !(c.is_ascii_graphic() || c == b' ') || INVALID_LABEL_CHARACTERS.contains(&c) {
+            return Err(error::Status::InvalidArgument);
+        }
+
+        volume_label[i] = c.to_ascii_uppercase();
+    }
+
+    Ok(volume_label)
+}
+
+fn create_bpb(
+    total_sectors: u32,
+    sectors_per_cluster: u32,
+    fat_size: u32,
+    volume_label: &[u8; 11],
+) -> Vec<u8> {
+    let mut bpb = vec![0u8; SECTOR_SIZE];
+
+    bpb[0x00..0x03].copy_from_slice(&[0xEB, 0x58, 0x90]);
+    bpb[0x03..0x0B].copy_from_slice(b"MSWIN4.1");
+    bpb[0x0B..0x0D].copy_from_slice(&(SECTOR_SIZE as u16).to_le_bytes());
+    bpb[0x0D] = sectors_per_cluster as u8;
+    bpb[0x0E..0x10].copy_from_slice(&(RESERVED_SECTOR_COUNT as u16).to_le_bytes());
+    bpb[0x10] = NUM_FATS as u8;
+    bpb[0x15] = MEDIA_DESCRIPTOR;
+    bpb[0x18..0x1A].copy_from_slice(&32u16.to_le_bytes()); // Sectors per track
+    bpb[0x1A..0x1C].copy_from_slice(&64u16.to_le_bytes()); // Heads
+    bpb[0x20..0x24].copy_from_slice(&total_sectors.to_le_bytes());
+    bpb[0x24..0x28].copy_from_slice(&fat_size.to_le_bytes());
+    bpb[0x2C..0x30].copy_from_slice(&ROOT_DIRECTORY_CLUSTER.to_le_bytes());
+    bpb[0x30..0x32].copy_from_slice(&(FS_INFO_SECTOR as u16).to_le_bytes());
+    bpb[0x32..0x34].copy_from_slice(&(BACKUP_BOOT_SECTOR as u16).to_le_bytes());
+    bpb[0x40] = 0x80; // Drive number
+    bpb[0x42] = 0x29;
+    bpb[0x43..0x47].copy_from_slice(&(time::get_epoch_time() as u32).to_le_bytes());
+    bpb[0x47..0x52].copy_from_slice(volume_label);
+    bpb[0x52..0x5A].copy_from_slice(b"FAT32   ");
+    bpb[0x1FE] = 0x55;
+    bpb[0x1FF] = 0xAA;
+
+    bpb
+}
+
+fn create_fs_info(free_clusters: u32) -> Vec<u8> {
+    let mut fs_info = vec![0u8; SECTOR_SIZE];
+
+    fs_info[0x000..0x004].copy_from_slice(&0x41615252u32.to_le_bytes());
+    fs_info[0x1E4..0x1E8].copy_from_slice(&0x61417272u32.to_le_bytes());
+    fs_info[0x1E8..0x1EC].copy_from_slice(&free_clusters.to_le_bytes());
+    fs_info[0x1EC..0x1F0].copy_from_slice(&(ROOT_DIRECTORY_CLUSTER + 1).to_le_bytes());
+    fs_info[0x1FC..0x200].copy_from_slice(&0xAA550000u32.to_le_bytes());
+
+    fs_info
+}
+
+fn zero_sectors(drive: &DeviceReference, start: usize, count: usize) -> error::Result<()> {
+    let zeros = vec![0u8; ZERO_CHUNK_SECTORS * SECTOR_SIZE];
+
+    let mut sector = start;
+    while sector < start + count {
+        let length = core::cmp::min(ZERO_CHUNK_SECTORS, start + count - sector);
+        cache::write(drive, sector, &zeros[..length * SECTOR_SIZE])?;
+        sector += length;
+    }
+
+    Ok(())
+}
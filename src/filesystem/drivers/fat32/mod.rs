@@ -1,54 +1,195 @@
-use crate::{device::DeviceReference, error, filesystem::FilesystemStarter, locks::Mutex};
+use crate::{
+    device::DeviceReference,
+    error,
+    filesystem::{cache, FilesystemStarter},
+    locks::Mutex,
+};
 use alloc::{
     boxed::Box,
     string::{String, ToString},
     sync::Arc,
 };
 
+mod check;
 mod directory;
 mod fat;
 mod file;
+mod format;
+
+pub use check::CheckReport;
 
 const SECTOR_SIZE: usize = 512;
 
-pub fn detect_fat32_filesystem(
+pub fn detect_fat_filesystem(
     drive_lock: DeviceReference,
-    start: usize,
     _: usize,
 ) -> error::Result<Option<FilesystemStarter>> {
-    let mut drive = drive_lock.lock();
+    let (fat, volume_name, root_directory_cluster) = match open_volume(&drive_lock)? {
+        Some(volume) => volume,
+        None => return Ok(None),
+    };
+
+    let filesystem_type = match fat.fat_type() {
+        fat::FATType::FAT12 => "fat12",
+        fat::FATType::FAT16 => "fat16",
+        fat::FATType::FAT32 => "fat32",
+    };
+    let fat = Arc::new(Mutex::new(fat));
+
+    // Create filesystem starter
+    Ok(Some(FilesystemStarter::new(
+        Box::new(directory::Directory::new_root(root_directory_cluster, fat)),
+        volume_name,
+        filesystem_type,
+    )))
+}
 
-    // Get BPB
-    let mut bpb = [0u8; 512];
-    drive.read(start * SECTOR_SIZE, &mut bpb)?;
+// The volume should not be in use by a mounted filesystem
+pub fn check_fat_filesystem(
+    drive_lock: DeviceReference,
+    repair: bool,
+) -> error::Result<CheckReport> {
+    let (mut fat, _, root_directory_cluster) = match open_volume(&drive_lock)? {
+        Some(volume) => volume,
+        None => return Err(error::Status::NoFilesystem),
+    };
+
+    check::check(&mut fat, root_directory_cluster, repair)
+}
 
-    bpb[0] = 0xFF;
-    bpb[1] = 0xAB;
+// Any existing data on the drive is lost
+pub fn format_fat_filesystem(drive_lock: DeviceReference, volume_name: &str) -> error::Result<()> {
+    format::format(&drive_lock, volume_name)
+}
 
-    drive.write(start * SECTOR_SIZE, &bpb)?;
+// Returns the FAT, volume name and root directory cluster if the drive holds a FAT volume
+fn open_volume(drive_lock: &DeviceReference) -> error::Result<Option<(fat::FAT, String, u32)>> {
+    // Get BPB, through the cache as it may hold writes not yet on the drive
+    let mut bpb = [0u8; SECTOR_SIZE];
+    cache::read(drive_lock, 0, &mut bpb)?;
 
-    // Locate signature in BPB
-    let bpb_signature = bpb[0x42];
-    if bpb_signature != 0x28 && bpb_signature != 0x29 {
+    // Locate bootable partition signature in BPB
+    let boot_signature = &bpb[0x1FE..0x200];
+    if boot_signature[0] != 0x55 || boot_signature[1] != 0xAA {
         return Ok(None);
     }
 
-    // Locate system identifier string in BPB
-    let system_identifier = String::from_utf8_lossy(&bpb[0x52..0x5A]);
-    if system_identifier != "FAT32   " {
+    // Gather FAT info
+    let bytes_per_sector = (bpb[0x0B] as u16) | ((bpb[0x0C] as u16) << 8);
+    let sectors_per_cluster = bpb[0x0D];
+    let reserved_sector_count = (bpb[0x0E] as u16) | ((bpb[0x0F] as u16) << 8);
+    let num_fats = bpb[0x10];
+    let root_entry_count = (bpb[0x11] as u32) | ((bpb[0x12] as u32) << 8);
+    let total_sectors_16 = (bpb[0x13] as u32) | ((bpb[0x14] as u32) << 8);
+    let fat_size_16 = (bpb[0x16] as u32) | ((bpb[0x17] as u32) << 8);
+    let total_sectors_32 = read_u32(&bpb, 0x20);
+    let fat_size_32 = read_u32(&bpb, 0x24);
+
+    // Verify the BPB is sane
+    if bytes_per_sector as usize != SECTOR_SIZE
+        || sectors_per_cluster == 0
+        || !sectors_per_cluster.is_power_of_two()
+        || reserved_sector_count == 0
+        || num_fats == 0
+    {
         return Ok(None);
     }
 
-    // Locate bootable partition signature in BPB
-    let boot_signature = &bpb[0x1FE..0x200];
-    if boot_signature[0] != 0x55 || boot_signature[1] != 0xAA {
+    let fat_size = if fat_size_16 != 0 {
+        fat_size_16
+    } else {
+        fat_size_32
+    };
+    let total_sectors = if total_sectors_16 != 0 {
+        total_sectors_16
+    } else {
+        total_sectors_32
+    };
+    let root_directory_sectors =
+        ((root_entry_count * 32) + (bytes_per_sector as u32 - 1)) / bytes_per_sector as u32;
+
+    // Determine the FAT type from the cluster count
+    let metadata_sectors =
+        reserved_sector_count as u32 + (num_fats as u32 * fat_size) + root_directory_sectors;
+    if fat_size == 0 || total_sectors <= metadata_sectors {
         return Ok(None);
     }
 
+    let cluster_count = (total_sectors - metadata_sectors) / sectors_per_cluster as u32;
+    let fat_type = if cluster_count < 4085 {
+        fat::FATType::FAT12
+    } else if cluster_count < 65525 {
+        fat::FATType::FAT16
+    } else {
+        fat::FATType::FAT32
+    };
+
+    let (volume_name, root_directory_cluster, fs_info) = match fat_type {
+        fat::FATType::FAT32 => {
+            // Locate signature in BPB
+            let bpb_signature = bpb[0x42];
+            if bpb_signature != 0x28 && bpb_signature != 0x29 {
+                return Ok(None);
+            }
+
+            // Locate system identifier string in BPB
+            let system_identifier = String::from_utf8_lossy(&bpb[0x52..0x5A]);
+            if system_identifier != "FAT32   " {
+                return Ok(None);
+            }
+
+            let fs_info = match read_fs_info(drive_lock, &bpb)? {
+                Some(fs_info) => fs_info,
+                None => return Ok(None),
+            };
+
+            (
+                String::from_utf8_lossy(&bpb[0x47..0x52]).trim().to_string(),
+                read_u32(&bpb, 0x2C),
+                Some(fs_info),
+            )
+        }
+        fat::FATType::FAT12 | fat::FATType::FAT16 => {
+            if root_entry_count == 0 {
+                return Ok(None);
+            }
+
+            // Older volumes may not have an extended BPB
+            let volume_name = match bpb[0x26] {
+                0x28 | 0x29 => String::from_utf8_lossy(&bpb[0x2B..0x36]).trim().to_string(),
+                _ => String::new(),
+            };
+
+            // The fixed root directory region is referred to as cluster 0
+            (volume_name, 0, None)
+        }
+    };
+
+    // Create FAT
+    let mut fat = fat::FAT::new(
+        drive_lock.clone(),
+        fat_type,
+        sectors_per_cluster,
+        reserved_sector_count,
+        num_fats,
+        fat_size,
+        bytes_per_sector,
+        root_directory_sectors,
+        cluster_count,
+    );
+    if let Some((fs_info_sector, free_clusters)) = fs_info {
+        fat.set_fs_info(fs_info_sector, free_clusters);
+    }
+
+    Ok(Some((fat, volume_name, root_directory_cluster)))
+}
+
+// Returns the FSInfo sector and its free cluster count if the signatures are valid
+fn read_fs_info(drive_lock: &DeviceReference, bpb: &[u8]) -> error::Result<Option<(usize, u32)>> {
     // Get FSInfo
-    let mut fs_info = [0u8; 512];
+    let mut fs_info = [0u8; SECTOR_SIZE];
     let fs_info_sector = (bpb[0x30] as usize) | ((bpb[0x31] as usize) << 8);
-    drive.read(fs_info_sector, &mut fs_info)?;
+    cache::read(drive_lock, fs_info_sector, &mut fs_info)?;
 
     // Verify lead, middle, and trailing signatures
     if fs_info[0] != 0x52 || fs_info[1] != 0x52 || fs_info[2] != 0x61 || fs_info[3] != 0x41 {
@@ -70,40 +211,13 @@ pub fn detect_fat32_filesystem(
     {
         return Ok(None);
     }
-    drop(fs_info);
 
-    // Gather FAT info
-    let sectors_per_cluster = bpb[0x0D];
-    let reserved_sector_count = (bpb[0x0E] as u16) | ((bpb[0x0F] as u16) << 8);
-    let num_fats = bpb[0x10];
-    let fat_size = (bpb[0x24] as u32)
-        | ((bpb[0x25] as u32) << 8)
-        | ((bpb[0x26] as u32) << 16)
-        | ((bpb[0x27] as u32) << 24);
-    let bytes_per_sector = (bpb[0x0B] as u16) | ((bpb[0x0C] as u16) << 8);
-
-    // Create FAT
-    let fat = Arc::new(Mutex::new(fat::FAT::new(
-        drive_lock.clone(),
-        sectors_per_cluster,
-        reserved_sector_count,
-        num_fats,
-        fat_size,
-        bytes_per_sector,
-    )));
-
-    // Get volume name
-    let volume_name = String::from_utf8_lossy(&bpb[0x47..0x52]).trim().to_string();
-
-    // Get root directory cluster
-    let root_directory_cluster = (bpb[0x2C] as u32)
-        | ((bpb[0x2D] as u32) << 8)
-        | ((bpb[0x2E] as u32) << 16)
-        | ((bpb[0x2F] as u32) << 24);
+    Ok(Some((fs_info_sector, read_u32(&fs_info, 0x1E8))))
+}
 
-    // Create filesystem starter
-    Ok(Some(FilesystemStarter::new(
-        Box::new(directory::Directory::new(root_directory_cluster, fat)),
-        volume_name,
-    )))
+fn read_u32(buffer: &[u8], offset: usize) -> u32 {
+    (buffer[offset] as u32)
+        | ((buffer[offset + 1] as u32) << 8)
+        | ((buffer[offset + 2] as u32) << 16)
+        | ((buffer[offset + 3] as u32) << 24)
 }
@@ -0,0 +1,172 @@
+use super::volume::{Record, Volume, FLAG_DIRECTORY, FLAG_HIDDEN, FLAG_MULTI_EXTENT};
+use crate::{
+    error,
+    filesystem::{self, File, Metadata},
+};
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use core::any::Any;
+
+pub struct Directory {
+    extent: u32,
+    length: u32,
+    volume: Arc<Volume>,
+}
+
+struct Entry {
+    record: Record,
+    extents: Vec<(u32, u32)>,
+}
+
+impl Directory {
+    pub fn new(extent: u32, length: u32, volume: Arc<Volume>) -> Self {
+        Directory {
+            extent,
+            length,
+            volume,
+        }
+    }
+
+    fn read_entries(&self) -> error::Result<Vec<Entry>> {
+        let mut entries: Vec<Entry> = Vec::new();
+        let mut continuing = false;
+        for mut record in self.volume.read_directory(self.extent, self.length)? {
+            // Files larger than an extent continue in records with the same name
+            if continuing {
+                if let Some(entry) = entries.last_mut() {
+                    entry.extents.push((record.extent, record.length));
+                }
+
+                continuing = record.flags & FLAG_MULTI_EXTENT != 0;
+                continue;
+            }
+
+            continuing = record.flags & FLAG_MULTI_EXTENT != 0;
+
+            // Relocated directories are reached through their child link instead
+            if record.name == "." || record.name == ".." || record.relocated {
+                continue;
+            }
+
+            if let Some(child_link) = record.child_link {
+                // Only the "." record is needed for the directory's length
+                let dot = self.volume.read_directory(child_link, 1)?;
+                record.flags |= FLAG_DIRECTORY;
+                record.extent = child_link;
+                record.length = match dot.first() {
+                    Some(dot) => dot.length,
+                    None => return Err(error::Status::CorruptFilesystem),
+                };
+            }
+
+            let mut extents = Vec::new();
+            extents.push((record.extent, record.length));
+            entries.push(Entry { record, extents });
+        }
+
+        Ok(entries)
+    }
+
+    fn find(&self, name: &str) -> error::Result<Entry> {
+        self.read_entries()?
+            .into_iter()
+            .find(|entry| entry.record.name == name)
+            .ok_or(error::Status::NoEntry)
+    }
+}
+
+impl filesystem::Directory for Directory {
+    fn get_children(&self) -> error::Result<Vec<(String, Metadata)>> {
+        Ok(self
+            .read_entries()?
+            .into_iter()
+            .map(|entry| {
+                let mut attributes = filesystem::ATTRIBUTE_READ_ONLY;
+                if entry.record.flags & FLAG_HIDDEN != 0 {
+                    attributes |= filesystem::ATTRIBUTE_HIDDEN;
+                }
+
+                let size = entry
+                    .extents
+                    .iter()
+                    .map(|(_, length)| *length as usize)
+                    .sum();
+                let metadata = Metadata::new(
+                    size,
+                    entry.record.flags & FLAG_DIRECTORY != 0,
+                    attributes,
+                    entry.record.time,
+                    entry.record.time,
+                    entry.record.time,
+                );
+
+                (entry.record.name, metadata)
+            })
+            .collect())
+    }
+
+    fn open_file(&self, filename: &str) -> error::Result<Box<dyn File>> {
+        let entry = self.find(filename)?;
+        if entry.record.flags & FLAG_DIRECTORY != 0 {
+            Err(error::Status::IsDirectory)
+        } else if entry.record.interleaved {
+            Err(error::Status::NotSupported)
+        } else {
+            Ok(Box::new(super::file::File::new(
+                entry.extents,
+                self.volume.clone(),
+            )))
+        }
+    }
+
+    fn open_directory(
+        &self,
+        directory_name: &str,
+    ) -> error::Result<Box<dyn filesystem::Directory>> {
+        let entry = self.find(directory_name)?;
+        if entry.record.flags & FLAG_DIRECTORY == 0 {
+            return Err(error::Status::IsFile);
+        }
+
+        Ok(Box::new(Directory::new(
+            entry.record.extent,
+            entry.record.length,
+            self.volume.clone(),
+        )))
+    }
+
+    fn make_file(&self, _: &str) -> error::Result<()> {
+        Err(error::Status::ReadOnlyFilesystem)
+    }
+
+    fn make_directory(&self, _: &str) -> error::Result<()> {
+        Err(error::Status::ReadOnlyFilesystem)
+    }
+
+    fn rename_file(&self, _: &str, _: &str) -> error::Result<()> {
+        Err(error::Status::ReadOnlyFilesystem)
+    }
+
+    fn rename_directory(&self, _: &str, _: &str) -> error::Result<()> {
+        Err(error::Status::ReadOnlyFilesystem)
+    }
+
+    fn move_entry(&self, _: &str, _: &dyn filesystem::Directory, _: &str) -> error::Result<()> {
+        Err(error::Status::ReadOnlyFilesystem)
+    }
+
+    fn remove(&self, _: &str) -> error::Result<()> {
+        Err(error::Status::ReadOnlyFilesystem)
+    }
+
+    fn update_metadata(&self, _: &str, _: Metadata) -> error::Result<()> {
+        Err(error::Status::ReadOnlyFilesystem)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn get_space(&self) -> error::Result<(usize, usize)> {
+        Ok((self.volume.size(), 0))
+    }
+}
@@ -0,0 +1,74 @@
+use super::volume::{Volume, BLOCK_SIZE};
+use crate::error;
+use alloc::{sync::Arc, vec::Vec};
+
+pub struct File {
+    extents: Vec<(u32, u32)>,
+    length: usize,
+    volume: Arc<Volume>,
+}
+
+impl File {
+    pub fn new(extents: Vec<(u32, u32)>, volume: Arc<Volume>) -> Self {
+        let length = extents.iter().map(|(_, length)| *length as usize).sum();
+        File {
+            extents,
+            length,
+            volume,
+        }
+    }
+}
+
+impl crate::filesystem::File for File {
+    fn write(&mut self, _: usize, _: &[u8]) -> error::Result<isize> {
+        Err(error::Status::ReadOnlyFilesystem)
+    }
+
+    fn read(&mut self, offset: usize, buffer: &mut [u8]) -> error::Result<isize> {
+        if offset >= self.length {
+            return Ok(-1);
+        }
+
+        let length = core::cmp::min(buffer.len(), self.length - offset);
+        let mut block = Vec::with_capacity(BLOCK_SIZE);
+        block.resize(BLOCK_SIZE, 0);
+
+        let mut read = 0;
+        let mut extent_start = 0;
+        for (extent, extent_length) in &self.extents {
+            let extent_length = *extent_length as usize;
+            while read < length && offset + read < extent_start + extent_length {
+                let position = offset + read - extent_start;
+                let block_offset = position % BLOCK_SIZE;
+                let chunk = core::cmp::min(
+                    core::cmp::min(BLOCK_SIZE - block_offset, length - read),
+                    extent_start + extent_length - (offset + read),
+                );
+
+                self.volume.read_blocks(
+                    extent + (position / BLOCK_SIZE) as u32,
+                    block.as_mut_slice(),
+                )?;
+                buffer[read..read + chunk]
+                    .copy_from_slice(&block[block_offset..block_offset + chunk]);
+                read += chunk;
+            }
+
+            extent_start += extent_length;
+        }
+
+        for byte in &mut buffer[length..] {
+            *byte = 0;
+        }
+
+        Ok(length as isize)
+    }
+
+    fn set_length(&mut self, _: usize) -> error::Result<()> {
+        Err(error::Status::ReadOnlyFilesystem)
+    }
+
+    fn get_length(&self) -> usize {
+        self.length
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{device::DeviceReference, error, filesystem::FilesystemStarter};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use volume::{Naming, Volume, BLOCK_SIZE};
+
+mod directory;
+mod file;
+mod volume;
+
+const DESCRIPTORS_START: u32 = 16;
+const MAX_DESCRIPTORS: u32 = 64;
+
+const DESCRIPTOR_PRIMARY: u8 = 1;
+const DESCRIPTOR_SUPPLEMENTARY: u8 = 2;
+const DESCRIPTOR_TERMINATOR: u8 = 255;
+
+const ROOT_RECORD_OFFSET: usize = 156;
+
+// Devices are either CD drives with 2048 byte sectors or disks with 512 byte sectors
+const SECTORS_PER_BLOCK: [usize; 2] = [1, BLOCK_SIZE / 512];
+
+pub fn detect_iso9660_filesystem(
+    drive_lock: DeviceReference,
+    size: usize,
+) -> error::Result<Option<FilesystemStarter>> {
+    // Locate the first volume descriptor
+    let mut descriptor = Vec::with_capacity(BLOCK_SIZE);
+    descriptor.resize(BLOCK_SIZE, 0);
+
+    let mut sectors_per_block = None;
+    for &candidate in SECTORS_PER_BLOCK.iter() {
+        // Assume the larger sector size so probing never reads past the end
+        let sector = DESCRIPTORS_START as usize * candidate;
+        if (sector + 1) * BLOCK_SIZE > size {
+            continue;
+        }
+
+        drive_lock.lock().read(sector, descriptor.as_mut_slice())?;
+        if is_volume_descriptor(&descriptor) {
+            sectors_per_block = Some(candidate);
+            break;
+        }
+    }
+
+    let sectors_per_block = match sectors_per_block {
+        Some(sectors_per_block) => sectors_per_block,
+        None => return Ok(None),
+    };
+
+    // Find the primary and Joliet descriptors
+    let mut primary = None;
+    let mut joliet = None;
+    for block in DESCRIPTORS_START..DESCRIPTORS_START + MAX_DESCRIPTORS {
+        drive_lock.lock().read(
+            block as usize * sectors_per_block,
+            descriptor.as_mut_slice(),
+        )?;
+        if !is_volume_descriptor(&descriptor) {
+            return Ok(None);
+        }
+
+        match descriptor[0] {
+            DESCRIPTOR_PRIMARY if primary.is_none() => primary = Some(descriptor.clone()),
+            DESCRIPTOR_SUPPLEMENTARY if joliet.is_none() && is_joliet(&descriptor) => {
+                joliet = Some(descriptor.clone())
+            }
+            DESCRIPTOR_TERMINATOR => break,
+            _ => {}
+        }
+    }
+
+    let primary = match primary {
+        Some(primary) => primary,
+        None => return Ok(None),
+    };
+
+    if volume::read_u16(&primary, 128) as usize != BLOCK_SIZE {
+        return Ok(None);
+    }
+
+    let volume_name = String::from_utf8_lossy(&primary[40..72]).trim().to_string();
+
+    // Prefer Rock Ridge names, which are recorded in the primary hierarchy
+    let (root_extent, root_length) = root_directory(&primary);
+    let mut root = Vec::with_capacity(BLOCK_SIZE);
+    root.resize(BLOCK_SIZE, 0);
+    drive_lock.lock().read(
+        root_extent as usize * sectors_per_block,
+        root.as_mut_slice(),
+    )?;
+
+    let (naming, (root_extent, root_length)) =
+        match volume::rock_ridge_skip(&root[..root[0] as usize]) {
+            Some(skip) => (Naming::RockRidge(skip), (root_extent, root_length)),
+            None => match &joliet {
+                Some(joliet) => (Naming::Joliet, root_directory(joliet)),
+                None => (Naming::Plain, (root_extent, root_length)),
+            },
+        };
+
+    let volume = Arc::new(Volume::new(
+        drive_lock.clone(),
+        sectors_per_block,
+        volume::read_u32(&primary, 80),
+        naming,
+    ));
+
+    // Create filesystem starter
+    Ok(Some(FilesystemStarter::new(
+        Box::new(directory::Directory::new(root_extent, root_length, volume)),
+        volume_name,
+        "iso9660",
+    )))
+}
+
+fn is_volume_descriptor(descriptor: &[u8]) -> bool {
+    &descriptor[1..6] == b"CD001" && descriptor[6] == 1
+}
+
+// Joliet is identified by its UCS-2 escape sequence
+fn is_joliet(descriptor: &[u8]) -> bool {
+    &descriptor[88..90] == b"%/" && matches!(descriptor[90], b'@' | b'C' | b'E')
+}
+
+fn root_directory(descriptor: &[u8]) -> (u32, u32) {
+    let record = &descriptor[ROOT_RECORD_OFFSET..ROOT_RECORD_OFFSET + 34];
+    (
+        volume::read_u32(record, 2) + record[1] as u32,
+        volume::read_u32(record, 10),
+    )
+}
@@ -0,0 +1,262 @@
+use crate::{device::DeviceReference, error, time};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+pub const BLOCK_SIZE: usize = 2048;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Naming {
+    Plain,
+    Joliet,
+    RockRidge(usize), // Bytes to skip at the start of each system use area
+}
+
+pub struct Volume {
+    drive: DeviceReference,
+    sectors_per_block: usize,
+    block_count: u32,
+    naming: Naming,
+}
+
+pub struct Record {
+    pub name: String,
+    pub extent: u32,
+    pub length: u32,
+    pub time: isize,
+    pub flags: u8,
+    pub interleaved: bool,
+    pub relocated: bool,
+    pub child_link: Option<u32>,
+}
+
+pub const FLAG_HIDDEN: u8 = 0x01;
+pub const FLAG_DIRECTORY: u8 = 0x02;
+pub const FLAG_MULTI_EXTENT: u8 = 0x80;
+
+const RECORD_HEADER_SIZE: usize = 33;
+const MAX_CONTINUATIONS: usize = 16;
+
+impl Volume {
+    pub fn new(
+        drive: DeviceReference,
+        sectors_per_block: usize,
+        block_count: u32,
+        naming: Naming,
+    ) -> Self {
+        Volume {
+            drive,
+            sectors_per_block,
+            block_count,
+            naming,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.block_count as usize * BLOCK_SIZE
+    }
+
+    pub fn read_blocks(&self, block: u32, buffer: &mut [u8]) -> error::Result<()> {
+        self.drive
+            .lock()
+            .read(block as usize * self.sectors_per_block, buffer)
+    }
+
+    // Returns every record in a directory extent, "." and ".." included
+    pub fn read_directory(&self, extent: u32, length: u32) -> error::Result<Vec<Record>> {
+        let block_count = (length as usize + BLOCK_SIZE - 1) / BLOCK_SIZE;
+        let mut data = Vec::with_capacity(block_count * BLOCK_SIZE);
+        data.resize(block_count * BLOCK_SIZE, 0);
+        self.read_blocks(extent, data.as_mut_slice())?;
+
+        let mut records = Vec::new();
+        let mut offset = 0;
+        while offset < length as usize {
+            // Records never cross block boundaries, a zero length pads to the next block
+            let record_length = data[offset] as usize;
+            if record_length == 0 {
+                offset = (offset / BLOCK_SIZE + 1) * BLOCK_SIZE;
+                continue;
+            }
+
+            if record_length < RECORD_HEADER_SIZE
+                || offset % BLOCK_SIZE + record_length > BLOCK_SIZE
+            {
+                return Err(error::Status::CorruptFilesystem);
+            }
+
+            records.push(self.parse_record(&data[offset..offset + record_length])?);
+            offset += record_length;
+        }
+
+        Ok(records)
+    }
+
+    fn parse_record(&self, record: &[u8]) -> error::Result<Record> {
+        let name_length = record[32] as usize;
+        if RECORD_HEADER_SIZE + name_length > record.len() {
+            return Err(error::Status::CorruptFilesystem);
+        }
+
+        let identifier = &record[RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + name_length];
+        let flags = record[25];
+
+        let mut parsed = Record {
+            name: match identifier {
+                [0] => ".".to_string(),
+                [1] => "..".to_string(),
+                _ => match self.naming {
+                    Naming::Joliet => joliet_name(identifier),
+                    _ => plain_name(identifier),
+                },
+            },
+            extent: read_u32(record, 2) + record[1] as u32,
+            length: read_u32(record, 10),
+            time: record_time(&record[18..25]),
+            flags,
+            interleaved: record[26] != 0 || record[27] != 0,
+            relocated: false,
+            child_link: None,
+        };
+
+        if let Naming::RockRidge(skip) = self.naming {
+            // The system use area follows the identifier and its padding byte
+            let start = RECORD_HEADER_SIZE + name_length + (1 - name_length % 2) + skip;
+            if start < record.len() {
+                self.parse_rock_ridge(&record[start..], &mut parsed)?;
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn parse_rock_ridge(&self, area: &[u8], record: &mut Record) -> error::Result<()> {
+        let mut name: Option<String> = None;
+        let mut area = area.to_vec();
+
+        for _ in 0..MAX_CONTINUATIONS {
+            let mut continuation = None;
+
+            let mut offset = 0;
+            while offset + 4 <= area.len() {
+                let signature = &area[offset..offset + 2];
+                let length = area[offset + 2] as usize;
+                if length < 4 || offset + length > area.len() {
+                    break;
+                }
+
+                let entry = &area[offset..offset + length];
+                match signature {
+                    b"NM" if length >= 5 => {
+                        // Current and parent flags only apply to "." and ".."
+                        if entry[4] & 0x06 == 0 {
+                            name.get_or_insert_with(String::new)
+                                .push_str(&String::from_utf8_lossy(&entry[5..]));
+                        }
+                    }
+                    b"CE" if length >= 28 => {
+                        continuation =
+                            Some((read_u32(entry, 4), read_u32(entry, 12), read_u32(entry, 20)))
+                    }
+                    b"CL" if length >= 12 => record.child_link = Some(read_u32(entry, 4)),
+                    b"RE" => record.relocated = true,
+                    b"ST" => break,
+                    _ => {}
+                }
+
+                offset += length;
+            }
+
+            let (block, block_offset, length) = match continuation {
+                Some(continuation) => continuation,
+                None => break,
+            };
+
+            // Read the continuation area
+            let start = block_offset as usize;
+            let end = start + length as usize;
+            let mut data = Vec::with_capacity((end + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE);
+            data.resize((end + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE, 0);
+            self.read_blocks(block, data.as_mut_slice())?;
+            area = data[start..end].to_vec();
+        }
+
+        if let Some(name) = name {
+            if record.name != "." && record.name != ".." {
+                record.name = name;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Returns the bytes to skip in each system use area if the "." record has a SUSP indicator
+pub fn rock_ridge_skip(dot_record: &[u8]) -> Option<usize> {
+    let name_length = *dot_record.get(32)? as usize;
+    let start = RECORD_HEADER_SIZE + name_length + (1 - name_length % 2);
+    let indicator = dot_record.get(start..start + 7)?;
+    if &indicator[0..2] == b"SP"
+        && indicator[2] == 7
+        && indicator[4] == 0xBE
+        && indicator[5] == 0xEF
+    {
+        Some(indicator[6] as usize)
+    } else {
+        None
+    }
+}
+
+// Strips the version number and an empty extension
+fn plain_name(identifier: &[u8]) -> String {
+    let name = String::from_utf8_lossy(identifier);
+    let name = match name.rfind(';') {
+        Some(index) => &name[..index],
+        None => &name,
+    };
+
+    name.trim_end_matches('.').to_lowercase()
+}
+
+fn joliet_name(identifier: &[u8]) -> String {
+    let name: String = core::char::decode_utf16(
+        identifier
+            .chunks_exact(2)
+            .map(|pair| ((pair[0] as u16) << 8) | pair[1] as u16),
+    )
+    .map(|c| c.unwrap_or(core::char::REPLACEMENT_CHARACTER))
+    .collect();
+
+    match name.rfind(';') {
+        Some(index) => name[..index].to_string(),
+        None => name,
+    }
+}
+
+// Recording times are years since 1900 followed by a GMT offset in 15 minute intervals
+fn record_time(date: &[u8]) -> isize {
+    if date[1] < 1 || date[1] > 12 || date[2] < 1 {
+        return 0;
+    }
+
+    time::date_to_epoch(
+        date[0] as isize + 1900,
+        date[1] as isize,
+        date[2] as isize,
+        date[3] as isize,
+        date[4] as isize,
+        date[5] as isize,
+    ) - (date[6] as i8) as isize * 15 * 60
+}
+
+pub fn read_u16(buffer: &[u8], offset: usize) -> u16 {
+    (buffer[offset] as u16) | ((buffer[offset + 1] as u16) << 8)
+}
+
+pub fn read_u32(buffer: &[u8], offset: usize) -> u32 {
+    (buffer[offset] as u32)
+        | ((buffer[offset + 1] as u32) << 8)
+        | ((buffer[offset + 2] as u32) << 16)
+        | ((buffer[offset + 3] as u32) << 24)
+}
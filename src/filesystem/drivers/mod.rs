@@ -1 +1,7 @@
+pub mod devfs;
+pub mod exfat;
+pub mod ext2;
 pub mod fat32;
+pub mod iso9660;
+pub mod procfs;
+pub mod tmpfs;
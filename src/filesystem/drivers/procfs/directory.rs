@@ -0,0 +1,149 @@
+use super::{Node, Source, GLOBAL_FILES, PROCESS_FILES};
+use crate::{
+    error,
+    filesystem::{self, File, Metadata},
+    session,
+};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::any::Any;
+
+pub struct Directory {
+    node: Node,
+}
+
+enum Entry {
+    File(Source),
+    Directory(Node),
+}
+
+impl Directory {
+    pub fn new(node: Node) -> Self {
+        Directory { node }
+    }
+
+    fn entries(&self) -> Vec<(String, Entry)> {
+        let mut entries = Vec::new();
+        match self.node {
+            Node::Root => {
+                for (name, source) in GLOBAL_FILES.iter() {
+                    entries.push((name.to_string(), Entry::File(*source)));
+                }
+
+                entries.push(("daemons".to_string(), Entry::Directory(Node::Daemons)));
+                entries.push(("sessions".to_string(), Entry::Directory(Node::Sessions)));
+            }
+            Node::Daemons => {
+                for pid in super::get_processes(None) {
+                    entries.push((pid.to_string(), Entry::Directory(Node::Process(None, pid))));
+                }
+            }
+            Node::Sessions => {
+                for session_id in session::get_sessions() {
+                    entries.push((
+                        session_id.to_string(),
+                        Entry::Directory(Node::Session(session_id)),
+                    ));
+                }
+            }
+            Node::Session(session_id) => {
+                for pid in super::get_processes(Some(session_id)) {
+                    entries.push((
+                        pid.to_string(),
+                        Entry::Directory(Node::Process(Some(session_id), pid)),
+                    ));
+                }
+            }
+            Node::Process(session_id, pid) => {
+                for (name, file) in PROCESS_FILES.iter() {
+                    entries.push((
+                        name.to_string(),
+                        Entry::File(Source::Process(session_id, pid, *file)),
+                    ));
+                }
+            }
+        }
+
+        entries
+    }
+
+    fn find(&self, name: &str) -> error::Result<Entry> {
+        self.entries()
+            .into_iter()
+            .find(|(entry_name, _)| entry_name == name)
+            .map(|(_, entry)| entry)
+            .ok_or(error::Status::NoEntry)
+    }
+}
+
+impl filesystem::Directory for Directory {
+    fn get_children(&self) -> error::Result<Vec<(String, Metadata)>> {
+        Ok(self
+            .entries()
+            .into_iter()
+            .map(|(name, entry)| {
+                let is_directory = match entry {
+                    Entry::File(_) => false,
+                    Entry::Directory(_) => true,
+                };
+
+                (
+                    name,
+                    Metadata::new(0, is_directory, filesystem::ATTRIBUTE_READ_ONLY, 0, 0, 0),
+                )
+            })
+            .collect())
+    }
+
+    fn open_file(&self, filename: &str) -> error::Result<Box<dyn File>> {
+        match self.find(filename)? {
+            Entry::File(source) => Ok(Box::new(super::file::File::new(source)?)),
+            Entry::Directory(_) => Err(error::Status::IsDirectory),
+        }
+    }
+
+    fn open_directory(
+        &self,
+        directory_name: &str,
+    ) -> error::Result<Box<dyn filesystem::Directory>> {
+        match self.find(directory_name)? {
+            Entry::File(_) => Err(error::Status::IsFile),
+            Entry::Directory(node) => Ok(Box::new(Directory::new(node))),
+        }
+    }
+
+    fn make_file(&self, _: &str) -> error::Result<()> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn make_directory(&self, _: &str) -> error::Result<()> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn rename_file(&self, _: &str, _: &str) -> error::Result<()> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn rename_directory(&self, _: &str, _: &str) -> error::Result<()> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn move_entry(&self, _: &str, _: &dyn filesystem::Directory, _: &str) -> error::Result<()> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn remove(&self, _: &str) -> error::Result<()> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn update_metadata(&self, _: &str, _: Metadata) -> error::Result<()> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
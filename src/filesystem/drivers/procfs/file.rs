@@ -0,0 +1,156 @@
+use super::{ProcessFile, Source};
+use crate::{error, filesystem, interrupts::irq, memory, time};
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::Write;
+
+// Contents are generated on open and by every read from the start
+pub struct File {
+    source: Source,
+    contents: Vec<u8>,
+}
+
+impl File {
+    pub fn new(source: Source) -> error::Result<Self> {
+        Ok(File {
+            source,
+            contents: generate(source)?.into_bytes(),
+        })
+    }
+}
+
+impl filesystem::File for File {
+    fn write(&mut self, _: usize, _: &[u8]) -> error::Result<isize> {
+        Err(error::Status::ReadOnlyFilesystem)
+    }
+
+    fn read(&mut self, offset: usize, buffer: &mut [u8]) -> error::Result<isize> {
+        if offset == 0 {
+            self.contents = generate(self.source)?.into_bytes();
+        }
+
+        let contents = &self.contents;
+        if offset >= contents.len() {
+            return Ok(-1);
+        }
+
+        let length = core::cmp::min(buffer.len(), contents.len() - offset);
+        buffer[..length].copy_from_slice(&contents[offset..offset + length]);
+        for byte in &mut buffer[length..] {
+            *byte = 0;
+        }
+
+        Ok(length as isize)
+    }
+
+    fn set_length(&mut self, _: usize) -> error::Result<()> {
+        Err(error::Status::ReadOnlyFilesystem)
+    }
+
+    fn get_length(&self) -> usize {
+        self.contents.len()
+    }
+}
+
+fn generate(source: Source) -> error::Result<String> {
+    let mut output = String::new();
+    match source {
+        Source::Memory => {
+            let usage = memory::get_memory_usage();
+            output = format!(
+                "available: {}\nfree: {}\nkernel heap: {}\nkernel stacks: {}\nuserspace: {}\n",
+                usage.available_memory(),
+                usage.free_memory(),
+                usage.kernel_heap_memory(),
+                usage.kernel_stack_memory(),
+                usage.userspace_memory()
+            );
+        }
+        Source::Uptime => {
+            let millis = time::current_time_millis();
+            output = format!("{}.{:03}\n", millis / 1000, millis % 1000);
+        }
+        Source::Interrupts => {
+            for (irq, count) in irq::get_irq_counts().iter().enumerate() {
+                writeln!(output, "{}: {}", irq, count).ok();
+            }
+        }
+        Source::Mounts => {
+            // Space is left out as querying it would lock the root directories
+            for info in filesystem::get_filesystems() {
+                writeln!(
+                    output,
+                    "{} {} {} {} {}",
+                    info.number,
+                    info.mount_path.as_deref().unwrap_or("-"),
+                    info.filesystem_type,
+                    info.device_path.as_deref().unwrap_or("-"),
+                    info.volume_name
+                )
+                .ok();
+            }
+        }
+        Source::Process(session_id, pid, file) => {
+            let info = super::get_process(session_id, pid)
+                .and_then(|process| process.get_process_info())
+                .ok_or(error::Status::NoProcess)?;
+
+            match file {
+                ProcessFile::Name => output = format!("{}\n", info.name),
+                ProcessFile::Threads => write_ids(&mut output, None, &info.threads),
+                ProcessFile::Time => output = format!("{}\n", info.time),
+                ProcessFile::Descriptors => {
+                    write_ids(&mut output, Some("files"), &info.files);
+                    write_ids(&mut output, Some("directories"), &info.directories);
+                    write_ids(&mut output, Some("devices"), &info.devices);
+                    write_ids(&mut output, Some("pipe readers"), &info.pipe_readers);
+                    write_ids(&mut output, Some("pipe writers"), &info.pipe_writers);
+                }
+                ProcessFile::WorkingDirectory => output = format!("{}\n", info.working_directory),
+                ProcessFile::Memory => {
+                    output = format!("{}\n", info.memory_pages * memory::PAGE_SIZE)
+                }
+                ProcessFile::Signals => {
+                    let signals = super::get_process(session_id, pid)
+                        .and_then(|process| process.signals())
+                        .ok_or(error::Status::NoProcess)?;
+
+                    let mut pending = Vec::new();
+                    let mut masked = Vec::new();
+                    for signal in 0..=255 {
+                        if signals.is_pending(signal) {
+                            pending.push(signal as isize);
+                        }
+
+                        if signals.is_masked(signal) {
+                            masked.push(signal as isize);
+                        }
+                    }
+
+                    write_ids(&mut output, Some("pending"), &pending);
+                    write_ids(&mut output, Some("masked"), &masked);
+                }
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+// Writes a labelled line of identifiers, or one identifier per line without a label
+fn write_ids(output: &mut String, label: Option<&str>, ids: &[isize]) {
+    match label {
+        Some(label) => {
+            output.push_str(label);
+            output.push(':');
+            for id in ids {
+                write!(output, " {}", id).ok();
+            }
+            output.push('\n');
+        }
+        None => {
+            for id in ids {
+                writeln!(output, "{}", id).ok();
+            }
+        }
+    }
+}
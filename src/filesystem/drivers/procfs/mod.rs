@@ -0,0 +1,77 @@
+use crate::{filesystem::FilesystemStarter, process::ProcessReference, session};
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+mod directory;
+mod file;
+
+// Processes are found under "daemons/<pid>" and "sessions/<sid>/<pid>"
+#[derive(Clone, Copy)]
+enum Node {
+    Root,
+    Daemons,
+    Sessions,
+    Session(isize),
+    Process(Option<isize>, isize),
+}
+
+#[derive(Clone, Copy)]
+enum Source {
+    Memory,
+    Uptime,
+    Interrupts,
+    Mounts,
+    Process(Option<isize>, isize, ProcessFile),
+}
+
+#[derive(Clone, Copy)]
+enum ProcessFile {
+    Name,
+    Threads,
+    Time,
+    Descriptors,
+    WorkingDirectory,
+    Memory,
+    Signals,
+}
+
+const GLOBAL_FILES: [(&str, Source); 4] = [
+    ("memory", Source::Memory),
+    ("uptime", Source::Uptime),
+    ("interrupts", Source::Interrupts),
+    ("mounts", Source::Mounts),
+];
+
+const PROCESS_FILES: [(&str, ProcessFile); 7] = [
+    ("name", ProcessFile::Name),
+    ("threads", ProcessFile::Threads),
+    ("time", ProcessFile::Time),
+    ("descriptors", ProcessFile::Descriptors),
+    ("working_directory", ProcessFile::WorkingDirectory),
+    ("memory", ProcessFile::Memory),
+    ("signals", ProcessFile::Signals),
+];
+
+pub fn create_procfs_filesystem(volume_name: String) -> FilesystemStarter {
+    FilesystemStarter::new(
+        Box::new(directory::Directory::new(Node::Root)),
+        volume_name,
+        "procfs",
+    )
+}
+
+fn get_process(session_id: Option<isize>, pid: isize) -> Option<ProcessReference> {
+    match session_id {
+        None => crate::process::get_daemon_process(pid),
+        Some(session_id) => session::get_session(session_id)?.lock().get_process(pid),
+    }
+}
+
+fn get_processes(session_id: Option<isize>) -> Vec<isize> {
+    match session_id {
+        None => crate::process::get_daemon_processes(),
+        Some(session_id) => match session::get_session(session_id) {
+            Some(session) => session.lock().get_processes(),
+            None => Vec::new(),
+        },
+    }
+}
@@ -0,0 +1,187 @@
+use super::{DirectoryBox, Node};
+use crate::{
+    error,
+    filesystem::{self, File, Metadata},
+    locks::Mutex,
+};
+use alloc::{borrow::ToOwned, boxed::Box, string::String, sync::Arc, vec::Vec};
+use core::any::Any;
+
+pub struct Directory {
+    children: DirectoryBox,
+}
+
+impl Directory {
+    pub fn new(children: DirectoryBox) -> Self {
+        Directory { children }
+    }
+
+    fn get_node(&self, name: &str) -> error::Result<Node> {
+        for (sub_name, node, _) in self.children.lock().iter() {
+            if sub_name == name {
+                return Ok(node.clone());
+            }
+        }
+
+        Err(error::Status::NoEntry)
+    }
+
+    fn create(&self, name: &str, node: Node, metadata: Metadata) -> error::Result<()> {
+        let mut children = self.children.lock();
+        for (sub_name, _, _) in children.iter() {
+            if sub_name == name {
+                return Err(error::Status::Exists);
+            }
+        }
+
+        children.push((name.to_owned(), node, metadata));
+        Ok(())
+    }
+
+    fn rename(&self, old_name: &str, new_name: &str, directory: bool) -> error::Result<()> {
+        let mut children = self.children.lock();
+        for (sub_name, _, _) in children.iter() {
+            if sub_name == new_name {
+                return Err(error::Status::Exists);
+            }
+        }
+
+        for (sub_name, node, _) in children.iter_mut() {
+            if sub_name != old_name {
+                continue;
+            }
+
+            return match node {
+                Node::File(_) if directory => Err(error::Status::IsFile),
+                Node::Directory(_) if !directory => Err(error::Status::IsDirectory),
+                _ => {
+                    *sub_name = new_name.to_owned();
+                    Ok(())
+                }
+            };
+        }
+
+        Err(error::Status::NoEntry)
+    }
+
+    fn take(&self, name: &str) -> error::Result<(Node, Metadata)> {
+        let mut children = self.children.lock();
+        match children
+            .iter()
+            .position(|(sub_name, _, _)| sub_name == name)
+        {
+            Some(index) => {
+                let (_, node, metadata) = children.remove(index);
+                Ok((node, metadata))
+            }
+            None => Err(error::Status::NoEntry),
+        }
+    }
+}
+
+impl filesystem::Directory for Directory {
+    fn get_children(&self) -> error::Result<Vec<(String, Metadata)>> {
+        let children = self.children.lock();
+        let mut ret = Vec::with_capacity(children.len());
+        for (name, node, metadata) in children.iter() {
+            let mut metadata = metadata.clone();
+            if let Node::File(data) = node {
+                metadata.set_size(data.lock().len());
+            }
+
+            ret.push((name.clone(), metadata));
+        }
+
+        Ok(ret)
+    }
+
+    fn open_file(&self, filename: &str) -> error::Result<Box<dyn File>> {
+        match self.get_node(filename)? {
+            Node::File(data) => Ok(Box::new(super::file::File::new(data))),
+            Node::Directory(_) => Err(error::Status::IsDirectory),
+        }
+    }
+
+    fn open_directory(
+        &self,
+        directory_name: &str,
+    ) -> error::Result<Box<dyn filesystem::Directory>> {
+        match self.get_node(directory_name)? {
+            Node::Directory(children) => Ok(Box::new(Directory::new(children))),
+            Node::File(_) => Err(error::Status::IsFile),
+        }
+    }
+
+    fn make_file(&self, filename: &str) -> error::Result<()> {
+        self.create(
+            filename,
+            Node::File(Arc::new(Mutex::new(Vec::new()))),
+            Metadata::new_now(0, false),
+        )
+    }
+
+    fn make_directory(&self, directory_name: &str) -> error::Result<()> {
+        self.create(
+            directory_name,
+            Node::Directory(Arc::new(Mutex::new(Vec::new()))),
+            Metadata::new_now(0, true),
+        )
+    }
+
+    fn rename_file(&self, old_name: &str, new_name: &str) -> error::Result<()> {
+        self.rename(old_name, new_name, false)
+    }
+
+    fn rename_directory(&self, old_name: &str, new_name: &str) -> error::Result<()> {
+        self.rename(old_name, new_name, true)
+    }
+
+    fn move_entry(
+        &self,
+        name: &str,
+        new_directory: &dyn filesystem::Directory,
+        new_name: &str,
+    ) -> error::Result<()> {
+        let new_directory = match new_directory.as_any().downcast_ref::<Directory>() {
+            Some(new_directory) => new_directory,
+            None => return Err(error::Status::NotSupported),
+        };
+
+        let (node, metadata) = self.take(name)?;
+        match new_directory.create(new_name, node.clone(), metadata) {
+            Ok(()) => Ok(()),
+            Err(status) => {
+                self.children.lock().push((name.to_owned(), node, metadata));
+                Err(status)
+            }
+        }
+    }
+
+    fn remove(&self, name: &str) -> error::Result<()> {
+        let mut children = self.children.lock();
+        let length = children.len();
+        children.retain(|(sub_name, _, _)| sub_name != name);
+
+        if children.len() == length {
+            Err(error::Status::NoEntry)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn update_metadata(&self, name: &str, new_metadata: Metadata) -> error::Result<()> {
+        // File sizes are tracked by the file data itself
+        for (sub_name, _, metadata) in self.children.lock().iter_mut() {
+            if sub_name == name {
+                *metadata = new_metadata;
+                return Ok(());
+            }
+        }
+
+        Err(error::Status::NoEntry)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,48 @@
+use super::FileBox;
+use crate::error;
+
+pub struct File {
+    data: FileBox,
+}
+
+impl File {
+    pub fn new(data: FileBox) -> Self {
+        File { data }
+    }
+}
+
+impl crate::filesystem::File for File {
+    fn write(&mut self, offset: usize, buffer: &[u8]) -> error::Result<isize> {
+        let mut data = self.data.lock();
+        if offset + buffer.len() > data.len() {
+            return Err(error::Status::OutOfRange);
+        }
+
+        data[offset..offset + buffer.len()].copy_from_slice(buffer);
+        Ok(buffer.len() as isize)
+    }
+
+    fn read(&mut self, offset: usize, buffer: &mut [u8]) -> error::Result<isize> {
+        let data = self.data.lock();
+        if offset >= data.len() {
+            return Ok(-1);
+        }
+
+        let length = core::cmp::min(buffer.len(), data.len() - offset);
+        buffer[..length].copy_from_slice(&data[offset..offset + length]);
+        for byte in &mut buffer[length..] {
+            *byte = 0;
+        }
+
+        Ok(length as isize)
+    }
+
+    fn set_length(&mut self, new_length: usize) -> error::Result<()> {
+        self.data.lock().resize(new_length, 0);
+        Ok(())
+    }
+
+    fn get_length(&self) -> usize {
+        self.data.lock().len()
+    }
+}
@@ -0,0 +1,25 @@
+use crate::{
+    filesystem::{FilesystemStarter, Metadata},
+    locks::Mutex,
+};
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+
+mod directory;
+mod file;
+
+type DirectoryBox = Arc<Mutex<Vec<(String, Node, Metadata)>>>;
+type FileBox = Arc<Mutex<Vec<u8>>>;
+
+#[derive(Clone)]
+enum Node {
+    File(FileBox),
+    Directory(DirectoryBox),
+}
+
+pub fn create_tmpfs_filesystem(volume_name: String) -> FilesystemStarter {
+    FilesystemStarter::new(
+        Box::new(directory::Directory::new(Arc::new(Mutex::new(Vec::new())))),
+        volume_name,
+        "tmpfs",
+    )
+}
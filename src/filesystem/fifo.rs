@@ -0,0 +1,61 @@
+use crate::{ipc::Pipe, locks::Mutex};
+use alloc::{
+    format,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+
+// Named pipes are marked on disk, open pipes are found by their path from the filesystem root
+// so their files are not held open
+struct Fifo {
+    path: String,
+    pipe: Weak<Mutex<Pipe>>,
+}
+
+static FIFOS: Mutex<Vec<Fifo>> = Mutex::new(Vec::new());
+
+pub fn unregister(path: &str) {
+    FIFOS.lock().retain(|fifo| fifo.path != path);
+}
+
+// Named pipes at or beneath the old path follow it, any replaced by the rename are forgotten
+pub fn rename(old_path: &str, new_path: &str) {
+    let mut fifos = FIFOS.lock();
+    fifos.retain(|fifo| !is_within(&fifo.path, new_path));
+    for fifo in fifos.iter_mut() {
+        if is_within(&fifo.path, old_path) {
+            fifo.path = format!("{}{}", new_path, &fifo.path[old_path.len()..]);
+        }
+    }
+}
+
+pub fn remove_filesystem(fs_number: isize) {
+    let root = format!(":{}", fs_number);
+    FIFOS.lock().retain(|fifo| !is_within(&fifo.path, &root));
+}
+
+// The pipe lives as long as either end is open, later opens start a new one
+pub fn get_pipe(path: String) -> Arc<Mutex<Pipe>> {
+    let mut fifos = FIFOS.lock();
+    if let Some(pipe) = fifos
+        .iter()
+        .find(|fifo| fifo.path == path)
+        .and_then(|fifo| fifo.pipe.upgrade())
+    {
+        return pipe;
+    }
+
+    let pipe = Pipe::new_named();
+    fifos.retain(|fifo| fifo.pipe.strong_count() > 0);
+    fifos.push(Fifo {
+        path,
+        pipe: Arc::downgrade(&pipe),
+    });
+    pipe
+}
+
+fn is_within(path: &str, directory: &str) -> bool {
+    path == directory
+        || (path.starts_with(directory) && path.as_bytes().get(directory.len()) == Some(&b'/'))
+}
@@ -1,11 +1,15 @@
+use super::lock::LockWait;
 use super::FileReference;
-use crate::error;
+use crate::{error, filesystem::Metadata, memory::PhysicalAddress};
+use alloc::string::String;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 pub struct Descriptor {
     file: FileReference,
     current_offset: usize,
     read: bool,
     write: bool,
+    lock_owner: usize,
 }
 
 pub enum SeekFrom {
@@ -14,6 +18,9 @@ pub enum SeekFrom {
     End,
 }
 
+// Identifies the descriptor holding an advisory lock
+static NEXT_LOCK_OWNER: AtomicUsize = AtomicUsize::new(0);
+
 impl Descriptor {
     pub fn new(file: FileReference, read: bool, write: bool, starting_offset: usize) -> Self {
         file.lock().open();
@@ -22,6 +29,7 @@ impl Descriptor {
             current_offset: starting_offset,
             read,
             write,
+            lock_owner: NEXT_LOCK_OWNER.fetch_add(1, Ordering::Relaxed),
         }
     }
 
@@ -53,14 +61,40 @@ impl Descriptor {
         Ok(ret)
     }
 
-    pub fn seek(&mut self, offset: usize, seek_from: SeekFrom) -> usize {
-        match seek_from {
-            SeekFrom::Start => self.current_offset = offset,
-            SeekFrom::Current => self.current_offset += offset,
-            SeekFrom::End => self.current_offset = self.file.lock().get_length() + offset,
+    pub fn seek(&mut self, offset: isize, seek_from: SeekFrom) -> error::Result<usize> {
+        let base = match seek_from {
+            SeekFrom::Start => 0,
+            SeekFrom::Current => self.current_offset,
+            SeekFrom::End => self.file.lock().get_length(),
+        };
+
+        // Offsets before the start of the file are rejected
+        let new_offset = (base as isize)
+            .checked_add(offset)
+            .ok_or(error::Status::InvalidArgument)?;
+        if new_offset < 0 {
+            return Err(error::Status::InvalidArgument);
         }
 
-        self.current_offset
+        self.current_offset = new_offset as usize;
+        Ok(self.current_offset)
+    }
+
+    // Positional reads and writes leave the current offset untouched
+    pub fn read_at(&self, offset: usize, buffer: &mut [u8]) -> error::Result<isize> {
+        if !self.read {
+            return Err(error::Status::WriteOnly);
+        }
+
+        self.file.lock().read(offset, buffer)
+    }
+
+    pub fn write_at(&self, offset: usize, buffer: &[u8]) -> error::Result<isize> {
+        if !self.write {
+            return Err(error::Status::ReadOnly);
+        }
+
+        self.file.lock().write(offset, buffer)
     }
 
     pub fn set_length(&self, new_length: usize) -> error::Result<()> {
@@ -71,6 +105,78 @@ impl Descriptor {
         self.file.lock().set_length(new_length)
     }
 
+    pub fn ioctrl(&self, code: usize, argument: usize) -> error::Result<usize> {
+        self.file.lock().ioctrl(code, argument)
+    }
+
+    pub fn flush(&self) -> error::Result<()> {
+        self.file.lock().flush()
+    }
+
+    // Shared writable mappings write back to the file, so they need write access
+    pub fn begin_mapping(&self, shared_writable: bool) -> error::Result<()> {
+        if !self.read {
+            return Err(error::Status::WriteOnly);
+        }
+
+        if shared_writable && !self.write {
+            return Err(error::Status::ReadOnly);
+        }
+
+        self.file.lock().begin_mapping();
+        Ok(())
+    }
+
+    pub fn end_mapping(&self) {
+        self.file.lock().end_mapping()
+    }
+
+    pub fn get_page(&self, index: usize) -> error::Result<PhysicalAddress> {
+        self.file.lock().get_page(index)
+    }
+
+    pub fn is_page(&self, index: usize, physical_address: PhysicalAddress) -> bool {
+        self.file.lock().is_page(index, physical_address)
+    }
+
+    pub fn write_back(&self, first_page: usize, page_count: usize) -> error::Result<()> {
+        self.file.lock().write_back(first_page, page_count)
+    }
+
+    pub fn construct_filesystem_path(&self) -> String {
+        self.file.lock().construct_filesystem_path()
+    }
+
+    // A length of zero locks to the end of the file and beyond
+    // If the range is held, a wait for the next release is returned
+    pub fn try_lock(
+        &self,
+        start: usize,
+        length: usize,
+        exclusive: bool,
+    ) -> error::Result<Option<LockWait>> {
+        let end = lock_end(start, length)?;
+        let mut file = self.file.lock();
+        if file
+            .locks()
+            .try_lock(self.lock_owner, start, end, exclusive)
+        {
+            Ok(None)
+        } else {
+            Ok(Some(file.locks().get_wait()))
+        }
+    }
+
+    pub fn unlock(&self, start: usize, length: usize) -> error::Result<()> {
+        let end = lock_end(start, length)?;
+        self.file.lock().locks().unlock(self.lock_owner, start, end);
+        Ok(())
+    }
+
+    pub fn get_metadata(&self) -> error::Result<Metadata> {
+        self.file.lock().get_metadata()
+    }
+
     pub fn tell(&self) -> usize {
         self.current_offset
     }
@@ -90,8 +196,20 @@ impl Clone for Descriptor {
 impl Drop for Descriptor {
     fn drop(&mut self) {
         let ptr = self.file.as_ptr();
-        self.file.lock().close(ptr);
+        let mut file = self.file.lock();
+        file.locks().release(self.lock_owner);
+        file.close(ptr);
+    }
+}
+
+fn lock_end(start: usize, length: usize) -> error::Result<usize> {
+    if length == 0 {
+        return Ok(usize::MAX);
     }
+
+    start
+        .checked_add(length)
+        .ok_or(error::Status::InvalidArgument)
 }
 
 impl From<usize> for SeekFrom {
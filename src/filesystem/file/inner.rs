@@ -3,4 +3,14 @@ pub trait File: Send {
     fn write(&mut self, offset: usize, buffer: &[u8]) -> crate::error::Result<isize>;
     fn set_length(&mut self, new_length: usize) -> crate::error::Result<()>;
     fn get_length(&self) -> usize;
+
+    // Writes anything held by the driver out to its device
+    fn flush(&mut self) -> crate::error::Result<()> {
+        Ok(())
+    }
+
+    // Only device files accept control requests
+    fn ioctrl(&mut self, _code: usize, _argument: usize) -> crate::error::Result<usize> {
+        Err(crate::error::Status::InvalidIOCtrl)
+    }
 }
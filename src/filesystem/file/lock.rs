@@ -0,0 +1,134 @@
+use crate::process::{self, CurrentQueue, ThreadQueue};
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+// Locks cover [start, end), an end of usize::MAX extends past the end of the file
+struct RangeLock {
+    owner: usize,
+    start: usize,
+    end: usize,
+    exclusive: bool,
+}
+
+// Releases are counted so a waiter can check for one it missed before sleeping
+pub struct Locks {
+    locks: Vec<RangeLock>,
+    queue: ThreadQueue,
+    releases: Arc<AtomicUsize>,
+}
+
+pub struct LockWait {
+    queue: CurrentQueue,
+    releases: Arc<AtomicUsize>,
+    observed: usize,
+}
+
+impl Locks {
+    pub fn new() -> Self {
+        Locks {
+            locks: Vec::new(),
+            queue: ThreadQueue::new(),
+            releases: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    // An owner's existing locks in the range are replaced, converting them if needed
+    pub fn try_lock(&mut self, owner: usize, start: usize, end: usize, exclusive: bool) -> bool {
+        for lock in &self.locks {
+            if lock.owner != owner
+                && lock.start < end
+                && start < lock.end
+                && (exclusive || lock.exclusive)
+            {
+                return false;
+            }
+        }
+
+        // Converting to a shared lock may let others in
+        if self.remove(owner, start, end) {
+            self.wake_all();
+        }
+
+        self.locks.push(RangeLock {
+            owner,
+            start,
+            end,
+            exclusive,
+        });
+        true
+    }
+
+    pub fn unlock(&mut self, owner: usize, start: usize, end: usize) {
+        if self.remove(owner, start, end) {
+            self.wake_all();
+        }
+    }
+
+    pub fn release(&mut self, owner: usize) {
+        let count = self.locks.len();
+        self.locks.retain(|lock| lock.owner != owner);
+        if self.locks.len() != count {
+            self.wake_all();
+        }
+    }
+
+    pub fn get_wait(&self) -> LockWait {
+        LockWait {
+            queue: self.queue.into_current_queue(),
+            releases: self.releases.clone(),
+            observed: self.releases.load(Ordering::Acquire),
+        }
+    }
+
+    // Splits any of the owner's locks which partially overlap the range
+    fn remove(&mut self, owner: usize, start: usize, end: usize) -> bool {
+        let mut removed = false;
+        let mut remaining = Vec::with_capacity(self.locks.len());
+        for lock in self.locks.drain(..) {
+            if lock.owner != owner || lock.end <= start || end <= lock.start {
+                remaining.push(lock);
+                continue;
+            }
+
+            removed = true;
+            if lock.start < start {
+                remaining.push(RangeLock {
+                    owner,
+                    start: lock.start,
+                    end: start,
+                    exclusive: lock.exclusive,
+                });
+            }
+
+            if end < lock.end {
+                remaining.push(RangeLock {
+                    owner,
+                    start: end,
+                    end: lock.end,
+                    exclusive: lock.exclusive,
+                });
+            }
+        }
+
+        self.locks = remaining;
+        removed
+    }
+
+    fn wake_all(&self) {
+        self.releases.fetch_add(1, Ordering::AcqRel);
+        while let Some(thread) = self.queue.pop() {
+            process::queue_thread(thread)
+        }
+    }
+}
+
+impl LockWait {
+    // Returns once a lock has been released since the wait was created
+    pub fn wait(self) {
+        let releases = self.releases;
+        let observed = self.observed;
+        process::yield_thread_unless(Some(self.queue), &|| {
+            releases.load(Ordering::Acquire) != observed
+        });
+    }
+}
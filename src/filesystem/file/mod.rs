@@ -1,5 +1,6 @@
 mod descriptor;
 mod inner;
+mod lock;
 mod owner;
 mod reference;
 
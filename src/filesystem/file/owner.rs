@@ -1,11 +1,23 @@
-use super::File;
-use crate::{filesystem::directory::DirectoryReference, locks::Mutex};
-use alloc::boxed::Box;
+use super::{lock::Locks, File};
+use crate::{
+    filesystem::{directory::DirectoryReference, Metadata},
+    locks::Mutex,
+    logln,
+    memory::{self, PhysicalAddress, KERNEL_VMA, PAGE_SIZE},
+    time,
+};
+use alloc::{boxed::Box, collections::BTreeMap, string::String};
 
 pub struct FileOwner {
     parent: DirectoryReference,
     file: Box<dyn File>,
     references: usize,
+    // Pages shared by every memory mapping of the file, by page index
+    pages: BTreeMap<usize, PhysicalAddress>,
+    mappings: usize,
+    locks: Locks,
+    // Writes only update the cached metadata, it is stored on flush or close
+    metadata_modified: bool,
 }
 
 impl FileOwner {
@@ -14,9 +26,17 @@ impl FileOwner {
             file: file,
             parent: parent,
             references: 0,
+            pages: BTreeMap::new(),
+            mappings: 0,
+            locks: Locks::new(),
+            metadata_modified: false,
         }
     }
 
+    pub fn set_parent(&mut self, parent: DirectoryReference) {
+        self.parent = parent;
+    }
+
     pub fn open(&mut self) {
         self.references += 1;
     }
@@ -28,15 +48,39 @@ impl FileOwner {
     pub fn write(&mut self, offset: usize, buffer: &[u8]) -> crate::error::Result<isize> {
         let file_length = self.file.get_length();
         if offset + buffer.len() > file_length {
-            self.set_length(offset + buffer.len())?;
+            self.file.set_length(offset + buffer.len())?;
+            self.clear_pages(file_length);
         }
 
-        self.file.write(offset, buffer)
+        let ret = self.file.write(offset, buffer)?;
+        self.set_metadata(false)?;
+
+        // Keep mapped pages up to date
+        let first_page = offset / PAGE_SIZE;
+        let last_page = (offset + buffer.len() + PAGE_SIZE - 1) / PAGE_SIZE;
+        for (&index, &physical_address) in self.pages.range(first_page..last_page) {
+            let page_start = index * PAGE_SIZE;
+            let start = core::cmp::max(page_start, offset);
+            let end = core::cmp::min(page_start + PAGE_SIZE, offset + buffer.len());
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    buffer[start - offset..].as_ptr(),
+                    (physical_address + KERNEL_VMA + start - page_start) as *mut u8,
+                    end - start,
+                )
+            };
+        }
+
+        Ok(ret)
     }
 
     pub fn close(&mut self, arc_ptr: *const Mutex<FileOwner>) {
         self.references -= 1;
         if self.references == 0 {
+            if let Err(status) = self.store_metadata() {
+                logln!("Failed to store file metadata: {}", status);
+            }
+
             let ptr = self.parent.as_ptr();
             self.parent.lock().close_file(arc_ptr, ptr);
         }
@@ -47,15 +91,149 @@ impl FileOwner {
     }
 
     pub fn set_length(&mut self, new_length: usize) -> crate::error::Result<()> {
-        // Update file
+        let old_length = self.file.get_length();
         self.file.set_length(new_length)?;
+        self.clear_pages(core::cmp::min(old_length, new_length));
+        self.update_metadata()
+    }
+
+    pub fn ioctrl(&mut self, code: usize, argument: usize) -> crate::error::Result<usize> {
+        self.file.ioctrl(code, argument)
+    }
+
+    pub fn flush(&mut self) -> crate::error::Result<()> {
+        self.store_metadata()?;
+        self.file.flush()
+    }
+
+    pub fn begin_mapping(&mut self) {
+        self.mappings += 1;
+    }
+
+    // Pages are freed when the last mapping ends
+    pub fn end_mapping(&mut self) {
+        self.mappings -= 1;
+        if self.mappings == 0 {
+            for (_, physical_address) in core::mem::take(&mut self.pages) {
+                memory::free_page(physical_address);
+            }
+        }
+    }
+
+    // Returns the page holding the file at an index, reading it in if needed
+    pub fn get_page(&mut self, index: usize) -> crate::error::Result<PhysicalAddress> {
+        if let Some(physical_address) = self.pages.get(&index) {
+            return Ok(*physical_address);
+        }
+
+        // Reads zero the buffer past the end of the file
+        let physical_address = memory::allocate_page();
+        let page = unsafe {
+            core::slice::from_raw_parts_mut((physical_address + KERNEL_VMA) as *mut u8, PAGE_SIZE)
+        };
+        if let Err(status) = self.file.read(index * PAGE_SIZE, page) {
+            memory::free_page(physical_address);
+            return Err(status);
+        }
+
+        self.pages.insert(index, physical_address);
+        Ok(physical_address)
+    }
+
+    pub fn is_page(&self, index: usize, physical_address: PhysicalAddress) -> bool {
+        self.pages.get(&index) == Some(&physical_address)
+    }
+
+    // Writes mapped pages back to the file without extending it
+    pub fn write_back(&mut self, first_page: usize, page_count: usize) -> crate::error::Result<()> {
+        let length = self.file.get_length();
+        let mut written = false;
+        for (&index, &physical_address) in self.pages.range(first_page..first_page + page_count) {
+            let offset = index * PAGE_SIZE;
+            if offset >= length {
+                break;
+            }
+
+            let page = unsafe {
+                core::slice::from_raw_parts((physical_address + KERNEL_VMA) as *const u8, PAGE_SIZE)
+            };
+            self.file
+                .write(offset, &page[..core::cmp::min(PAGE_SIZE, length - offset)])?;
+            written = true;
+        }
+
+        if written {
+            self.update_metadata()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn locks(&mut self) -> &mut Locks {
+        &mut self.locks
+    }
+
+    pub fn construct_filesystem_path(&self) -> String {
+        let parent = self.parent.lock();
+        let mut path = parent.construct_filesystem_path();
+        path.push('/');
+        path.push_str(parent.get_name(self as *const _ as *const _));
+        path
+    }
+
+    pub fn get_metadata(&self) -> crate::error::Result<Metadata> {
+        self.parent
+            .lock()
+            .get_metadata_ptr(self as *const _ as *const _)
+    }
+
+    // Cached pages past the end of the file may hold old data, which must read as zeros
+    fn clear_pages(&mut self, offset: usize) {
+        for (&index, &physical_address) in self.pages.range(offset / PAGE_SIZE..) {
+            let start = core::cmp::max(offset, index * PAGE_SIZE) - index * PAGE_SIZE;
+            unsafe {
+                core::ptr::write_bytes(
+                    (physical_address + KERNEL_VMA + start) as *mut u8,
+                    0,
+                    PAGE_SIZE - start,
+                )
+            };
+        }
+    }
+
+    fn update_metadata(&mut self) -> crate::error::Result<()> {
+        self.set_metadata(true)
+    }
+
+    fn set_metadata(&mut self, store: bool) -> crate::error::Result<()> {
+        let now = time::get_epoch_time();
 
-        // Update directory
         let mut directory = self.parent.lock();
         let mut metadata = directory.get_metadata_ptr(self as *const _ as *const _)?;
-        metadata.set_size(new_length);
+        metadata.set_size(self.file.get_length());
+        metadata.set_access_time(now);
+        metadata.set_modification_time(now);
+        if store {
+            directory.update_metadata(self, metadata)?;
+        } else {
+            directory.cache_metadata(self, metadata)?;
+        }
+
+        self.metadata_modified = !store;
+        Ok(())
+    }
+
+    fn store_metadata(&mut self) -> crate::error::Result<()> {
+        if !self.metadata_modified {
+            return Ok(());
+        }
+
+        let mut directory = self.parent.lock();
+        let metadata = directory.get_metadata_ptr(self as *const _ as *const _)?;
         directory.update_metadata(self, metadata)?;
+        drop(directory);
 
+        self.metadata_modified = false;
         Ok(())
     }
 }
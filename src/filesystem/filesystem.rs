@@ -2,38 +2,75 @@ use super::{Directory, DirectoryOwner, DirectoryReference, Parent};
 use crate::{device::DeviceReference, error, map::*};
 use alloc::{boxed::Box, string::String};
 
-pub type DetectFilesystemFunction = fn(
-    drive: DeviceReference,
-    start: usize,
-    size: usize,
-) -> error::Result<Option<FilesystemStarter>>;
+pub type DetectFilesystemFunction =
+    fn(drive: DeviceReference, size: usize) -> error::Result<Option<FilesystemStarter>>;
 
 pub struct FilesystemStarter {
     volume_name: String,
+    filesystem_type: &'static str,
     root_directory: Box<dyn Directory>,
 }
 
 pub struct Filesystem {
     number: isize,
-    _volume_name: String,
+    volume_name: String,
+    filesystem_type: &'static str,
+    device_path: Option<String>,
     root_directory: DirectoryReference,
 }
 
+pub struct FilesystemInfo {
+    pub number: isize,
+    pub volume_name: String,
+    pub filesystem_type: &'static str,
+    pub device_path: Option<String>,
+    pub mount_path: Option<String>,
+}
+
+// Paths are empty for virtual and unmounted filesystems, space is in bytes
+#[repr(C)]
+pub struct FilesystemStat {
+    number: isize,
+    volume_name: [u8; 256],
+    filesystem_type: [u8; 16],
+    device_path: [u8; 256],
+    mount_path: [u8; 256],
+    total_space: usize,
+    free_space: usize,
+}
+
 impl Filesystem {
-    pub fn new(filesystem_starter: FilesystemStarter) -> error::Result<Self> {
+    pub fn new(
+        filesystem_starter: FilesystemStarter,
+        device_path: Option<String>,
+    ) -> error::Result<Self> {
         Ok(Filesystem {
             number: INVALID_ID,
             root_directory: DirectoryReference::new(DirectoryOwner::new(
                 filesystem_starter.root_directory,
                 Parent::Root(INVALID_ID),
             )?),
-            _volume_name: filesystem_starter.volume_name,
+            volume_name: filesystem_starter.volume_name,
+            filesystem_type: filesystem_starter.filesystem_type,
+            device_path,
         })
     }
 
     pub fn root_directory(&self) -> &DirectoryReference {
         &self.root_directory
     }
+
+    pub fn volume_name(&self) -> &str {
+        &self.volume_name
+    }
+
+    pub fn filesystem_type(&self) -> &'static str {
+        self.filesystem_type
+    }
+
+    pub fn device_path(&self) -> Option<&str> {
+        self.device_path.as_deref()
+    }
 }
 
 impl Mappable for Filesystem {
@@ -48,10 +85,47 @@ impl Mappable for Filesystem {
 }
 
 impl FilesystemStarter {
-    pub fn new(root_directory: Box<dyn Directory>, volume_name: String) -> Self {
+    pub fn new(
+        root_directory: Box<dyn Directory>,
+        volume_name: String,
+        filesystem_type: &'static str,
+    ) -> Self {
         FilesystemStarter {
             root_directory: root_directory,
             volume_name: volume_name,
+            filesystem_type: filesystem_type,
         }
     }
 }
+
+impl FilesystemStat {
+    pub fn new(info: &FilesystemInfo, total_space: usize, free_space: usize) -> Self {
+        let mut stat = FilesystemStat {
+            number: info.number,
+            volume_name: [0; 256],
+            filesystem_type: [0; 16],
+            device_path: [0; 256],
+            mount_path: [0; 256],
+            total_space,
+            free_space,
+        };
+
+        copy_string(&mut stat.volume_name, &info.volume_name);
+        copy_string(&mut stat.filesystem_type, info.filesystem_type);
+        copy_string(
+            &mut stat.device_path,
+            info.device_path.as_deref().unwrap_or(""),
+        );
+        copy_string(
+            &mut stat.mount_path,
+            info.mount_path.as_deref().unwrap_or(""),
+        );
+        stat
+    }
+}
+
+// Copies as much of the string as fits while leaving a null terminator
+fn copy_string(destination: &mut [u8], string: &str) {
+    let length = core::cmp::min(string.len(), destination.len() - 1);
+    destination[..length].copy_from_slice(&string.as_bytes()[..length]);
+}
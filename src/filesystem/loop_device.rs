@@ -0,0 +1,84 @@
+use super::{partition::SECTOR_SIZE, FileDescriptor};
+use crate::{device::Device, error};
+
+// A drive backed by an image file, addresses are sectors like other drives
+pub struct LoopDevice {
+    file: FileDescriptor,
+    size: usize,
+    read_only: bool,
+}
+
+impl LoopDevice {
+    pub fn new(file: FileDescriptor, read_only: bool) -> error::Result<Self> {
+        let size = file.get_metadata()?.size();
+        Ok(LoopDevice {
+            file,
+            size,
+            read_only,
+        })
+    }
+
+    fn verify_bounds(&self, lba: usize, length: usize) -> error::Result<usize> {
+        match lba
+            .checked_mul(SECTOR_SIZE)
+            .and_then(|offset| offset.checked_add(length).map(|end| (offset, end)))
+        {
+            Some((offset, end)) => {
+                if end > self.size {
+                    Err(error::Status::OutOfRange)
+                } else {
+                    Ok(offset)
+                }
+            }
+            None => Err(error::Status::OutOfRange),
+        }
+    }
+}
+
+impl Device for LoopDevice {
+    fn read(&self, lba: usize, buffer: &mut [u8]) -> error::Result<()> {
+        let offset = self.verify_bounds(lba, buffer.len())?;
+        if self.file.read_at(offset, buffer)? != buffer.len() as isize {
+            return Err(error::Status::IOError);
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, lba: usize, buffer: &[u8]) -> error::Result<()> {
+        if self.read_only {
+            return Err(error::Status::ReadOnly);
+        }
+
+        let offset = self.verify_bounds(lba, buffer.len())?;
+        if self.file.write_at(offset, buffer)? != buffer.len() as isize {
+            return Err(error::Status::IOError);
+        }
+
+        Ok(())
+    }
+
+    fn read_register(&mut self, _: usize) -> error::Result<usize> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn write_register(&mut self, _: usize, _: usize) -> error::Result<()> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn ioctrl(&mut self, code: usize, _: usize) -> error::Result<usize> {
+        match code {
+            0 => Ok(self.size),
+            _ => Err(error::Status::InvalidIOCtrl),
+        }
+    }
+
+    fn sector_size(&self) -> Option<usize> {
+        Some(SECTOR_SIZE)
+    }
+
+    // The image file's blocks are already cached on the device holding it
+    fn cacheable(&self) -> bool {
+        false
+    }
+}
@@ -2,11 +2,55 @@
 pub struct Metadata {
     size: usize,
     is_directory: bool,
+    attributes: usize,
+    creation_time: isize,
+    access_time: isize,
+    modification_time: isize,
 }
 
+#[repr(C)]
+pub struct Stat {
+    class: usize,
+    size: usize,
+    attributes: usize,
+    creation_time: isize,
+    access_time: isize,
+    modification_time: isize,
+}
+
+pub const ATTRIBUTE_READ_ONLY: usize = 0x01;
+pub const ATTRIBUTE_HIDDEN: usize = 0x02;
+pub const ATTRIBUTE_SYSTEM: usize = 0x04;
+pub const ATTRIBUTE_ARCHIVE: usize = 0x20;
+// Marks a named pipe, the reserved FAT device bit is used so it is kept on disk
+pub const ATTRIBUTE_FIFO: usize = 0x40;
+
+const DIRECTORY: usize = 0;
+const FILE: usize = 1;
+
 impl Metadata {
-    pub fn new(size: usize, is_directory: bool) -> Self {
-        Metadata { size, is_directory }
+    pub fn new(
+        size: usize,
+        is_directory: bool,
+        attributes: usize,
+        creation_time: isize,
+        access_time: isize,
+        modification_time: isize,
+    ) -> Self {
+        Metadata {
+            size,
+            is_directory,
+            attributes,
+            creation_time,
+            access_time,
+            modification_time,
+        }
+    }
+
+    // Used for newly created entries
+    pub fn new_now(size: usize, is_directory: bool) -> Self {
+        let now = crate::time::get_epoch_time();
+        Metadata::new(size, is_directory, 0, now, now, now)
     }
 
     pub fn size(&self) -> usize {
@@ -20,4 +64,49 @@ impl Metadata {
     pub fn is_directory(&self) -> bool {
         self.is_directory
     }
+
+    pub fn attributes(&self) -> usize {
+        self.attributes
+    }
+
+    pub fn set_attributes(&mut self, new_attributes: usize) {
+        self.attributes = new_attributes
+    }
+
+    pub fn creation_time(&self) -> isize {
+        self.creation_time
+    }
+
+    pub fn access_time(&self) -> isize {
+        self.access_time
+    }
+
+    pub fn set_access_time(&mut self, new_time: isize) {
+        self.access_time = new_time
+    }
+
+    pub fn modification_time(&self) -> isize {
+        self.modification_time
+    }
+
+    pub fn set_modification_time(&mut self, new_time: isize) {
+        self.modification_time = new_time
+    }
+}
+
+impl Stat {
+    pub fn new(metadata: &Metadata) -> Self {
+        Stat {
+            class: if metadata.is_directory() {
+                DIRECTORY
+            } else {
+                FILE
+            },
+            size: metadata.size(),
+            attributes: metadata.attributes(),
+            creation_time: metadata.creation_time(),
+            access_time: metadata.access_time(),
+            modification_time: metadata.modification_time(),
+        }
+    }
 }
@@ -1,26 +1,36 @@
 use crate::{
     device::{self, DeviceReference},
     error,
-    locks::Mutex,
-    map::Map,
+    ipc::{Pipe, PipeReader, PipeWriter},
+    locks::{Mutex, MutexGuard},
+    map::{Map, Mappable},
     process,
 };
-use alloc::vec::Vec;
+use alloc::{borrow::ToOwned, boxed::Box, format, string::String, sync::Arc, vec::Vec};
 use core::ops::Deref;
 
+pub mod cache;
 pub mod drivers;
 
 mod directory;
+mod fifo;
 mod file;
 mod filesystem;
+mod loop_device;
 mod metadata;
+mod mount;
+mod partition;
 
 pub use directory::Descriptor as DirectoryDescriptor;
 pub use directory::Entry as DirectoryEntry;
-pub use directory::{Directory, Parent};
+pub use directory::{Directory, Parent, Watcher};
+pub use drivers::fat32::CheckReport;
 pub use file::Descriptor as FileDescriptor;
 pub use file::{File, SeekFrom};
-pub use metadata::Metadata;
+pub use metadata::{
+    Metadata, Stat, ATTRIBUTE_ARCHIVE, ATTRIBUTE_FIFO, ATTRIBUTE_HIDDEN, ATTRIBUTE_READ_ONLY,
+    ATTRIBUTE_SYSTEM,
+};
 
 type DirectoryReference = directory::DirectoryReference;
 type DirectoryOwner = directory::DirectoryOwner;
@@ -28,8 +38,15 @@ type FileReference = file::FileReference;
 type FileOwner = file::FileOwner;
 type DetectFilesystemFunction = filesystem::DetectFilesystemFunction;
 type Filesystem = filesystem::Filesystem;
+pub type FilesystemInfo = filesystem::FilesystemInfo;
+pub type FilesystemStat = filesystem::FilesystemStat;
 type FilesystemStarter = filesystem::FilesystemStarter;
 
+enum Location {
+    Absolute(Vec<String>),
+    Relative(DirectoryReference, Vec<String>),
+}
+
 const OPEN_READ: usize = 1;
 const OPEN_WRITE: usize = 2;
 const OPEN_READ_WRITE: usize = 3;
@@ -37,8 +54,13 @@ const OPEN_TRUNCATE: usize = 4;
 const OPEN_APPEND: usize = 8;
 const OPEN_CREATE: usize = 16;
 
+const RENAME_NO_REPLACE: usize = 1;
+
+const ROOT_FILESYSTEM_DIRECTORY: &str = "los";
+
 static FILESYSTEM_DRIVERS: Mutex<Vec<DetectFilesystemFunction>> = Mutex::new(Vec::new());
 static FILESYSTEMS: Mutex<Map<Filesystem>> = Mutex::new(Map::with_starting_index(1));
+static NEXT_LOOP_DEVICE: Mutex<usize> = Mutex::new(0);
 
 pub fn register_filesystem_driver(detect_function: DetectFilesystemFunction) {
     FILESYSTEM_DRIVERS.lock().push(detect_function);
@@ -58,10 +80,94 @@ pub fn register_drive(drive_path: &str) -> error::Result<()> {
         return Ok(());
     }
 
-    // TODO: Search for GUID Partition table
+    detect_drive(drive_path, drive_lock, size)
+}
+
+// Registers the partitions on a drive and detects their filesystems, or the drive's if it has none
+fn detect_drive(drive_path: &str, drive_lock: DeviceReference, size: usize) -> error::Result<()> {
+    // Search for a partition table
+    match partition::read_partition_table(&drive_lock, size)? {
+        Some(partitions) => {
+            for (i, partition) in partitions.into_iter().enumerate() {
+                // Register the partition as a child of the drive
+                let partition_path = format!("{}/p{}", drive_path, i + 1);
+                let size = partition.size();
+                device::register_device(
+                    &partition_path,
+                    DeviceReference::new(Box::new(partition::PartitionDevice::new(
+                        drive_lock.clone(),
+                        partition.start(),
+                        size,
+                    ))),
+                )?;
+
+                detect_filesystem(&partition_path, device::get_device(&partition_path)?, size)?;
+            }
+
+            Ok(())
+        }
+
+        // No partition table found, assuming whole disk is one partition
+        None => detect_filesystem(drive_path, drive_lock, size),
+    }
+}
+
+// Attaches an image file as "/loop<n>" and detects filesystems on it, returning n
+// Images that cannot be opened for writing are attached as read-only devices
+pub fn attach_loop_device(filepath: &str) -> error::Result<isize> {
+    let (file, read_only) = match open(filepath, OPEN_READ_WRITE, None) {
+        Ok(file) => (file, false),
+        Err(_) => (open(filepath, OPEN_READ, None)?, true),
+    };
+    let device = DeviceReference::new(Box::new(loop_device::LoopDevice::new(file, read_only)?));
+
+    let number = {
+        let mut next_loop_device = NEXT_LOOP_DEVICE.lock();
+        let number = *next_loop_device;
+        *next_loop_device += 1;
+        number
+    };
 
-    // No GPT found, assuming whole disk is one partition
-    detect_filesystem(drive_lock, 0, size)
+    let drive_path = format!("/loop{}", number);
+    device::register_device(&drive_path, device)?;
+
+    match register_drive(&drive_path) {
+        Ok(()) => Ok(number as isize),
+        Err(status) => {
+            device::remove_device(&drive_path);
+            Err(status)
+        }
+    }
+}
+
+// Removes "/loop<n>" and the filesystems on it, which must not be mounted or open
+pub fn detach_loop_device(number: usize) -> error::Result<()> {
+    let drive_path = format!("/loop{}", number);
+    let drive = device::get_device(&drive_path)?;
+
+    let registered = get_drive_filesystems(&drive_path);
+    let root_directories = lock_unused_filesystems(&registered)?;
+
+    let mut filesystems = FILESYSTEMS.lock();
+    for (fs_number, _) in &registered {
+        filesystems.remove(*fs_number);
+        fifo::remove_filesystem(*fs_number);
+    }
+    drop(filesystems);
+    drop(root_directories);
+
+    cache::flush(&drive)?;
+    for partition in device::get_children(&drive_path)? {
+        device::remove_device(&format!("{}/{}", drive_path, partition));
+    }
+    device::remove_device(&drive_path);
+    Ok(())
+}
+
+pub fn register_virtual_filesystem(filesystem_starter: FilesystemStarter) -> error::Result<isize> {
+    Ok(FILESYSTEMS
+        .lock()
+        .insert(Filesystem::new(filesystem_starter, None)?))
 }
 
 pub fn open(
@@ -74,16 +180,10 @@ pub fn open(
     }
 
     // Parse filepath
-    let (fs_number, path) = parse_filepath(filepath, true)?;
-    if path.len() == 0 {
-        return Err(error::Status::InvalidArgument);
-    }
-
-    // Open filesystem
-    let root_directory = get_root_directory(fs_number, starting_root)?;
+    let location = parse_filepath(filepath, true, starting_root)?;
 
     // Iterate path
-    let (current_directory, filename) = get_directory(path, root_directory, true)?;
+    let (current_directory, filename) = get_directory(location, true)?;
     let filename = match filename {
         Some(filename) => filename,
         None => return Err(error::Status::InvalidArgument),
@@ -91,13 +191,13 @@ pub fn open(
 
     // Open file
     let mut directory = current_directory.lock();
-    let file = match directory.open_file(filename, &current_directory) {
+    let file = match directory.open_file(&filename, &current_directory) {
         Ok(file) => file,
         Err(status) => match status {
             error::Status::NoEntry => {
                 if flags & OPEN_CREATE != 0 {
-                    directory.create_file(filename)?;
-                    directory.open_file(filename, &current_directory)?
+                    directory.create_file(&filename)?;
+                    directory.open_file(&filename, &current_directory)?
                 } else {
                     return Err(status);
                 }
@@ -131,26 +231,20 @@ pub fn open_directory(
     starting_root: Option<&DirectoryDescriptor>,
 ) -> error::Result<DirectoryDescriptor> {
     // Parse filepath
-    let (fs_number, path) = parse_filepath(path, false)?;
-
-    // Open filesystem
-    let root_directory = get_root_directory(fs_number, starting_root)?;
+    let location = parse_filepath(path, false, starting_root)?;
 
     // Iterate path
-    let (directory, _) = get_directory(path, root_directory, false)?;
+    let (directory, _) = get_directory(location, false)?;
 
     Ok(DirectoryDescriptor::new(directory))
 }
 
 pub fn read(filepath: &str) -> error::Result<Vec<u8>> {
     // Parse filepath
-    let (fs_number, path) = parse_filepath(filepath, true)?;
-
-    // Open filesystem
-    let root_directory = get_root_directory(fs_number, None)?;
+    let location = parse_filepath(filepath, true, None)?;
 
     // Iterate path
-    let (current_directory, filename) = get_directory(path, root_directory, true)?;
+    let (current_directory, filename) = get_directory(location, true)?;
     let filename = match filename {
         Some(filename) => filename,
         None => return Err(error::Status::InvalidArgument),
@@ -159,8 +253,8 @@ pub fn read(filepath: &str) -> error::Result<Vec<u8>> {
     // Get metadata and file
     let (metadata, file) = {
         let mut dir = current_directory.lock();
-        let metadata = dir.get_metadata(filename)?;
-        let file = dir.open_file(filename, &current_directory)?;
+        let metadata = dir.get_metadata(&filename)?;
+        let file = dir.open_file(&filename, &current_directory)?;
         (metadata, file)
     };
 
@@ -184,32 +278,63 @@ pub fn read(filepath: &str) -> error::Result<Vec<u8>> {
 
 pub fn remove(path: &str) -> error::Result<()> {
     // Parse filepath
-    let (fs_number, path) = parse_filepath(path, false)?;
-
-    // Open filesystem
-    let root_directory = get_root_directory(fs_number, None)?;
+    let location = parse_filepath(path, false, None)?;
 
     // Iterate path
-    let (parent_directory_lock, filename) = get_directory(path, root_directory, true)?;
+    let (parent_directory_lock, filename) = get_directory(location, true)?;
     let filename = match filename {
         Some(str) => str,
         None => return Err(error::Status::InvalidArgument),
     };
 
     // Remove directory
+    let path = construct_filesystem_path(&parent_directory_lock, &filename);
+    parent_directory_lock.lock().remove(&filename)?;
+    fifo::unregister(&path);
+    Ok(())
+}
+
+pub fn create_fifo(path: &str) -> error::Result<()> {
+    // Parse filepath
+    let location = parse_filepath(path, true, None)?;
+
+    // Iterate path
+    let (parent_directory_lock, filename) = get_directory(location, true)?;
+    let filename = match filename {
+        Some(str) => str,
+        None => return Err(error::Status::InvalidArgument),
+    };
+
+    // Create the file backing the pipe
     let mut parent_directory = parent_directory_lock.lock();
-    parent_directory.remove(filename)
+    parent_directory.create_fifo(&filename)
+}
+
+pub fn open_fifo_reader(path: &str) -> error::Result<PipeReader> {
+    let pipe = get_fifo(open(path, OPEN_READ, None)?)?;
+    Ok(Pipe::open_reader(&pipe))
+}
+
+pub fn open_fifo_writer(path: &str) -> error::Result<PipeWriter> {
+    let pipe = get_fifo(open(path, OPEN_WRITE, None)?)?;
+    Ok(Pipe::open_writer(&pipe))
+}
+
+// The path is taken from the open file, so it has the names as stored on disk
+fn get_fifo(file: FileDescriptor) -> error::Result<Arc<Mutex<Pipe>>> {
+    if file.get_metadata()?.attributes() & ATTRIBUTE_FIFO == 0 {
+        return Err(error::Status::InvalidArgument);
+    }
+
+    Ok(fifo::get_pipe(file.construct_filesystem_path()))
 }
 
 pub fn create_directory(path: &str) -> error::Result<()> {
     // Parse filepath
-    let (fs_number, path) = parse_filepath(path, false)?;
-
-    // Open filesystem
-    let root_directory = get_root_directory(fs_number, None)?;
+    let location = parse_filepath(path, false, None)?;
 
     // Iterate path
-    let (parent_directory_lock, directory_name) = get_directory(path, root_directory, true)?;
+    let (parent_directory_lock, directory_name) = get_directory(location, true)?;
     let directory_name = match directory_name {
         Some(str) => str,
         None => return Err(error::Status::InvalidArgument),
@@ -217,15 +342,357 @@ pub fn create_directory(path: &str) -> error::Result<()> {
 
     // Create directory
     let mut parent_directory = parent_directory_lock.lock();
-    parent_directory.create_directory(directory_name)
+    parent_directory.create_directory(&directory_name)
+}
+
+pub fn get_metadata(path: &str) -> error::Result<Metadata> {
+    // Parse filepath
+    let location = parse_filepath(path, false, None)?;
+
+    // Mount points are the roots of their filesystems
+    if let Location::Absolute(path) = &location {
+        if mount::is_mount_point(path) {
+            return Ok(Metadata::new(0, true, 0, 0, 0, 0));
+        }
+    }
+
+    // Iterate path
+    let (parent_directory_lock, name) = get_directory(location, true)?;
+    match name {
+        Some(name) => parent_directory_lock.lock().get_metadata(&name),
+        None => Ok(Metadata::new(0, true, 0, 0, 0, 0)),
+    }
 }
 
-fn detect_filesystem(drive: DeviceReference, start: usize, size: usize) -> error::Result<()> {
+pub fn rename(old_path: &str, new_path: &str, flags: usize) -> error::Result<()> {
+    let replace = flags & RENAME_NO_REPLACE == 0;
+
+    // Iterate paths
+    let (old_parent, old_name) = get_directory(parse_filepath(old_path, false, None)?, true)?;
+    let old_name = match old_name {
+        Some(str) => str,
+        None => return Err(error::Status::InvalidArgument),
+    };
+
+    let (new_parent, new_name) = get_directory(parse_filepath(new_path, false, None)?, true)?;
+    let new_name = match new_name {
+        Some(str) => str,
+        None => return Err(error::Status::InvalidArgument),
+    };
+
+    // Open named pipes are tracked by path and follow the rename
+    let old_fifo_path = construct_filesystem_path(&old_parent, &old_name);
+    let new_fifo_path = construct_filesystem_path(&new_parent, &new_name);
+
+    // Rename within a directory
+    if old_parent.as_ptr() == new_parent.as_ptr() {
+        old_parent.lock().rename(&old_name, &new_name, replace)?;
+        fifo::rename(&old_fifo_path, &new_fifo_path);
+        return Ok(());
+    }
+
+    // Moves cannot cross filesystems, each parent is unlocked before the other is walked
+    let old_drive_number = old_parent.lock().get_drive_number();
+    let new_drive_number = new_parent.lock().get_drive_number();
+    if old_drive_number != new_drive_number {
+        return Err(error::Status::NotSupported);
+    }
+
+    // Verify a directory is not being moved into itself and find the lock order
+    let child = old_parent.lock().get_child_reference(&old_name)?;
+    let mut new_parent_is_descendant = false;
+    let mut current = Some(new_parent.clone());
+    while let Some(directory) = current {
+        if let Some(directory::Child::Directory(child)) = &child {
+            if directory.as_ptr() == child.as_ptr() {
+                return Err(error::Status::InvalidArgument);
+            }
+        }
+
+        if directory.as_ptr() == old_parent.as_ptr() {
+            new_parent_is_descendant = true;
+        }
+
+        current = directory.lock().get_parent();
+    }
+
+    // Children are locked before their parents
+    let mut file_lock = None;
+    let mut directory_lock = None;
+    match &child {
+        Some(directory::Child::File(file)) => file_lock = Some(file.lock()),
+        Some(directory::Child::Directory(directory)) => directory_lock = Some(directory.lock()),
+        None => {}
+    }
+
+    let (mut old_directory, mut new_directory) = if new_parent_is_descendant {
+        let new_directory = new_parent.lock();
+        (old_parent.lock(), new_directory)
+    } else {
+        let old_directory = old_parent.lock();
+        (old_directory, new_parent.lock())
+    };
+
+    old_directory.move_child(
+        &old_name,
+        child.is_some(),
+        &mut new_directory,
+        &new_name,
+        replace,
+    )?;
+
+    // Re-parent the open child
+    if let Some(mut file) = file_lock {
+        file.set_parent(new_parent.clone());
+    }
+
+    if let Some(mut directory) = directory_lock {
+        directory.set_parent(new_parent.clone());
+    }
+
+    fifo::rename(&old_fifo_path, &new_fifo_path);
+    Ok(())
+}
+
+pub fn mount(fs_number: isize, path: &str) -> error::Result<()> {
+    // Verify the filesystem exists
+    get_filesystem_root(fs_number)?;
+
+    // Parse the mount point
+    let path = match parse_filepath(path, false, None)? {
+        Location::Absolute(path) => path,
+        Location::Relative(_, _) => return Err(error::Status::InvalidArgument),
+    };
+
+    // Verify the mount point is a directory, unless mounting the root
+    if path.len() > 0 || mount::find(&path).is_some() {
+        get_directory(Location::Absolute(path.clone()), false)?;
+    }
+
+    mount::mount(fs_number, path)
+}
+
+// Flushes and removes the filesystem mounted at the path
+pub fn unmount(path: &str) -> error::Result<()> {
+    let path = match parse_filepath(path, false, None)? {
+        Location::Absolute(path) => path,
+        Location::Relative(_, _) => return Err(error::Status::InvalidArgument),
+    };
+
+    let fs_number = match mount::find(&path) {
+        Some((fs_number, length)) if length == path.len() => fs_number,
+        _ => return Err(error::Status::NotFound),
+    };
+
+    // Holding the root prevents anything new being opened on the filesystem
+    let root_directory_lock = get_filesystem_root(fs_number)?;
+    let root_directory = root_directory_lock.lock();
+    if root_directory.is_open() {
+        return Err(error::Status::Busy);
+    }
+
+    root_directory.flush()?;
+    mount::unmount(&path)?;
+    FILESYSTEMS.lock().remove(fs_number);
+    fifo::remove_filesystem(fs_number);
+    Ok(())
+}
+
+// Checks the FAT volume on an unmounted drive, filesystems on it are re-detected after repairs
+pub fn check_filesystem(drive_path: &str, repair: bool) -> error::Result<CheckReport> {
+    modify_unmounted_drive(drive_path, |drive| {
+        let report = drivers::fat32::check_fat_filesystem(drive, repair)?;
+        let modified = repair && report.problems() > 0;
+        Ok((report, modified))
+    })
+}
+
+// Writes an empty FAT32 volume to an unmounted drive and detects it
+pub fn format_filesystem(drive_path: &str, volume_name: &str) -> error::Result<()> {
+    modify_unmounted_drive(drive_path, |drive| {
+        drivers::fat32::format_fat_filesystem(drive, volume_name)?;
+        Ok(((), true))
+    })
+}
+
+// Runs an operation on a drive with no mounted or open filesystem, the operation returns
+// whether it modified the drive so the partitions and filesystems on it need to be detected again
+// A failed operation may have partially modified the drive, so it is detected again as well
+fn modify_unmounted_drive<T, F: FnOnce(DeviceReference) -> error::Result<(T, bool)>>(
+    drive_path: &str,
+    operation: F,
+) -> error::Result<T> {
+    let drive = device::get_device(drive_path)?;
+
+    let registered = get_drive_filesystems(drive_path);
+    let root_directories = lock_unused_filesystems(&registered)?;
+
+    let result = operation(drive.clone());
+    let modified = match &result {
+        Ok((_, modified)) => *modified,
+        Err(_) => true,
+    };
+
+    // The registered filesystems hold stale state after the drive is modified
+    if modified {
+        let mut filesystems = FILESYSTEMS.lock();
+        for (fs_number, _) in &registered {
+            filesystems.remove(*fs_number);
+            fifo::remove_filesystem(*fs_number);
+        }
+    }
+    drop(root_directories);
+
+    // The operation's error is returned before any from detecting the drive again
+    let redetect_result = if modified {
+        redetect_drive(drive_path, drive)
+    } else {
+        Ok(())
+    };
+
+    let (result, _) = result?;
+    redetect_result?;
+    Ok(result)
+}
+
+// Filesystems on the drive's partitions are included
+fn get_drive_filesystems(drive_path: &str) -> Vec<(isize, DirectoryReference)> {
+    let partition_prefix = format!("{}/", drive_path);
+    let mut registered: Vec<(isize, DirectoryReference)> = FILESYSTEMS
+        .lock()
+        .iter()
+        .filter(|filesystem| match filesystem.device_path() {
+            Some(device_path) => {
+                device_path == drive_path || device_path.starts_with(&partition_prefix)
+            }
+            None => false,
+        })
+        .map(|filesystem| (filesystem.id(), filesystem.root_directory().clone()))
+        .collect();
+    registered.sort_by_key(|(fs_number, _)| *fs_number);
+    registered
+}
+
+// Flushes the filesystems and holds their roots, which prevents anything being opened
+fn lock_unused_filesystems(
+    registered: &[(isize, DirectoryReference)],
+) -> error::Result<Vec<MutexGuard<DirectoryOwner>>> {
+    for (fs_number, _) in registered {
+        if mount::get_mount_path(*fs_number).is_some() {
+            return Err(error::Status::Busy);
+        }
+    }
+
+    let mut root_directories = Vec::new();
+    for (_, root_directory_lock) in registered {
+        let root_directory = root_directory_lock.lock();
+        if root_directory.is_open() {
+            return Err(error::Status::Busy);
+        }
+
+        root_directory.flush()?;
+        root_directories.push(root_directory);
+    }
+
+    Ok(root_directories)
+}
+
+fn redetect_drive(drive_path: &str, drive: DeviceReference) -> error::Result<()> {
+    cache::flush(&drive)?;
+
+    // Partitions are the only children of a drive, the partition table may have been overwritten
+    for partition in device::get_children(drive_path)? {
+        device::remove_device(&format!("{}/{}", drive_path, partition));
+    }
+
+    let size = drive.lock().ioctrl(0, 0)?;
+    if size == 0 {
+        return Ok(());
+    }
+
+    // Partitions are not searched for partition tables of their own
+    if drive.lock().translate(0, 0)?.is_some() {
+        detect_filesystem(drive_path, drive, size)
+    } else {
+        detect_drive(drive_path, drive, size)
+    }
+}
+
+pub fn sync() -> error::Result<()> {
+    let root_directories: Vec<DirectoryReference> = FILESYSTEMS
+        .lock()
+        .iter()
+        .map(|filesystem| filesystem.root_directory().clone())
+        .collect();
+
+    // Every filesystem and then the block cache is flushed, even after a failure
+    let mut result = Ok(());
+    for root_directory in root_directories {
+        let flush_result = root_directory.lock().flush();
+        result = result.and(flush_result);
+    }
+
+    result.and(cache::flush_all())
+}
+
+pub fn get_filesystems() -> Vec<FilesystemInfo> {
+    let mut filesystems: Vec<FilesystemInfo> = FILESYSTEMS
+        .lock()
+        .iter()
+        .map(|filesystem| FilesystemInfo {
+            number: filesystem.id(),
+            volume_name: filesystem.volume_name().to_owned(),
+            filesystem_type: filesystem.filesystem_type(),
+            device_path: filesystem.device_path().map(|path| path.to_owned()),
+            mount_path: None,
+        })
+        .collect();
+
+    for filesystem in &mut filesystems {
+        filesystem.mount_path = mount::get_mount_path(filesystem.number);
+    }
+
+    filesystems
+}
+
+// Returns the total and free bytes of a filesystem, this locks its root directory
+pub fn get_space(fs_number: isize) -> error::Result<(usize, usize)> {
+    get_filesystem_root(fs_number)?.lock().get_space()
+}
+
+pub fn mount_root_filesystem() -> error::Result<isize> {
+    let mut fs_numbers = FILESYSTEMS.lock().ids();
+    fs_numbers.sort();
+
+    // Mount the first filesystem containing the system directory
+    for fs_number in fs_numbers {
+        let root_directory = get_filesystem_root(fs_number)?;
+        let is_root = match root_directory
+            .lock()
+            .get_metadata(ROOT_FILESYSTEM_DIRECTORY)
+        {
+            Ok(metadata) => metadata.is_directory(),
+            Err(_) => false,
+        };
+
+        if is_root {
+            mount::mount(fs_number, Vec::new())?;
+            return Ok(fs_number);
+        }
+    }
+
+    Err(error::Status::NoFilesystem)
+}
+
+fn detect_filesystem(drive_path: &str, drive: DeviceReference, size: usize) -> error::Result<()> {
     let drivers = FILESYSTEM_DRIVERS.lock();
 
     for filesystem in drivers.deref() {
-        match filesystem(drive.clone(), start, size)? {
-            Some(filesystem_starter) => register_filesystem(Filesystem::new(filesystem_starter)?),
+        match filesystem(drive.clone(), size)? {
+            Some(filesystem_starter) => register_filesystem(Filesystem::new(
+                filesystem_starter,
+                Some(drive_path.to_owned()),
+            )?),
             None => {}
         }
     }
@@ -237,77 +704,142 @@ fn register_filesystem(filesystem: Filesystem) {
     FILESYSTEMS.lock().insert(filesystem);
 }
 
-fn parse_filepath(filepath: &str, file: bool) -> error::Result<(Option<isize>, Vec<&str>)> {
-    let filepath = if filepath.ends_with('/') {
-        if file {
-            return Err(error::Status::InvalidArgument);
-        }
-        &filepath[..filepath.len() - 1]
-    } else {
-        filepath
-    };
+fn parse_filepath(
+    filepath: &str,
+    file: bool,
+    starting_root: Option<&DirectoryDescriptor>,
+) -> error::Result<Location> {
+    if file && (filepath.ends_with('/') || filepath.ends_with('\\')) {
+        return Err(error::Status::InvalidArgument);
+    }
 
     let mut iter = filepath.split(|c| -> bool { c == '\\' || c == '/' });
 
     // Parse drive number
-    let drive_number = if filepath.starts_with(':') {
-        match iter.next() {
+    if filepath.starts_with(':') {
+        let fs_number = match iter.next() {
             Some(str) => match isize::from_str_radix(&str[1..], 10) {
                 Ok(value) => {
                     if value < 0 {
                         return Err(error::Status::InvalidArgument);
                     }
-                    Some(value)
+                    value
                 }
                 Err(_) => return Err(error::Status::InvalidArgument),
             },
             None => return Err(error::Status::InvalidArgument),
+        };
+
+        let mut path = Vec::new();
+        push_path_components(&mut path, iter);
+        return Ok(Location::Relative(get_filesystem_root(fs_number)?, path));
+    }
+
+    // Make the path absolute
+    let mut path = Vec::new();
+    if !filepath.starts_with(|c| -> bool { c == '\\' || c == '/' }) {
+        let working_directory = get_working_directory(starting_root)?;
+        let working_path = working_directory.lock().construct_path_name();
+
+        // Paths in unmounted filesystems can only be walked from the working directory
+        if working_path.starts_with(':') {
+            return Ok(Location::Relative(
+                working_directory,
+                iter.filter(|part| part.len() > 0)
+                    .map(|part| part.to_owned())
+                    .collect(),
+            ));
         }
-    } else {
-        None
-    };
 
-    // Get path
-    let path = iter.collect();
+        push_path_components(
+            &mut path,
+            working_path.split(|c| -> bool { c == '\\' || c == '/' }),
+        );
+    }
+
+    push_path_components(&mut path, iter);
 
-    Ok((drive_number, path))
+    Ok(Location::Absolute(path))
 }
 
-fn get_root_directory(
-    fs_number: Option<isize>,
+fn push_path_components<'a, I: Iterator<Item = &'a str>>(path: &mut Vec<String>, parts: I) {
+    for part in parts {
+        match part {
+            "" | "." => {}
+            ".." => {
+                path.pop();
+            }
+            _ => path.push(part.to_owned()),
+        }
+    }
+}
+
+fn construct_filesystem_path(directory: &DirectoryReference, name: &str) -> String {
+    let mut path = directory.lock().construct_filesystem_path();
+    path.push('/');
+    path.push_str(name);
+    path
+}
+
+fn get_filesystem_root(fs_number: isize) -> error::Result<DirectoryReference> {
+    let mut filesystems = FILESYSTEMS.lock();
+    match filesystems.get_mut(fs_number) {
+        Some(filesystem) => Ok(filesystem.root_directory().clone()),
+        None => Err(error::Status::NoFilesystem),
+    }
+}
+
+fn get_working_directory(
     provided_root: Option<&DirectoryDescriptor>,
 ) -> error::Result<DirectoryReference> {
-    match fs_number {
-        Some(fs_number) => {
-            let mut filesystems = FILESYSTEMS.lock();
-            match filesystems.get_mut(fs_number) {
-                Some(filesystem) => Ok(filesystem.root_directory().clone()),
-                None => Err(error::Status::NoFilesystem),
+    match provided_root {
+        Some(root_descriptor) => Ok(root_descriptor.get_directory()),
+        None => {
+            let process_lock = process::get_current_thread()
+                .process()
+                .unwrap()
+                .upgrade()
+                .unwrap();
+            let mut process = process_lock.lock();
+            match process.current_working_directory() {
+                Some(dir) => Ok(dir.get_directory()),
+                None => Err(error::Status::NotSupported),
             }
         }
-        None => match provided_root {
-            Some(root_descriptor) => Ok(root_descriptor.get_directory()),
-            None => {
-                let process_lock = process::get_current_thread()
-                    .process()
-                    .unwrap()
-                    .upgrade()
-                    .unwrap();
-                let mut process = process_lock.lock();
-                match process.current_working_directory() {
-                    Some(dir) => Ok(dir.get_directory()),
-                    None => Err(error::Status::NotSupported),
-                }
-            }
-        },
     }
 }
 
 fn get_directory(
-    path: Vec<&str>,
-    root_directory: DirectoryReference,
+    location: Location,
     filename: bool,
-) -> error::Result<(DirectoryReference, Option<&str>)> {
+) -> error::Result<(DirectoryReference, Option<String>)> {
+    let (root_directory, path) = match location {
+        Location::Relative(directory, path) => (directory, path),
+        Location::Absolute(mut path) => {
+            // Mount points cannot be used as filenames
+            if filename && path.len() > 0 && mount::is_mount_point(&path) {
+                return Err(error::Status::Busy);
+            }
+
+            // Locate the filesystem containing the path
+            let directory_length = if filename && path.len() > 0 {
+                path.len() - 1
+            } else {
+                path.len()
+            };
+
+            let (fs_number, mount_length) = match mount::find(&path[..directory_length]) {
+                Some(mount_point) => mount_point,
+                None => return Err(error::Status::NoFilesystem),
+            };
+
+            (
+                get_filesystem_root(fs_number)?,
+                path.split_off(mount_length),
+            )
+        }
+    };
+
     let mut iter = path.into_iter();
     let filename = if filename { iter.next_back() } else { None };
 
@@ -324,7 +856,7 @@ fn get_directory(
                 None => continue,
             }
         } else {
-            directory.open_directory(dir_name, &current_directory)?
+            directory.open_directory(&dir_name, &current_directory)?
         };
         drop(directory);
         current_directory = new_directory;
@@ -0,0 +1,86 @@
+use crate::{error, locks::Mutex};
+use alloc::{string::String, vec::Vec};
+
+struct MountPoint {
+    path: Vec<String>,
+    filesystem: isize,
+}
+
+static MOUNT_POINTS: Mutex<Vec<MountPoint>> = Mutex::new(Vec::new());
+
+pub fn mount(filesystem: isize, path: Vec<String>) -> error::Result<()> {
+    let mut mount_points = MOUNT_POINTS.lock();
+
+    for mount_point in mount_points.iter() {
+        if mount_point.path == path {
+            return Err(error::Status::Busy);
+        }
+
+        if mount_point.filesystem == filesystem {
+            return Err(error::Status::InUse);
+        }
+    }
+
+    mount_points.push(MountPoint { path, filesystem });
+    Ok(())
+}
+
+pub fn unmount(path: &[String]) -> error::Result<isize> {
+    let mut mount_points = MOUNT_POINTS.lock();
+
+    let mut index = None;
+    for (i, mount_point) in mount_points.iter().enumerate() {
+        if mount_point.path == path {
+            index = Some(i);
+        } else if mount_point.path.starts_with(path) {
+            // Another filesystem is mounted beneath this one
+            return Err(error::Status::Busy);
+        }
+    }
+
+    match index {
+        Some(index) => Ok(mount_points.remove(index).filesystem),
+        None => Err(error::Status::NotFound),
+    }
+}
+
+// Returns the filesystem mounted closest to the path and the number of path components it covers
+pub fn find(path: &[String]) -> Option<(isize, usize)> {
+    let mut ret: Option<(isize, usize)> = None;
+    for mount_point in MOUNT_POINTS.lock().iter() {
+        if !path.starts_with(&mount_point.path) {
+            continue;
+        }
+
+        match ret {
+            Some((_, length)) if length >= mount_point.path.len() => {}
+            _ => ret = Some((mount_point.filesystem, mount_point.path.len())),
+        }
+    }
+
+    ret
+}
+
+pub fn is_mount_point(path: &[String]) -> bool {
+    for mount_point in MOUNT_POINTS.lock().iter() {
+        if mount_point.path == path {
+            return true;
+        }
+    }
+
+    false
+}
+
+pub fn get_mount_path(filesystem: isize) -> Option<String> {
+    for mount_point in MOUNT_POINTS.lock().iter() {
+        if mount_point.filesystem != filesystem {
+            continue;
+        }
+
+        let mut path = String::from("/");
+        path.push_str(&mount_point.path.join("/"));
+        return Some(path);
+    }
+
+    None
+}
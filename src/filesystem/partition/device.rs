@@ -0,0 +1,73 @@
+use super::SECTOR_SIZE;
+use crate::{
+    device::{Device, DeviceReference},
+    error,
+};
+
+pub struct PartitionDevice {
+    drive: DeviceReference,
+    start: usize,
+    size: usize,
+}
+
+impl PartitionDevice {
+    pub fn new(drive: DeviceReference, start: usize, size: usize) -> Self {
+        PartitionDevice { drive, start, size }
+    }
+
+    fn verify_bounds(&self, lba: usize, length: usize) -> error::Result<usize> {
+        match lba
+            .checked_mul(SECTOR_SIZE)
+            .and_then(|offset| offset.checked_add(length))
+        {
+            Some(end) => {
+                if end > self.size {
+                    Err(error::Status::OutOfRange)
+                } else {
+                    Ok(self.start + lba)
+                }
+            }
+            None => Err(error::Status::OutOfRange),
+        }
+    }
+}
+
+impl Device for PartitionDevice {
+    fn read(&self, lba: usize, buffer: &mut [u8]) -> error::Result<()> {
+        let lba = self.verify_bounds(lba, buffer.len())?;
+        self.drive.lock().read(lba, buffer)
+    }
+
+    fn write(&mut self, lba: usize, buffer: &[u8]) -> error::Result<()> {
+        let lba = self.verify_bounds(lba, buffer.len())?;
+        self.drive.lock().write(lba, buffer)
+    }
+
+    fn read_register(&mut self, _: usize) -> error::Result<usize> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn write_register(&mut self, _: usize, _: usize) -> error::Result<()> {
+        Err(error::Status::NotSupported)
+    }
+
+    fn ioctrl(&mut self, code: usize, _: usize) -> error::Result<usize> {
+        match code {
+            0 => Ok(self.size),
+            _ => Err(error::Status::InvalidIOCtrl),
+        }
+    }
+
+    fn sector_size(&self) -> Option<usize> {
+        Some(SECTOR_SIZE)
+    }
+
+    fn translate(
+        &self,
+        lba: usize,
+        length: usize,
+    ) -> error::Result<Option<(DeviceReference, usize)>> {
+        let lba = self.verify_bounds(lba, length)?;
+        Ok(Some((self.drive.clone(), lba)))
+    }
+}
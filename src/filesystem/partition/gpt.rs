@@ -0,0 +1,169 @@
+use super::{read_sectors, read_u32, read_u64, Partition, SECTOR_SIZE};
+use crate::{device::DeviceReference, error};
+use alloc::vec::Vec;
+
+struct Header {
+    current_lba: u64,
+    backup_lba: u64,
+    first_usable_lba: u64,
+    last_usable_lba: u64,
+    partition_entry_lba: u64,
+    num_partition_entries: u32,
+    partition_entry_size: u32,
+    partition_entry_array_crc32: u32,
+}
+
+const HEADER_SIGNATURE: &[u8; 8] = b"EFI PART";
+const MINIMUM_HEADER_SIZE: usize = 92;
+const MINIMUM_PARTITION_ENTRY_SIZE: u32 = 128;
+const MAXIMUM_PARTITION_ENTRIES_SIZE: usize = 1024 * 1024;
+
+const PROTECTIVE_MBR_TYPE: u8 = 0xEE;
+
+pub fn is_protective_mbr(mbr: &[u8]) -> bool {
+    for i in 0..4 {
+        if mbr[0x1BE + i * 16 + 4] == PROTECTIVE_MBR_TYPE {
+            return true;
+        }
+    }
+
+    false
+}
+
+pub fn read_partitions(
+    drive: &DeviceReference,
+    size: usize,
+) -> error::Result<Option<Vec<Partition>>> {
+    let last_lba = match (size / SECTOR_SIZE).checked_sub(1) {
+        Some(last_lba) => last_lba as u64,
+        None => return Ok(None),
+    };
+
+    // Try the primary header
+    let backup_lba = match read_header(drive, 1, last_lba)? {
+        Some(header) => match read_entries(drive, &header)? {
+            Some(partitions) => return Ok(Some(partitions)),
+            None => header.backup_lba,
+        },
+        None => last_lba,
+    };
+
+    // Fall back on the backup header
+    match read_header(drive, backup_lba, last_lba)? {
+        Some(header) => read_entries(drive, &header),
+        None => Ok(None),
+    }
+}
+
+fn read_header(drive: &DeviceReference, lba: u64, last_lba: u64) -> error::Result<Option<Header>> {
+    if lba < 1 || lba > last_lba {
+        return Ok(None);
+    }
+
+    let mut buffer = read_sectors(drive, lba as usize, 1)?;
+
+    // Verify signature
+    if &buffer[0x00..0x08] != HEADER_SIGNATURE {
+        return Ok(None);
+    }
+
+    // Verify header checksum
+    let header_size = read_u32(&buffer, 0x0C) as usize;
+    if header_size < MINIMUM_HEADER_SIZE || header_size > SECTOR_SIZE {
+        return Ok(None);
+    }
+
+    let header_crc32 = read_u32(&buffer, 0x10);
+    for i in 0x10..0x14 {
+        buffer[i] = 0;
+    }
+
+    if crc32(&buffer[..header_size]) != header_crc32 {
+        return Ok(None);
+    }
+
+    let header = Header {
+        current_lba: read_u64(&buffer, 0x18),
+        backup_lba: read_u64(&buffer, 0x20),
+        first_usable_lba: read_u64(&buffer, 0x28),
+        last_usable_lba: read_u64(&buffer, 0x30),
+        partition_entry_lba: read_u64(&buffer, 0x48),
+        num_partition_entries: read_u32(&buffer, 0x50),
+        partition_entry_size: read_u32(&buffer, 0x54),
+        partition_entry_array_crc32: read_u32(&buffer, 0x58),
+    };
+
+    // Verify header contents
+    if header.current_lba != lba
+        || header.first_usable_lba > header.last_usable_lba
+        || header.last_usable_lba > last_lba
+        || header.partition_entry_lba < 1
+        || header.partition_entry_lba > last_lba
+        || header.partition_entry_size < MINIMUM_PARTITION_ENTRY_SIZE
+        || header.partition_entry_size % MINIMUM_PARTITION_ENTRY_SIZE != 0
+        || (header.num_partition_entries as usize) * (header.partition_entry_size as usize)
+            > MAXIMUM_PARTITION_ENTRIES_SIZE
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(header))
+}
+
+fn read_entries(drive: &DeviceReference, header: &Header) -> error::Result<Option<Vec<Partition>>> {
+    // Read the partition entry array
+    let entry_size = header.partition_entry_size as usize;
+    let array_size = header.num_partition_entries as usize * entry_size;
+    let num_sectors = (array_size + SECTOR_SIZE - 1) / SECTOR_SIZE;
+    if num_sectors == 0 {
+        return Ok(Some(Vec::new()));
+    }
+
+    let buffer = read_sectors(drive, header.partition_entry_lba as usize, num_sectors)?;
+
+    // Verify array checksum
+    if crc32(&buffer[..array_size]) != header.partition_entry_array_crc32 {
+        return Ok(None);
+    }
+
+    // Parse entries
+    let mut partitions = Vec::new();
+    for entry in buffer[..array_size].chunks_exact(entry_size) {
+        // Skip unused entries
+        if entry[0x00..0x10].iter().all(|byte| *byte == 0) {
+            continue;
+        }
+
+        let first_lba = read_u64(entry, 0x20);
+        let last_lba = read_u64(entry, 0x28);
+        if first_lba < header.first_usable_lba
+            || last_lba > header.last_usable_lba
+            || first_lba > last_lba
+        {
+            continue;
+        }
+
+        partitions.push(Partition::new(
+            first_lba as usize,
+            (last_lba - first_lba + 1) as usize * SECTOR_SIZE,
+        ));
+    }
+
+    Ok(Some(partitions))
+}
+
+fn crc32(buffer: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for byte in buffer {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
@@ -0,0 +1,147 @@
+use super::{read_sectors, read_u32, Partition, SECTOR_SIZE};
+use crate::{device::DeviceReference, error};
+use alloc::vec::Vec;
+
+struct Entry {
+    boot_indicator: u8,
+    partition_type: u8,
+    start: usize,
+    num_sectors: usize,
+}
+
+const PARTITION_TABLE_OFFSET: usize = 0x1BE;
+const PARTITION_ENTRY_SIZE: usize = 16;
+const NUM_PARTITION_ENTRIES: usize = 4;
+
+const PARTITION_TYPE_EMPTY: u8 = 0x00;
+const PARTITION_TYPE_EXTENDED_CHS: u8 = 0x05;
+const PARTITION_TYPE_EXTENDED_LBA: u8 = 0x0F;
+const PARTITION_TYPE_EXTENDED_LINUX: u8 = 0x85;
+
+const MAXIMUM_LOGICAL_PARTITIONS: usize = 128;
+
+pub fn read_partitions(
+    drive: &DeviceReference,
+    mbr: &[u8],
+    size: usize,
+) -> error::Result<Option<Vec<Partition>>> {
+    let num_sectors = size / SECTOR_SIZE;
+
+    // Verify the partition table
+    let entries = parse_entries(mbr);
+    let mut found = false;
+    for entry in &entries {
+        if entry.boot_indicator != 0x00 && entry.boot_indicator != 0x80 {
+            return Ok(None);
+        }
+
+        if entry.partition_type == PARTITION_TYPE_EMPTY {
+            continue;
+        }
+
+        if entry.start == 0 || entry.start + entry.num_sectors > num_sectors {
+            return Ok(None);
+        }
+
+        found = true;
+    }
+
+    if !found {
+        return Ok(None);
+    }
+
+    // Collect primary partitions and follow extended partitions
+    let mut partitions = Vec::new();
+    let mut extended_partitions = Vec::new();
+    for entry in entries {
+        if entry.partition_type == PARTITION_TYPE_EMPTY || entry.num_sectors == 0 {
+            continue;
+        }
+
+        if is_extended(entry.partition_type) {
+            extended_partitions.push(entry);
+        } else {
+            partitions.push(entry.to_partition(0));
+        }
+    }
+
+    for extended_partition in extended_partitions {
+        read_logical_partitions(drive, &extended_partition, num_sectors, &mut partitions)?;
+    }
+
+    Ok(Some(partitions))
+}
+
+fn read_logical_partitions(
+    drive: &DeviceReference,
+    extended_partition: &Entry,
+    num_sectors: usize,
+    partitions: &mut Vec<Partition>,
+) -> error::Result<()> {
+    let extended_start = extended_partition.start;
+    let extended_end = extended_start + extended_partition.num_sectors;
+
+    let mut ebr_lba = extended_start;
+    for _ in 0..MAXIMUM_LOGICAL_PARTITIONS {
+        let ebr = read_sectors(drive, ebr_lba, 1)?;
+        if ebr[0x1FE] != 0x55 || ebr[0x1FF] != 0xAA {
+            break;
+        }
+
+        let entries = parse_entries(&ebr);
+
+        // The first entry describes a logical partition relative to this EBR
+        let logical = &entries[0];
+        if logical.partition_type != PARTITION_TYPE_EMPTY && logical.num_sectors != 0 {
+            let start = ebr_lba + logical.start;
+            if start > ebr_lba
+                && start + logical.num_sectors <= extended_end
+                && start + logical.num_sectors <= num_sectors
+            {
+                partitions.push(logical.to_partition(ebr_lba));
+            }
+        }
+
+        // The second entry links to the next EBR relative to the extended partition
+        let next = &entries[1];
+        if !is_extended(next.partition_type) || next.start == 0 {
+            break;
+        }
+
+        let next_lba = extended_start + next.start;
+        if next_lba <= ebr_lba || next_lba >= extended_end {
+            break;
+        }
+
+        ebr_lba = next_lba;
+    }
+
+    Ok(())
+}
+
+fn parse_entries(sector: &[u8]) -> Vec<Entry> {
+    let mut entries = Vec::with_capacity(NUM_PARTITION_ENTRIES);
+    for i in 0..NUM_PARTITION_ENTRIES {
+        let offset = PARTITION_TABLE_OFFSET + i * PARTITION_ENTRY_SIZE;
+        entries.push(Entry {
+            boot_indicator: sector[offset],
+            partition_type: sector[offset + 4],
+            start: read_u32(sector, offset + 8) as usize,
+            num_sectors: read_u32(sector, offset + 12) as usize,
+        });
+    }
+
+    entries
+}
+
+fn is_extended(partition_type: u8) -> bool {
+    partition_type == PARTITION_TYPE_EXTENDED_CHS
+        || partition_type == PARTITION_TYPE_EXTENDED_LBA
+        || partition_type == PARTITION_TYPE_EXTENDED_LINUX
+}
+
+impl Entry {
+    fn to_partition(&self, base: usize) -> Partition {
+        Partition::new(base + self.start, self.num_sectors * SECTOR_SIZE)
+    }
+}
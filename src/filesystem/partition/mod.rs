@@ -0,0 +1,72 @@
+use crate::{device::DeviceReference, error};
+use alloc::{vec, vec::Vec};
+
+mod device;
+mod gpt;
+mod mbr;
+
+pub use device::PartitionDevice;
+
+pub const SECTOR_SIZE: usize = 512;
+
+pub struct Partition {
+    start: usize,
+    size: usize,
+}
+
+impl Partition {
+    pub fn new(start: usize, size: usize) -> Self {
+        Partition { start, size }
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+pub fn read_partition_table(
+    drive: &DeviceReference,
+    size: usize,
+) -> error::Result<Option<Vec<Partition>>> {
+    // Tables are parsed in 512 byte sectors, so drives with other sector sizes are one volume
+    if drive.lock().sector_size() != Some(SECTOR_SIZE) {
+        return Ok(None);
+    }
+
+    // Read the master boot record
+    let mut mbr = [0u8; SECTOR_SIZE];
+    drive.lock().read(0, &mut mbr)?;
+
+    if mbr[0x1FE] != 0x55 || mbr[0x1FF] != 0xAA {
+        return Ok(None);
+    }
+
+    // Check for a protective MBR
+    if gpt::is_protective_mbr(&mbr) {
+        return gpt::read_partitions(drive, size);
+    }
+
+    // Fall back on a legacy partition table
+    mbr::read_partitions(drive, &mbr, size)
+}
+
+fn read_sectors(drive: &DeviceReference, lba: usize, count: usize) -> error::Result<Vec<u8>> {
+    let mut buffer = vec![0; count * SECTOR_SIZE];
+    drive.lock().read(lba, buffer.as_mut_slice())?;
+    Ok(buffer)
+}
+
+fn read_u32(buffer: &[u8], offset: usize) -> u32 {
+    (buffer[offset] as u32)
+        | ((buffer[offset + 1] as u32) << 8)
+        | ((buffer[offset + 2] as u32) << 16)
+        | ((buffer[offset + 3] as u32) << 24)
+}
+
+fn read_u64(buffer: &[u8], offset: usize) -> u64 {
+    (read_u32(buffer, offset) as u64) | ((read_u32(buffer, offset + 4) as u64) << 32)
+}
@@ -79,6 +79,7 @@ const SPURIOUS_INTERRUPT_VECTOR: u8 = 0xFF;
 
 static mut LOCAL_APIC: *mut u32 = null_mut();
 static mut IRQ_HANDLERS: [Option<HandlerWithContext>; 16] = [None; 16];
+static mut IRQ_COUNTS: [usize; 16] = [0; 16];
 
 extern "C" {
     fn spurious_irq_handler();
@@ -225,6 +226,8 @@ unsafe extern "C" fn common_irq_handler(irq: usize, registers: Registers, info:
     end_irq(irq as u8);
     end_interrupt();
 
+    IRQ_COUNTS[irq] += 1;
+
     match IRQ_HANDLERS[irq] {
         None => {}
         Some(handler) => (handler.handler)(handler.context),
@@ -276,6 +279,10 @@ pub fn install_irq_handler(irq: u8, handler: Handler, context: usize) -> bool {
     }
 }
 
+pub fn get_irq_counts() -> [usize; 16] {
+    unsafe { IRQ_COUNTS }
+}
+
 fn end_irq(irq: u8) {
     if irq > 15 {
         return;
@@ -3,11 +3,13 @@ use crate::locks::Mutex;
 use crate::process::{self, CurrentQueue, ThreadQueue};
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
+// The end counts are only changed with the pipe locked, but can be checked while waiting
 pub struct Pipe {
     buffer: VecDeque<u8>,
-    reader_count: usize,
-    writer_count: usize,
+    reader_count: Arc<AtomicUsize>,
+    writer_count: Arc<AtomicUsize>,
     queue: ThreadQueue,
 }
 
@@ -23,20 +25,71 @@ impl Pipe {
     pub fn new() -> (PipeReader, PipeWriter) {
         let pipe = Arc::new(Mutex::new(Pipe {
             buffer: VecDeque::new(),
-            reader_count: 1,
-            writer_count: 1,
+            reader_count: Arc::new(AtomicUsize::new(1)),
+            writer_count: Arc::new(AtomicUsize::new(1)),
             queue: ThreadQueue::new(),
         }));
 
         (PipeReader { pipe: pipe.clone() }, PipeWriter { pipe })
     }
+
+    // Named pipes start without ends, they are added as each side opens
+    pub fn new_named() -> Arc<Mutex<Pipe>> {
+        Arc::new(Mutex::new(Pipe {
+            buffer: VecDeque::new(),
+            reader_count: Arc::new(AtomicUsize::new(0)),
+            writer_count: Arc::new(AtomicUsize::new(0)),
+            queue: ThreadQueue::new(),
+        }))
+    }
+
+    // Blocks until the pipe has a writer
+    pub fn open_reader(pipe: &Arc<Mutex<Pipe>>) -> PipeReader {
+        pipe.lock().increment_read();
+        let reader = PipeReader { pipe: pipe.clone() };
+
+        loop {
+            let pipe = pipe.lock();
+            if pipe.writer_count() > 0 {
+                return reader;
+            }
+
+            let current_queue = Some(pipe.get_queue());
+            let writer_count = pipe.writer_count.clone();
+            drop(pipe);
+            process::yield_thread_unless(current_queue, &|| {
+                writer_count.load(Ordering::Acquire) > 0
+            });
+        }
+    }
+
+    // Blocks until the pipe has a reader
+    pub fn open_writer(pipe: &Arc<Mutex<Pipe>>) -> PipeWriter {
+        pipe.lock().increment_write();
+        let writer = PipeWriter { pipe: pipe.clone() };
+
+        loop {
+            let pipe = pipe.lock();
+            if pipe.reader_count() > 0 {
+                return writer;
+            }
+
+            let current_queue = Some(pipe.get_queue());
+            let reader_count = pipe.reader_count.clone();
+            drop(pipe);
+            process::yield_thread_unless(current_queue, &|| {
+                reader_count.load(Ordering::Acquire) > 0
+            });
+        }
+    }
+
     pub fn get_queue(&self) -> CurrentQueue {
         self.queue.into_current_queue()
     }
 
     //    pub fn read(&mut self, buffer: &mut [u8]) -> usize {
     pub fn read(&mut self, buffer: &mut [u8]) -> error::Result<usize> {
-        if self.writer_count < 1 {
+        if self.writer_count() < 1 {
             return Err(error::Status::NoWriters);
         }
         for i in 0..buffer.len() {
@@ -50,7 +103,7 @@ impl Pipe {
     }
 
     pub fn write(&mut self, buffer: &[u8]) -> error::Result<()> {
-        if self.reader_count < 1 {
+        if self.reader_count() < 1 {
             return Err(error::Status::NoReaders);
         }
 
@@ -65,20 +118,23 @@ impl Pipe {
         Ok(())
     }
 
+    // Threads waiting to open the other end are woken as well
     pub fn increment_write(&mut self) {
-        self.writer_count += 1;
+        self.writer_count.fetch_add(1, Ordering::AcqRel);
+        self.wake_all();
     }
 
     pub fn increment_read(&mut self) {
-        self.reader_count += 1;
+        self.reader_count.fetch_add(1, Ordering::AcqRel);
+        self.wake_all();
     }
 
     pub fn decrement_write(&mut self) {
-        self.writer_count -= 1;
+        self.writer_count.fetch_sub(1, Ordering::AcqRel);
 
         // Queue up any threads waiting to read if there are no more writers
         // so they can get an error
-        if self.writer_count == 0 {
+        if self.writer_count() == 0 {
             while let Some(thread) = self.queue.pop() {
                 process::queue_thread(thread)
             }
@@ -86,7 +142,21 @@ impl Pipe {
     }
 
     pub fn decrement_read(&mut self) {
-        self.reader_count -= 1;
+        self.reader_count.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    fn reader_count(&self) -> usize {
+        self.reader_count.load(Ordering::Acquire)
+    }
+
+    fn writer_count(&self) -> usize {
+        self.writer_count.load(Ordering::Acquire)
+    }
+
+    fn wake_all(&self) {
+        while let Some(thread) = self.queue.pop() {
+            process::queue_thread(thread)
+        }
     }
 }
 
@@ -125,7 +195,7 @@ impl Drop for PipeReader {
 }
 
 impl PipeWriter {
-    pub fn write(&self, buffer: &mut [u8]) -> error::Result<()> {
+    pub fn write(&self, buffer: &[u8]) -> error::Result<()> {
         self.pipe.lock().write(buffer)
     }
 }
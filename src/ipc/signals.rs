@@ -89,6 +89,14 @@ impl Signals {
         }
     }
 
+    pub fn is_pending(&self, signal: u8) -> bool {
+        self[signal].pending
+    }
+
+    pub fn is_masked(&self, signal: u8) -> bool {
+        self[signal].mask
+    }
+
     pub fn set_handler(&mut self, signal: u8, handler: SignalHandler) {
         self[signal].handler = handler;
     }
@@ -81,7 +81,14 @@ pub extern "C" fn kmain(
 
 fn kinit() -> isize {
     logln!("Loading filesystem drivers . . .");
-    filesystem::register_filesystem_driver(filesystem::drivers::fat32::detect_fat32_filesystem);
+    filesystem::register_filesystem_driver(filesystem::drivers::fat32::detect_fat_filesystem);
+    filesystem::register_filesystem_driver(filesystem::drivers::ext2::detect_ext2_filesystem);
+    filesystem::register_filesystem_driver(filesystem::drivers::iso9660::detect_iso9660_filesystem);
+    filesystem::register_filesystem_driver(filesystem::drivers::exfat::detect_exfat_filesystem);
+
+    log!("Starting block cache flush daemon . . . ");
+    process::create_process(filesystem::cache::flush_daemon, None, "bflush".to_owned());
+    logln!("OK!");
 
     logln!("Loading boot device drivers . . . ");
     device::drivers::hpet::initialize();
@@ -89,6 +96,18 @@ fn kinit() -> isize {
     device::drivers::ide::initialize();
     device::drivers::cmos::initialize();
 
+    log!("Mounting root filesystem . . . ");
+    match filesystem::mount_root_filesystem() {
+        Ok(number) => logln!("Mounted :{} at /", number),
+        Err(status) => logln!("Error: {}!", status),
+    }
+
+    log!("Creating temporary filesystem . . . ");
+    match mount_temporary_filesystem() {
+        Ok(number) => logln!("Mounted :{} at /tmp", number),
+        Err(status) => logln!("Error: {}!", status),
+    }
+
     if device::get_device("/boot_video").is_ok() {
         logln!("Starting boot video session . . . ");
         //logger::disable_boot_video_logging();
@@ -101,12 +120,67 @@ fn kinit() -> isize {
     logln!("Loading device drivers . . . ");
     device::drivers::ps2::initialize();
 
+    log!("Creating device filesystem . . . ");
+    match mount_device_filesystem() {
+        Ok(number) => logln!("Mounted :{} at /dev", number),
+        Err(status) => logln!("Error: {}!", status),
+    }
+
+    log!("Creating process filesystem . . . ");
+    match mount_process_filesystem() {
+        Ok(number) => logln!("Mounted :{} at /proc", number),
+        Err(status) => logln!("Error: {}!", status),
+    }
+
     // Idle process
     loop {
         process::queue_and_yield()
     }
 }
 
+// The mount point must already exist, nothing is written to the root volume
+fn mount_temporary_filesystem() -> error::Result<isize> {
+    if !filesystem::get_metadata("/tmp")?.is_directory() {
+        return Err(error::Status::NotDirectory);
+    }
+
+    let number = filesystem::register_virtual_filesystem(
+        filesystem::drivers::tmpfs::create_tmpfs_filesystem("tmpfs".to_owned()),
+    )?;
+
+    filesystem::mount(number, "/tmp")?;
+    Ok(number)
+}
+
+// Listings are read when a directory is opened, so devices should be registered first
+// Like /tmp the mount point must already exist
+fn mount_device_filesystem() -> error::Result<isize> {
+    if !filesystem::get_metadata("/dev")?.is_directory() {
+        return Err(error::Status::NotDirectory);
+    }
+
+    let number = filesystem::register_virtual_filesystem(
+        filesystem::drivers::devfs::create_devfs_filesystem("devfs".to_owned()),
+    )?;
+
+    filesystem::mount(number, "/dev")?;
+    Ok(number)
+}
+
+// Like /tmp the mount point must already exist
+fn mount_process_filesystem() -> error::Result<isize> {
+    if !filesystem::get_metadata("/proc")?.is_directory() {
+        return Err(error::Status::NotDirectory);
+    }
+
+    let number = filesystem::register_virtual_filesystem(
+        filesystem::drivers::procfs::create_procfs_filesystem("procfs".to_owned()),
+    )?;
+
+    filesystem::mount(number, "/proc")?;
+    Ok(number)
+}
+
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
     match info.message() {
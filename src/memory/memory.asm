@@ -11,4 +11,8 @@ get_cr2:
 GLOBAL get_current_address_space
 get_current_address_space:
     mov rax, cr3
-    ret
\ No newline at end of file
+    ret
+GLOBAL invalidate_page
+invalidate_page:
+    invlpg [rdi]
+    ret
@@ -11,6 +11,10 @@ pub type AddressSpace = virtual_mem::AddressSpace;
 pub const PAGE_SIZE: usize = 4096;
 pub const KERNEL_VMA: usize = 0xFFFF800000000000;
 
+// File mappings are placed in this range of each address space
+pub const MAPPING_START: VirtualAddress = 0x600000000000;
+pub const MAPPING_END: VirtualAddress = 0x700000000000;
+
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct MemoryUsage {
@@ -56,6 +60,22 @@ pub fn map_virtual_memory(virtual_address: VirtualAddress, physical_address: Phy
     virtual_mem::allocate(virtual_address, physical_address)
 }
 
+pub fn current_address_space() -> AddressSpace {
+    virtual_mem::current_address_space()
+}
+
+// Pages are zeroed when freed, so allocated pages start zeroed
+pub fn allocate_page() -> PhysicalAddress {
+    unsafe { physical::allocate() }
+}
+
+pub fn free_page(physical_address: PhysicalAddress) {
+    unsafe {
+        core::ptr::write_bytes((physical_address + KERNEL_VMA) as *mut u8, 0, PAGE_SIZE);
+        physical::free(physical_address);
+    }
+}
+
 impl MemoryUsage {
     pub fn free_memory(&self) -> usize {
         self.page_size * self.free_pages
@@ -64,4 +84,16 @@ impl MemoryUsage {
     pub fn available_memory(&self) -> usize {
         self.page_size * self.available_pages
     }
+
+    pub fn kernel_heap_memory(&self) -> usize {
+        self.page_size * self.kernel_heap_pages
+    }
+
+    pub fn userspace_memory(&self) -> usize {
+        self.page_size * self.userspace_pages
+    }
+
+    pub fn kernel_stack_memory(&self) -> usize {
+        self.kernel_stack_usage
+    }
 }
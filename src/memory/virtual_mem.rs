@@ -5,6 +5,7 @@ use core::{
 
 use crate::{
     bootloader::{self, MemoryDescriptor},
+    critical,
     interrupts::{
         exceptions::{install_exception_handler, ExceptionInfo},
         Registers,
@@ -12,7 +13,9 @@ use crate::{
     process,
 };
 
-use super::{physical, PhysicalAddress, VirtualAddress, KERNEL_VMA, PAGE_SIZE};
+use super::{
+    physical, PhysicalAddress, VirtualAddress, KERNEL_VMA, MAPPING_END, MAPPING_START, PAGE_SIZE,
+};
 
 trait PhysicalDrop {
     unsafe fn physical_drop(&mut self);
@@ -44,12 +47,17 @@ const PAGE_FLAG_PRESENT: usize = 1 << 0;
 const PAGE_FLAG_WRITABLE: usize = 1 << 1;
 const PAGE_FLAG_USER: usize = 1 << 2;
 
+const PAGE_ADDRESS_MASK: usize = 0x000FFFFFFFFFF000;
+
 static mut KERNEL_ADDRESS_SPACE: AddressSpace = AddressSpace(0);
 
 extern "C" {
     fn set_current_pml4(pml4: PhysicalAddress);
     fn get_cr2() -> usize;
     fn get_current_address_space() -> AddressSpace;
+    fn invalidate_page(address: VirtualAddress);
+
+    static mut LOCAL_CRITICAL_COUNT: usize;
 }
 
 pub unsafe fn initialize(
@@ -111,10 +119,37 @@ pub fn allocate(virtual_address: VirtualAddress, physical_address: PhysicalAddre
     current_address_space.allocate(virtual_address, physical_address)
 }
 
+pub fn current_address_space() -> AddressSpace {
+    unsafe { get_current_address_space() }
+}
+
 unsafe fn page_fault_handler(_registers: &Registers, info: &ExceptionInfo) {
     let cr2 = get_cr2();
+    let present = info.error_code & 1 != 0;
+    let write = info.error_code & 2 != 0;
+
+    // File mappings are checked first as they may also fault on present pages
+    if cr2 >= MAPPING_START && cr2 < MAPPING_END {
+        // Reading the file may block, which is only possible outside of a critical section
+        let blocking = LOCAL_CRITICAL_COUNT == 1;
+        if blocking {
+            critical::leave_local();
+        }
+
+        let result = process::handle_mapping_fault(cr2, write);
+
+        if blocking {
+            critical::enter_local();
+        }
 
-    if (info.error_code & 1) == 0 {
+        match result {
+            Some(Ok(())) => return,
+            Some(Err(_)) => process::exit_process(129 + 33),
+            None => {}
+        }
+    }
+
+    if !present {
         if cr2 < PAGE_SIZE {
             match process::get_current_thread_option() {
                 Some(_) => process::exit_process(129 + 32),
@@ -171,33 +206,118 @@ impl AddressSpace {
         }
 
         unsafe {
-            // Check PDPT
-            let pml4 = (self.0 + KERNEL_VMA) as *mut PML4;
-            if (*pml4).entries[index.pml4_index] & PAGE_FLAG_PRESENT == 0 {
-                let new_pdpt = PDPT::new();
-                (*pml4).set_entry(index.pml4_index, new_pdpt, user);
+            // Check page
+            let page_table = self.get_page_table(&index, true, user);
+            if (*page_table).entries[index.page_table_index] & PAGE_FLAG_PRESENT == 0 {
+                (*page_table).set_entry(index.page_table_index, physical_address, user);
+            }
+        }
+    }
+
+    // Maps a lower half page, replacing any existing mapping without freeing it
+    pub fn map_user_page(
+        &mut self,
+        virtual_address: VirtualAddress,
+        physical_address: PhysicalAddress,
+        writable: bool,
+    ) {
+        let index = PageIndex::new(virtual_address);
+
+        unsafe {
+            let page_table = self.get_page_table(&index, true, true);
+            if (*page_table).entries[index.page_table_index] & PAGE_FLAG_PRESENT == 0 {
+                super::MEMORY_USAGE.lock().userspace_pages += 1;
             }
 
-            // Check page directory
-            let pdpt = (*pml4).get_entry(index.pml4_index);
-            if (*pdpt).entries[index.pdpt_index] & PAGE_FLAG_PRESENT == 0 {
-                let new_page_directory = PageDirectory::new();
-                (*pdpt).set_entry(index.pdpt_index, new_page_directory, user);
+            (*page_table).set_entry(index.page_table_index, physical_address, true);
+            if !writable {
+                (*page_table).entries[index.page_table_index] &= !PAGE_FLAG_WRITABLE;
             }
 
-            // Check page table
-            let page_directory = (*pdpt).get_entry(index.pdpt_index);
-            if (*page_directory).entries[index.page_directory_index] & PAGE_FLAG_PRESENT == 0 {
-                let new_page_table = PageTable::new();
-                (*page_directory).set_entry(index.page_directory_index, new_page_table, user);
+            invalidate_page(virtual_address);
+        }
+    }
+
+    // Removes a lower half page without freeing it, returning its physical address
+    pub fn unmap_user_page(&mut self, virtual_address: VirtualAddress) -> Option<PhysicalAddress> {
+        let index = PageIndex::new(virtual_address);
+
+        unsafe {
+            let page_table = self.get_page_table(&index, false, true);
+            if page_table == null_mut()
+                || (*page_table).entries[index.page_table_index] & PAGE_FLAG_PRESENT == 0
+            {
+                return None;
             }
 
-            // Check page
-            let page_table = (*page_directory).get_entry(index.page_directory_index);
-            if (*page_table).entries[index.page_table_index] & PAGE_FLAG_PRESENT == 0 {
-                (*page_table).set_entry(index.page_table_index, physical_address, user);
+            let physical_address =
+                (*page_table).entries[index.page_table_index] & PAGE_ADDRESS_MASK;
+            (*page_table).clear_entry(index.page_table_index);
+            super::MEMORY_USAGE.lock().userspace_pages -= 1;
+
+            invalidate_page(virtual_address);
+            Some(physical_address)
+        }
+    }
+
+    // Returns the physical address of a lower half page and whether it is writable
+    pub fn get_user_page(
+        &self,
+        virtual_address: VirtualAddress,
+    ) -> Option<(PhysicalAddress, bool)> {
+        let index = PageIndex::new(virtual_address);
+
+        unsafe {
+            let page_table = self.get_page_table(&index, false, true);
+            if page_table == null_mut() {
+                return None;
+            }
+
+            let entry = (*page_table).entries[index.page_table_index];
+            if entry & PAGE_FLAG_PRESENT == 0 {
+                None
+            } else {
+                Some((entry & PAGE_ADDRESS_MASK, entry & PAGE_FLAG_WRITABLE != 0))
+            }
+        }
+    }
+
+    // Returns the page table covering an index, or null if it is missing and not created
+    unsafe fn get_page_table(&self, index: &PageIndex, create: bool, user: bool) -> *mut PageTable {
+        // Check PDPT
+        let pml4 = (self.0 + KERNEL_VMA) as *mut PML4;
+        if (*pml4).entries[index.pml4_index] & PAGE_FLAG_PRESENT == 0 {
+            if !create {
+                return null_mut();
+            }
+
+            let new_pdpt = PDPT::new();
+            (*pml4).set_entry(index.pml4_index, new_pdpt, user);
+        }
+
+        // Check page directory
+        let pdpt = (*pml4).get_entry(index.pml4_index);
+        if (*pdpt).entries[index.pdpt_index] & PAGE_FLAG_PRESENT == 0 {
+            if !create {
+                return null_mut();
             }
+
+            let new_page_directory = PageDirectory::new();
+            (*pdpt).set_entry(index.pdpt_index, new_page_directory, user);
         }
+
+        // Check page table
+        let page_directory = (*pdpt).get_entry(index.pdpt_index);
+        if (*page_directory).entries[index.page_directory_index] & PAGE_FLAG_PRESENT == 0 {
+            if !create {
+                return null_mut();
+            }
+
+            let new_page_table = PageTable::new();
+            (*page_directory).set_entry(index.page_directory_index, new_page_table, user);
+        }
+
+        (*page_directory).get_entry(index.page_directory_index)
     }
 
     pub fn set_as_current(&self) {
@@ -206,6 +326,42 @@ impl AddressSpace {
         }
     }
 
+    // Counts the pages mapped in the lower half
+    pub fn user_pages(&self) -> usize {
+        let mut count = 0;
+        unsafe {
+            let pml4 = (self.0 + KERNEL_VMA) as *mut PML4;
+            for i in 0..256 {
+                let pdpt = (*pml4).get_entry(i);
+                if pdpt == null_mut() {
+                    continue;
+                }
+
+                for j in 0..512 {
+                    let page_directory = (*pdpt).get_entry(j);
+                    if page_directory == null_mut() {
+                        continue;
+                    }
+
+                    for k in 0..512 {
+                        let page_table = (*page_directory).get_entry(k);
+                        if page_table == null_mut() {
+                            continue;
+                        }
+
+                        for l in 0..512 {
+                            if (*page_table).entries[l] & PAGE_FLAG_PRESENT != 0 {
+                                count += 1;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        count
+    }
+
     pub unsafe fn free(&mut self) {
         let pml4 = (self.0 + KERNEL_VMA) as *mut PML4;
 
@@ -14,7 +14,7 @@ use crate::{
     ipc::{SignalHandleReturn, Signals, UserspaceSignalContext},
     locks::Spinlock,
     map::{Mappable, INVALID_ID},
-    memory::KERNEL_VMA,
+    memory::{VirtualAddress, KERNEL_VMA},
     session::get_session,
     LOCAL_CRITICAL_COUNT,
 };
@@ -368,11 +368,22 @@ pub fn queue_and_yield() {
 }
 
 pub fn yield_thread(queue: Option<CurrentQueue>) {
+    yield_thread_unless(queue, &|| false);
+}
+
+// The condition is checked with interrupts disabled until the thread is queued, so a wakeup
+// from an interrupt handler after the caller's last check is not missed
+pub fn yield_thread_unless(queue: Option<CurrentQueue>, condition: &dyn Fn() -> bool) {
     unsafe { assert!(LOCAL_CRITICAL_COUNT == 0) };
 
     loop {
         unsafe {
             crate::critical::enter_local();
+            if condition() {
+                crate::critical::leave_local();
+                return;
+            }
+
             let next_thread = THREAD_CONTROL.lock().get_next_thread();
             match next_thread {
                 Some(next_thread) => {
@@ -465,6 +476,13 @@ pub fn wait_process(pid: isize) -> isize {
 pub fn exit_thread(exit_status: isize, critical: bool) -> ! {
     unsafe {
         if !critical {
+            // The last thread writes back the mappings before the process is freed
+            let current_thread = get_current_thread();
+            let process = current_thread.process().unwrap();
+            if process.get_threads(current_thread.id()).len() == 0 {
+                process.sync_mappings();
+            }
+
             crate::critical::enter_local();
         }
         let current_thread = get_current_thread();
@@ -481,6 +499,11 @@ pub fn exit_thread(exit_status: isize, critical: bool) -> ! {
 
 pub fn exit_process(exit_status: isize) -> ! {
     unsafe {
+        // Mappings can't be written back from within a critical section, such as a fault
+        if LOCAL_CRITICAL_COUNT == 0 {
+            get_current_thread().process().unwrap().sync_mappings();
+        }
+
         crate::critical::enter_local();
         let current_thread = get_current_thread();
         let current_process = current_thread.process().unwrap();
@@ -522,6 +545,15 @@ pub fn kill_process(pid: isize) {
         },
         None => return daemon::kill_process(pid),
     };
+
+    // Write back the mappings of another process before it is freed
+    let target = session_lock.lock().get_process(pid);
+    if let Some(target) = target {
+        if target != current_process {
+            target.sync_mappings();
+        }
+    }
+
     let mut session = session_lock.lock();
 
     unsafe {
@@ -560,6 +592,32 @@ pub fn get_current_thread_option() -> Option<ThreadReference> {
     THREAD_CONTROL.lock().get_current_thread()
 }
 
+// Returns none if the address isn't in a mapping of the current process
+pub fn handle_mapping_fault(address: VirtualAddress, write: bool) -> Option<error::Result<()>> {
+    let process = get_current_thread_option()?.process()?.upgrade()?;
+    let mapping = process.lock().find_mapping(address)?;
+
+    Some(mapping.fault(&process, address, write))
+}
+
+// Faults in the mapped pages of a userspace range, so it can be accessed while file locks are held
+pub fn fault_in_mappings(address: VirtualAddress, length: usize, write: bool) -> error::Result<()> {
+    let process = match get_current_thread_option()
+        .and_then(|thread| thread.process())
+        .and_then(|process| process.upgrade())
+    {
+        Some(process) => process,
+        None => return Ok(()),
+    };
+
+    let mappings = process.lock().find_mappings(address, address + length);
+    for mapping in mappings {
+        mapping.fault_in(&process, address, address + length, write)?;
+    }
+
+    Ok(())
+}
+
 pub fn preempt() {
     if !THREAD_CONTROL.lock().is_next_thread() {
         return;
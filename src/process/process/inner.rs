@@ -1,17 +1,17 @@
-use super::ProcessOwner;
+use super::{Mapping, ProcessOwner};
 use crate::{
     conditional_variable::ConditionalVariable,
     critical::CriticalLock,
     device::DeviceReference,
     error,
-    filesystem::{DirectoryDescriptor, FileDescriptor},
+    filesystem::{DirectoryDescriptor, FileDescriptor, Watcher},
     ipc::{
         Pipe, PipeReader, PipeWriter, SignalHandleReturn, SignalHandler, Signals,
         UserspaceSignalContext,
     },
     locks::{Mutex, MutexGuard},
     map::{Map, Mappable, MappedItem, INVALID_ID},
-    memory::AddressSpace,
+    memory::{AddressSpace, VirtualAddress, MAPPING_END, MAPPING_START},
     process::{
         queue_thread,
         thread::{ThreadOwner, ThreadReference},
@@ -30,6 +30,13 @@ pub struct PipeWriterDescriptor(Arc<Mutex<PipeWriter>>, isize);
 #[derive(Clone)]
 pub struct PipeReaderDescriptor(Arc<Mutex<PipeReader>>, isize);
 
+pub struct WatchDescriptor {
+    watcher: Arc<Watcher>,
+    // The directory is held open for as long as it is watched
+    _directory: DirectoryDescriptor,
+    id: isize,
+}
+
 pub struct MutexDescriptor(Arc<UserspaceMutex>, isize);
 pub struct CondVarDescriptor(Arc<ConditionalVariable>, isize);
 
@@ -50,6 +57,9 @@ pub struct ProcessInner {
     signals: Signals,
     pipe_reader_descriptors: Map<PipeReaderDescriptor>,
     pipe_writer_descriptors: Map<PipeWriterDescriptor>,
+    watch_descriptors: Map<WatchDescriptor>,
+    mappings: Vec<Mapping>,
+    next_mapping_address: VirtualAddress,
 }
 
 pub struct ProcessInfo {
@@ -59,6 +69,13 @@ pub struct ProcessInfo {
     pub num_directories: usize,
     pub working_directory: String,
     pub name: String,
+    pub threads: Vec<isize>,
+    pub files: Vec<isize>,
+    pub directories: Vec<isize>,
+    pub devices: Vec<isize>,
+    pub pipe_readers: Vec<isize>,
+    pub pipe_writers: Vec<isize>,
+    pub memory_pages: usize,
 }
 
 impl ProcessInner {
@@ -85,6 +102,9 @@ impl ProcessInner {
             signals,
             pipe_reader_descriptors: Map::new(),
             pipe_writer_descriptors: Map::new(),
+            watch_descriptors: Map::new(),
+            mappings: Vec::new(),
+            next_mapping_address: MAPPING_START,
         })))
     }
 
@@ -268,6 +288,13 @@ impl ProcessInner {
             num_directories: self.directory_descriptors.count(),
             working_directory,
             name: self.name.clone(),
+            threads: self.threads.ids(),
+            files: self.file_descriptors.ids(),
+            directories: self.directory_descriptors.ids(),
+            devices: self.device_descriptors.ids(),
+            pipe_readers: self.pipe_reader_descriptors.ids(),
+            pipe_writers: self.pipe_writer_descriptors.ids(),
+            memory_pages: self.address_space.user_pages(),
         }
     }
 
@@ -346,6 +373,76 @@ impl ProcessInner {
     pub fn close_pipe_writer(&mut self, pw: isize) {
         self.pipe_writer_descriptors.remove(pw);
     }
+
+    pub fn watch_directory(&mut self, dd: isize) -> error::Result<isize> {
+        let directory = self.get_directory(dd)?;
+        let directory = DirectoryDescriptor::new(directory.lock().get_directory());
+        let watcher = directory.watch();
+
+        Ok(self.watch_descriptors.insert(WatchDescriptor {
+            watcher,
+            _directory: directory,
+            id: INVALID_ID,
+        }))
+    }
+
+    pub fn get_watcher(&self, wd: isize) -> error::Result<Arc<Watcher>> {
+        match self.watch_descriptors.get(wd) {
+            None => Err(error::Status::BadDescriptor),
+            Some(watch_descriptor) => Ok(watch_descriptor.watcher.clone()),
+        }
+    }
+
+    pub fn close_watcher(&mut self, wd: isize) {
+        self.watch_descriptors.remove(wd);
+    }
+
+    // Mapping addresses are never reused
+    pub fn insert_mapping(&mut self, mut mapping: Mapping) -> error::Result<VirtualAddress> {
+        let start = self.next_mapping_address;
+        if MAPPING_END - start < mapping.size() {
+            return Err(error::Status::OutOfResource);
+        }
+
+        self.next_mapping_address += mapping.size();
+        mapping.set_start(start);
+        self.mappings.push(mapping);
+        Ok(start)
+    }
+
+    pub fn find_mapping(&self, address: VirtualAddress) -> Option<Mapping> {
+        self.mappings
+            .iter()
+            .find(|mapping| mapping.contains(address))
+            .cloned()
+    }
+
+    pub fn has_mapping(&self, start: VirtualAddress) -> bool {
+        self.mappings.iter().any(|mapping| mapping.start() == start)
+    }
+
+    pub fn get_mappings(&self) -> Vec<Mapping> {
+        self.mappings.clone()
+    }
+
+    pub fn find_mappings(&self, start: VirtualAddress, end: VirtualAddress) -> Vec<Mapping> {
+        self.mappings
+            .iter()
+            .filter(|mapping| mapping.start() < end && start < mapping.start() + mapping.size())
+            .cloned()
+            .collect()
+    }
+
+    pub fn remove_mapping(&mut self, start: VirtualAddress) -> error::Result<Mapping> {
+        match self
+            .mappings
+            .iter()
+            .position(|mapping| mapping.start() == start)
+        {
+            Some(index) => Ok(self.mappings.remove(index)),
+            None => Err(error::Status::InvalidArgument),
+        }
+    }
 }
 
 impl Mappable for ProcessInner {
@@ -360,6 +457,12 @@ impl Mappable for ProcessInner {
 
 impl Drop for ProcessInner {
     fn drop(&mut self) {
+        // Mapped file pages are owned by the file, so they are removed before freeing
+        // Shared mappings were written back as the process exited, this runs in a critical section
+        for mapping in core::mem::take(&mut self.mappings) {
+            mapping.release_pages(&mut self.address_space);
+        }
+
         unsafe { self.address_space.free() };
 
         match self.session_id {
@@ -412,6 +515,16 @@ impl Mappable for PipeWriterDescriptor {
     }
 }
 
+impl Mappable for WatchDescriptor {
+    fn set_id(&mut self, id: isize) {
+        self.id = id;
+    }
+
+    fn id(&self) -> isize {
+        self.id
+    }
+}
+
 impl Mappable for MutexDescriptor {
     fn set_id(&mut self, id: isize) {
         self.1 = id;
@@ -0,0 +1,185 @@
+use super::{inner::Container, ProcessOwner};
+use crate::{
+    error,
+    filesystem::FileDescriptor,
+    memory::{self, AddressSpace, PhysicalAddress, VirtualAddress, KERNEL_VMA, PAGE_SIZE},
+};
+
+// A range of a file mapped into a process, pages are faulted in on first access
+#[derive(Clone)]
+pub struct Mapping {
+    start: VirtualAddress,
+    page_count: usize,
+    first_page: usize,
+    shared: bool,
+    writable: bool,
+    file: Container<FileDescriptor>,
+}
+
+impl Mapping {
+    pub fn new(
+        file: Container<FileDescriptor>,
+        offset: usize,
+        length: usize,
+        shared: bool,
+        writable: bool,
+    ) -> error::Result<Self> {
+        if offset % PAGE_SIZE != 0 || length == 0 {
+            return Err(error::Status::InvalidArgument);
+        }
+
+        file.lock().begin_mapping(shared && writable)?;
+
+        Ok(Mapping {
+            start: 0,
+            page_count: (length + PAGE_SIZE - 1) / PAGE_SIZE,
+            first_page: offset / PAGE_SIZE,
+            shared,
+            writable,
+            file,
+        })
+    }
+
+    pub fn start(&self) -> VirtualAddress {
+        self.start
+    }
+
+    pub fn size(&self) -> usize {
+        self.page_count * PAGE_SIZE
+    }
+
+    pub fn set_start(&mut self, start: VirtualAddress) {
+        self.start = start;
+    }
+
+    pub fn contains(&self, address: VirtualAddress) -> bool {
+        address >= self.start && address < self.start + self.size()
+    }
+
+    pub fn fault(
+        &self,
+        process: &ProcessOwner,
+        address: VirtualAddress,
+        write: bool,
+    ) -> error::Result<()> {
+        if write && !self.writable {
+            return Err(error::Status::ReadOnly);
+        }
+
+        let virtual_address = address & !(PAGE_SIZE - 1);
+        let index = self.first_page + (virtual_address - self.start) / PAGE_SIZE;
+
+        let file = self.file.lock();
+        let physical_address = file.get_page(index)?;
+
+        // Private writes get their own copy of the page
+        let (physical_address, writable) = if self.shared {
+            (physical_address, self.writable)
+        } else if write {
+            let copy = memory::allocate_page();
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    (physical_address + KERNEL_VMA) as *const u8,
+                    (copy + KERNEL_VMA) as *mut u8,
+                    PAGE_SIZE,
+                )
+            };
+            (copy, true)
+        } else {
+            (physical_address, false)
+        };
+
+        // The page is only installed while the mapping exists, so unmapping always finds it
+        let process = process.lock();
+        let mut address_space = memory::current_address_space();
+        let present = match address_space.get_user_page(virtual_address) {
+            Some((_, true)) => true,
+            Some((_, false)) => !write,
+            None => false,
+        };
+
+        // Another thread may have handled the fault already
+        if present || !process.has_mapping(self.start) {
+            drop(process);
+            self.discard_copy(&*file, index, physical_address);
+            return Ok(());
+        }
+
+        address_space.map_user_page(virtual_address, physical_address, writable);
+        Ok(())
+    }
+
+    // Faults in the pages overlapping a range which are not yet present, or not writable for writes
+    pub fn fault_in(
+        &self,
+        process: &ProcessOwner,
+        start: VirtualAddress,
+        end: VirtualAddress,
+        write: bool,
+    ) -> error::Result<()> {
+        if write && !self.writable {
+            return Err(error::Status::InvalidArgument);
+        }
+
+        let start = core::cmp::max(start, self.start) & !(PAGE_SIZE - 1);
+        let end = core::cmp::min(end, self.start + self.size());
+
+        let mut virtual_address = start;
+        while virtual_address < end {
+            let present = match memory::current_address_space().get_user_page(virtual_address) {
+                Some((_, writable)) => writable || !write,
+                None => false,
+            };
+
+            if !present {
+                self.fault(process, virtual_address, write)?;
+            }
+
+            virtual_address += PAGE_SIZE;
+        }
+
+        Ok(())
+    }
+
+    pub fn sync(&self) -> error::Result<()> {
+        if !self.shared {
+            return Ok(());
+        }
+
+        self.file
+            .lock()
+            .write_back(self.first_page, self.page_count)
+    }
+
+    // Ends a mapping which was never inserted into a process
+    pub fn release(self) {
+        self.file.lock().end_mapping();
+    }
+
+    // Writes back shared pages and frees private copies
+    pub fn unmap(self, address_space: &mut AddressSpace) -> error::Result<()> {
+        let result = self.sync();
+        self.release_pages(address_space);
+        result
+    }
+
+    // Removes the pages without writing them back, as done when a process is freed
+    pub fn release_pages(self, address_space: &mut AddressSpace) {
+        let file = self.file.lock();
+        for page in 0..self.page_count {
+            let virtual_address = self.start + page * PAGE_SIZE;
+            if let Some(physical_address) = address_space.unmap_user_page(virtual_address) {
+                self.discard_copy(&*file, self.first_page + page, physical_address);
+            }
+        }
+
+        file.end_mapping();
+    }
+
+    // Pages owned by the file are left to it
+    fn discard_copy(&self, file: &FileDescriptor, index: usize, physical_address: PhysicalAddress) {
+        if !file.is_page(index, physical_address) {
+            memory::free_page(physical_address);
+        }
+    }
+}
@@ -1,6 +1,8 @@
 mod inner;
+mod mapping;
 mod owner;
 mod reference;
 
+pub use mapping::Mapping;
 pub use owner::ProcessOwner;
 pub use reference::ProcessReference;
@@ -11,6 +11,7 @@ use crate::{
         PipeReader, PipeWriter, SignalHandleReturn, SignalHandler, Signals, UserspaceSignalContext,
     },
     locks::Mutex,
+    logln,
     map::{Mappable, INVALID_ID},
     process::{CurrentQueue, ThreadOwner, ThreadReference},
     userspace_mutex::UserspaceMutex,
@@ -155,6 +156,20 @@ impl ProcessReference {
         }
     }
 
+    // Shared mappings are written back while the process can still block, before it is freed
+    pub fn sync_mappings(&self) {
+        let mappings = match self.0.upgrade() {
+            Some(process) => process.lock().get_mappings(),
+            None => return,
+        };
+
+        for mapping in mappings {
+            if let Err(status) = mapping.sync() {
+                logln!("Failed to write back mapping: {}", status);
+            }
+        }
+    }
+
     pub fn increase_time(&self, amount: isize) {
         match self.0.upgrade() {
             Some(process) => process.lock().increase_time(amount),
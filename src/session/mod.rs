@@ -40,10 +40,10 @@ pub fn create_console_session(output_device_path: &str) -> error::Result<isize>
         .insert(SessionBox(Arc::new(CriticalLock::new(new_session))));
 
     let mut env = Vec::new();
-    env.push("PATH=:1/los/bin".to_string());
+    env.push("PATH=/los/bin".to_string());
 
     process::execute_session(
-        ":1/los/bin/cshell.app",
+        "/los/bin/cshell.app",
         Vec::new(),
         env,
         StandardIO::new(
@@ -63,6 +63,10 @@ pub fn get_session(sid: isize) -> Option<SessionBox> {
     SESSIONS.lock().get(sid).map(|sbox| sbox.clone())
 }
 
+pub fn get_sessions() -> Vec<isize> {
+    SESSIONS.lock().ids()
+}
+
 impl Session {
     pub fn new(sub: SubSession) -> Self {
         Session {
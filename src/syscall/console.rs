@@ -91,7 +91,7 @@ pub fn system_call(
                 Err(status) => status.to_return_code(),
             }
         }
-        CONSOLE_WRITE_SYSCALL => match super::to_slice_mut(arg1, arg2) {
+        CONSOLE_WRITE_SYSCALL => match super::to_slice(arg1, arg2) {
             Ok(slice) => console_output.write_str(&String::from_utf8_lossy(slice)),
             Err(status) => Err(status),
         },
@@ -72,7 +72,7 @@ pub fn system_call(
                 Err(status) => return status.to_return_code(),
             };
 
-            let buffer = match super::to_slice_mut(arg3, arg4) {
+            let buffer = match super::to_slice(arg3, arg4) {
                 Ok(slice) => slice,
                 Err(status) => return status.to_return_code(),
             };
@@ -1,6 +1,6 @@
 use crate::{
     error,
-    filesystem::{self, SeekFrom},
+    filesystem::{self, FilesystemStat, SeekFrom, Stat},
     logln, process,
 };
 
@@ -17,13 +17,38 @@ const REMOVE_FILE_SYSCALL: usize = 0x2009;
 const REMOVE_DIRECTORY_SYSCALL: usize = 0x200A;
 const CREATE_DIRECTORY_SYSCALL: usize = 0x200B;
 const TELL_FILE_SYSCALL: usize = 0x200C;
+const MOUNT_SYSCALL: usize = 0x200D;
+const UNMOUNT_SYSCALL: usize = 0x200E;
+const RENAME_SYSCALL: usize = 0x200F;
+const STAT_SYSCALL: usize = 0x2010;
+const FSTAT_SYSCALL: usize = 0x2011;
+const IOCTRL_FILE_SYSCALL: usize = 0x2012;
+const FLUSH_FILE_SYSCALL: usize = 0x2013;
+const SYNC_SYSCALL: usize = 0x2014;
+const LIST_FILESYSTEMS_SYSCALL: usize = 0x2015;
+const READ_FILE_AT_SYSCALL: usize = 0x2016;
+const WRITE_FILE_AT_SYSCALL: usize = 0x2017;
+const ATTACH_LOOP_DEVICE_SYSCALL: usize = 0x2018;
+const LOCK_FILE_SYSCALL: usize = 0x2019;
+const UNLOCK_FILE_SYSCALL: usize = 0x201A;
+const WATCH_DIRECTORY_SYSCALL: usize = 0x201B;
+const READ_WATCH_SYSCALL: usize = 0x201C;
+const CLOSE_WATCH_SYSCALL: usize = 0x201D;
+const CHECK_FILESYSTEM_SYSCALL: usize = 0x201E;
+const FORMAT_FILESYSTEM_SYSCALL: usize = 0x201F;
+const DETACH_LOOP_DEVICE_SYSCALL: usize = 0x2020;
+
+const LOCK_FLAG_EXCLUSIVE: usize = 1 << 0;
+const LOCK_FLAG_NON_BLOCKING: usize = 1 << 1;
+
+const CHECK_FLAG_REPAIR: usize = 1 << 0;
 
 pub fn system_call(
     code: usize,
     arg1: usize,
     arg2: usize,
     arg3: usize,
-    _arg4: usize,
+    arg4: usize,
     _arg5: usize,
 ) -> isize {
     match code {
@@ -61,7 +86,11 @@ pub fn system_call(
             };
 
             let mut file = file.lock();
-            (file.seek(arg2, SeekFrom::from(arg3)) & 0x7FFFFFFFFFFF) as isize
+            match file.seek(arg2 as isize, SeekFrom::from(arg3)) {
+                Ok(offset) if offset <= isize::MAX as usize => offset as isize,
+                Ok(_) => error::Status::InvalidArgument.to_return_code(),
+                Err(status) => status.to_return_code(),
+            }
         }
         READ_FILE_SYSCALL => {
             let process = process::get_current_thread()
@@ -159,7 +188,7 @@ pub fn system_call(
                 Err(status) => return status.to_return_code(),
             };
 
-            let buffer = match super::to_slice_mut(arg2, arg3) {
+            let buffer = match super::to_slice(arg2, arg3) {
                 Ok(slice) => slice,
                 Err(status) => return status.to_return_code(),
             };
@@ -217,6 +246,339 @@ pub fn system_call(
             let ret = file.lock().tell();
             (ret & 0x7FFFFFFFFFFF) as isize
         }
+        MOUNT_SYSCALL => {
+            let path = match super::to_str(arg2) {
+                Ok(str) => str,
+                Err(status) => return status.to_return_code(),
+            };
+
+            match filesystem::mount((arg1 & 0x7FFFFFFFFFFF) as isize, path) {
+                Ok(()) => 0,
+                Err(status) => status.to_return_code(),
+            }
+        }
+        UNMOUNT_SYSCALL => {
+            let path = match super::to_str(arg1) {
+                Ok(str) => str,
+                Err(status) => return status.to_return_code(),
+            };
+
+            match filesystem::unmount(path) {
+                Ok(()) => 0,
+                Err(status) => status.to_return_code(),
+            }
+        }
+        RENAME_SYSCALL => {
+            let old_path = match super::to_str(arg1) {
+                Ok(str) => str,
+                Err(status) => return status.to_return_code(),
+            };
+
+            let new_path = match super::to_str(arg2) {
+                Ok(str) => str,
+                Err(status) => return status.to_return_code(),
+            };
+
+            match filesystem::rename(old_path, new_path, arg3) {
+                Ok(()) => 0,
+                Err(status) => status.to_return_code(),
+            }
+        }
+        STAT_SYSCALL => {
+            let path = match super::to_str(arg1) {
+                Ok(str) => str,
+                Err(status) => return status.to_return_code(),
+            };
+
+            let destination = match super::to_ptr_mut(arg2) {
+                Ok(ptr) => ptr,
+                Err(status) => return status.to_return_code(),
+            };
+
+            match filesystem::get_metadata(path) {
+                Ok(metadata) => {
+                    unsafe { *destination = Stat::new(&metadata) };
+                    0
+                }
+                Err(status) => status.to_return_code(),
+            }
+        }
+        FSTAT_SYSCALL => {
+            let destination = match super::to_ptr_mut(arg2) {
+                Ok(ptr) => ptr,
+                Err(status) => return status.to_return_code(),
+            };
+
+            let process = process::get_current_thread()
+                .process()
+                .unwrap()
+                .upgrade()
+                .unwrap();
+            let file = match process.lock().get_file((arg1 & 0x7FFFFFFFFFFF) as isize) {
+                Ok(file) => file,
+                Err(status) => return status.to_return_code(),
+            };
+
+            let metadata = file.lock().get_metadata();
+            match metadata {
+                Ok(metadata) => {
+                    unsafe { *destination = Stat::new(&metadata) };
+                    0
+                }
+                Err(status) => status.to_return_code(),
+            }
+        }
+        IOCTRL_FILE_SYSCALL => {
+            let process = process::get_current_thread()
+                .process()
+                .unwrap()
+                .upgrade()
+                .unwrap();
+            let file = match process.lock().get_file((arg1 & 0x7FFFFFFFFFFF) as isize) {
+                Ok(file) => file,
+                Err(status) => return status.to_return_code(),
+            };
+
+            let result = file.lock().ioctrl(arg2, arg3);
+            match result {
+                Ok(value) => value as isize,
+                Err(status) => status.to_return_code(),
+            }
+        }
+        FLUSH_FILE_SYSCALL => {
+            let process = process::get_current_thread()
+                .process()
+                .unwrap()
+                .upgrade()
+                .unwrap();
+            let file = match process.lock().get_file((arg1 & 0x7FFFFFFFFFFF) as isize) {
+                Ok(file) => file,
+                Err(status) => return status.to_return_code(),
+            };
+
+            let result = file.lock().flush();
+            match result {
+                Ok(()) => 0,
+                Err(status) => status.to_return_code(),
+            }
+        }
+        SYNC_SYSCALL => match filesystem::sync() {
+            Ok(()) => 0,
+            Err(status) => status.to_return_code(),
+        },
+        LIST_FILESYSTEMS_SYSCALL => {
+            // Fills up to arg2 entries and returns the number of filesystems
+            let filesystems = filesystem::get_filesystems();
+            if arg2 > 0 {
+                let destination = match super::to_slice_mut::<FilesystemStat>(arg1, arg2) {
+                    Ok(slice) => slice,
+                    Err(status) => return status.to_return_code(),
+                };
+
+                for (stat, info) in destination.iter_mut().zip(filesystems.iter()) {
+                    let (total_space, free_space) = match filesystem::get_space(info.number) {
+                        Ok(space) => space,
+                        Err(status) => return status.to_return_code(),
+                    };
+
+                    *stat = FilesystemStat::new(info, total_space, free_space);
+                }
+            }
+
+            filesystems.len() as isize
+        }
+        READ_FILE_AT_SYSCALL => {
+            let process = process::get_current_thread()
+                .process()
+                .unwrap()
+                .upgrade()
+                .unwrap();
+            let file = match process.lock().get_file((arg1 & 0x7FFFFFFFFFFF) as isize) {
+                Ok(file) => file,
+                Err(status) => return status.to_return_code(),
+            };
+
+            if arg4.checked_add(arg3).is_none() {
+                return error::Status::InvalidArgument.to_return_code();
+            }
+
+            let buffer = match super::to_slice_mut(arg2, arg3) {
+                Ok(slice) => slice,
+                Err(status) => return status.to_return_code(),
+            };
+
+            let result = file.lock().read_at(arg4, buffer);
+            match result {
+                Ok(bytes_read) => bytes_read,
+                Err(status) => status.to_return_code(),
+            }
+        }
+        WRITE_FILE_AT_SYSCALL => {
+            let process = process::get_current_thread()
+                .process()
+                .unwrap()
+                .upgrade()
+                .unwrap();
+            let file = match process.lock().get_file((arg1 & 0x7FFFFFFFFFFF) as isize) {
+                Ok(file) => file,
+                Err(status) => return status.to_return_code(),
+            };
+
+            if arg4.checked_add(arg3).is_none() {
+                return error::Status::InvalidArgument.to_return_code();
+            }
+
+            let buffer = match super::to_slice(arg2, arg3) {
+                Ok(slice) => slice,
+                Err(status) => return status.to_return_code(),
+            };
+
+            let result = file.lock().write_at(arg4, buffer);
+            match result {
+                Ok(bytes_written) => bytes_written,
+                Err(status) => status.to_return_code(),
+            }
+        }
+        ATTACH_LOOP_DEVICE_SYSCALL => {
+            let filepath = match super::to_str(arg1) {
+                Ok(str) => str,
+                Err(status) => return status.to_return_code(),
+            };
+
+            match filesystem::attach_loop_device(filepath) {
+                Ok(number) => number,
+                Err(status) => status.to_return_code(),
+            }
+        }
+        DETACH_LOOP_DEVICE_SYSCALL => match filesystem::detach_loop_device(arg1) {
+            Ok(()) => 0,
+            Err(status) => status.to_return_code(),
+        },
+        LOCK_FILE_SYSCALL => {
+            let process = process::get_current_thread()
+                .process()
+                .unwrap()
+                .upgrade()
+                .unwrap();
+            let file = match process.lock().get_file((arg1 & 0x7FFFFFFFFFFF) as isize) {
+                Ok(file) => file,
+                Err(status) => return status.to_return_code(),
+            };
+
+            // The descriptor is unlocked while waiting so it can still be used and closed
+            loop {
+                let result = file
+                    .lock()
+                    .try_lock(arg2, arg3, arg4 & LOCK_FLAG_EXCLUSIVE != 0);
+                match result {
+                    Ok(None) => return 0,
+                    Ok(Some(_)) if arg4 & LOCK_FLAG_NON_BLOCKING != 0 => {
+                        return error::Status::TryAgain.to_return_code()
+                    }
+                    Ok(Some(wait)) => wait.wait(),
+                    Err(status) => return status.to_return_code(),
+                }
+            }
+        }
+        UNLOCK_FILE_SYSCALL => {
+            let process = process::get_current_thread()
+                .process()
+                .unwrap()
+                .upgrade()
+                .unwrap();
+            let file = match process.lock().get_file((arg1 & 0x7FFFFFFFFFFF) as isize) {
+                Ok(file) => file,
+                Err(status) => return status.to_return_code(),
+            };
+
+            let result = file.lock().unlock(arg2, arg3);
+            match result {
+                Ok(()) => 0,
+                Err(status) => status.to_return_code(),
+            }
+        }
+        WATCH_DIRECTORY_SYSCALL => {
+            let process = process::get_current_thread()
+                .process()
+                .unwrap()
+                .upgrade()
+                .unwrap();
+
+            let result = process
+                .lock()
+                .watch_directory((arg1 & 0x7FFFFFFFFFFF) as isize);
+            match result {
+                Ok(wd) => wd,
+                Err(status) => status.to_return_code(),
+            }
+        }
+        READ_WATCH_SYSCALL => {
+            let process = process::get_current_thread()
+                .process()
+                .unwrap()
+                .upgrade()
+                .unwrap();
+            let watcher = match process.lock().get_watcher((arg1 & 0x7FFFFFFFFFFF) as isize) {
+                Ok(watcher) => watcher,
+                Err(status) => return status.to_return_code(),
+            };
+
+            let buffer = match super::to_slice_mut(arg2, arg3) {
+                Ok(slice) => slice,
+                Err(status) => return status.to_return_code(),
+            };
+
+            // Blocks until an event arrives
+            watcher.read(buffer) as isize
+        }
+        CLOSE_WATCH_SYSCALL => {
+            let process = process::get_current_thread()
+                .process()
+                .unwrap()
+                .upgrade()
+                .unwrap();
+
+            process
+                .lock()
+                .close_watcher((arg1 & 0x7FFFFFFFFFFF) as isize);
+            0
+        }
+        CHECK_FILESYSTEM_SYSCALL => {
+            let drive_path = match super::to_str(arg1) {
+                Ok(str) => str,
+                Err(status) => return status.to_return_code(),
+            };
+
+            let destination = match super::to_ptr_mut(arg2) {
+                Ok(ptr) => ptr,
+                Err(status) => return status.to_return_code(),
+            };
+
+            match filesystem::check_filesystem(drive_path, arg3 & CHECK_FLAG_REPAIR != 0) {
+                Ok(report) => {
+                    let problems = report.problems();
+                    unsafe { *destination = report };
+                    problems as isize
+                }
+                Err(status) => status.to_return_code(),
+            }
+        }
+        FORMAT_FILESYSTEM_SYSCALL => {
+            let drive_path = match super::to_str(arg1) {
+                Ok(str) => str,
+                Err(status) => return status.to_return_code(),
+            };
+
+            let volume_name = match super::to_str(arg2) {
+                Ok(str) => str,
+                Err(status) => return status.to_return_code(),
+            };
+
+            match filesystem::format_filesystem(drive_path, volume_name) {
+                Ok(()) => 0,
+                Err(status) => status.to_return_code(),
+            }
+        }
         _ => {
             logln!("Invalid filesystem system call: {}", code);
             error::Status::InvalidRequestCode.to_return_code()
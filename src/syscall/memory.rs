@@ -1,13 +1,19 @@
-use crate::{error, logln, memory};
+use crate::{error, logln, memory, process};
 
 const GET_MEMORY_USAGE_SYSCALL: usize = 0x7000;
+const MAP_FILE_SYSCALL: usize = 0x7001;
+const UNMAP_MEMORY_SYSCALL: usize = 0x7002;
+const SYNC_MEMORY_SYSCALL: usize = 0x7003;
+
+const MAP_FLAG_SHARED: usize = 1 << 0;
+const MAP_FLAG_WRITABLE: usize = 1 << 1;
 
 pub fn system_call(
     code: usize,
     arg1: usize,
-    _arg2: usize,
-    _arg3: usize,
-    _arg4: usize,
+    arg2: usize,
+    arg3: usize,
+    arg4: usize,
     _arg5: usize,
 ) -> isize {
     match code {
@@ -21,6 +27,72 @@ pub fn system_call(
 
             0
         }
+        MAP_FILE_SYSCALL => {
+            let process = process::get_current_thread()
+                .process()
+                .unwrap()
+                .upgrade()
+                .unwrap();
+
+            let file = match process.lock().get_file((arg1 & 0x7FFFFFFFFFFF) as isize) {
+                Ok(file) => file,
+                Err(status) => return status.to_return_code(),
+            };
+
+            let mapping = match process::Mapping::new(
+                file,
+                arg2,
+                arg3,
+                arg4 & MAP_FLAG_SHARED != 0,
+                arg4 & MAP_FLAG_WRITABLE != 0,
+            ) {
+                Ok(mapping) => mapping,
+                Err(status) => return status.to_return_code(),
+            };
+
+            let result = process.lock().insert_mapping(mapping.clone());
+            match result {
+                Ok(address) => (address & 0x7FFFFFFFFFFF) as isize,
+                Err(status) => {
+                    mapping.release();
+                    status.to_return_code()
+                }
+            }
+        }
+        UNMAP_MEMORY_SYSCALL => {
+            let process = process::get_current_thread()
+                .process()
+                .unwrap()
+                .upgrade()
+                .unwrap();
+
+            let mapping = match process.lock().remove_mapping(arg1) {
+                Ok(mapping) => mapping,
+                Err(status) => return status.to_return_code(),
+            };
+
+            match mapping.unmap(&mut memory::current_address_space()) {
+                Ok(()) => 0,
+                Err(status) => status.to_return_code(),
+            }
+        }
+        SYNC_MEMORY_SYSCALL => {
+            let process = process::get_current_thread()
+                .process()
+                .unwrap()
+                .upgrade()
+                .unwrap();
+
+            let mapping = match process.lock().find_mapping(arg1) {
+                Some(mapping) => mapping,
+                None => return error::Status::InvalidArgument.to_return_code(),
+            };
+
+            match mapping.sync() {
+                Ok(()) => 0,
+                Err(status) => status.to_return_code(),
+            }
+        }
         _ => {
             logln!("Invalid memory system call: {}", code);
             error::Status::InvalidRequestCode.to_return_code()
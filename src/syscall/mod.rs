@@ -67,10 +67,21 @@ extern "C" fn system_call(
 }
 
 // Argument translation functions
+// Mapped pages are faulted in now, as buffers are often used while file locks are held
+fn to_slice<T>(ptr: usize, len: usize) -> error::Result<&'static [T]> {
+    if ptr >= KERNEL_VMA || ptr + len * core::mem::size_of::<T>() >= KERNEL_VMA {
+        Err(error::Status::ArgumentSecurity)
+    } else {
+        crate::process::fault_in_mappings(ptr, len * core::mem::size_of::<T>(), false)?;
+        Ok(unsafe { core::slice::from_raw_parts(ptr as *const T, len) })
+    }
+}
+
 fn to_slice_mut<T>(ptr: usize, len: usize) -> error::Result<&'static mut [T]> {
     if ptr >= KERNEL_VMA || ptr + len * core::mem::size_of::<T>() >= KERNEL_VMA {
         Err(error::Status::ArgumentSecurity)
     } else {
+        crate::process::fault_in_mappings(ptr, len * core::mem::size_of::<T>(), true)?;
         Ok(unsafe { core::slice::from_raw_parts_mut(ptr as *mut T, len) })
     }
 }
@@ -102,6 +113,7 @@ fn to_ptr_mut<T>(ptr: usize) -> error::Result<*mut T> {
     if ptr >= KERNEL_VMA || ptr + core::mem::size_of::<T>() >= KERNEL_VMA {
         Err(error::Status::ArgumentSecurity)
     } else {
+        crate::process::fault_in_mappings(ptr, core::mem::size_of::<T>(), true)?;
         Ok(ptr as *mut T)
     }
 }
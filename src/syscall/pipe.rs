@@ -1,10 +1,14 @@
-use crate::{error, logln, process};
+use crate::{error, filesystem, locks::Mutex, logln, process};
+use alloc::sync::Arc;
 
 const CLOSE_PIPE_READ_SYSCALL: usize = 0xA000;
 const CLOSE_PIPE_WRITE_SYSCALL: usize = 0xA001;
 const CREATE_PIPE_SYSCALL: usize = 0xA002;
 const READ_PIPE_SYSCALL: usize = 0xA003;
 const WRITE_PIPE_SYSCALL: usize = 0xA004;
+const CREATE_FIFO_SYSCALL: usize = 0xA005;
+const OPEN_FIFO_READ_SYSCALL: usize = 0xA006;
+const OPEN_FIFO_WRITE_SYSCALL: usize = 0xA007;
 
 pub fn system_call(
     code: usize,
@@ -81,7 +85,7 @@ pub fn system_call(
         }
 
         WRITE_PIPE_SYSCALL => {
-            let buffer = match super::to_slice_mut(arg2, arg3) {
+            let buffer = match super::to_slice(arg2, arg3) {
                 Ok(buffer) => buffer,
                 Err(error) => return error.to_return_code(),
             };
@@ -100,6 +104,47 @@ pub fn system_call(
             };
 
 
+        }
+        CREATE_FIFO_SYSCALL => {
+            let path = match super::to_str(arg1) {
+                Ok(str) => str,
+                Err(error) => return error.to_return_code(),
+            };
+
+            match filesystem::create_fifo(path) {
+                Ok(()) => 0,
+                Err(error) => error.to_return_code(),
+            }
+        }
+        OPEN_FIFO_READ_SYSCALL => {
+            let path = match super::to_str(arg1) {
+                Ok(str) => str,
+                Err(error) => return error.to_return_code(),
+            };
+
+            // Blocks until a writer opens the pipe
+            let pr = match filesystem::open_fifo_reader(path) {
+                Ok(pr) => pr,
+                Err(error) => return error.to_return_code(),
+            };
+
+            let process = process::get_current_thread().process().unwrap();
+            process.insert_pipe_reader(Arc::new(Mutex::new(pr)))
+        }
+        OPEN_FIFO_WRITE_SYSCALL => {
+            let path = match super::to_str(arg1) {
+                Ok(str) => str,
+                Err(error) => return error.to_return_code(),
+            };
+
+            // Blocks until a reader opens the pipe
+            let pw = match filesystem::open_fifo_writer(path) {
+                Ok(pw) => pw,
+                Err(error) => return error.to_return_code(),
+            };
+
+            let process = process::get_current_thread().process().unwrap();
+            process.insert_pipe_writer(Arc::new(Mutex::new(pw)))
         }
         _ => {
             logln!("Invalid pipe system call: {}", code);
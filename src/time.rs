@@ -16,6 +16,8 @@ static mut EPOCH_TIME: isize = 0;
 static mut SYSTEM_OFFSET: usize = 0;
 static mut TIME_ZONE: isize = 0;
 
+const MONTH_TO_DAYS: [isize; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+
 static SLEEPING_THREADS: SortedThreadQueue<usize> = SortedThreadQueue::new();
 static ALARMS: CriticalLock<SortedQueue<usize, ProcessReference>> =
     CriticalLock::new(SortedQueue::new());
@@ -48,6 +50,93 @@ pub fn get_epoch_time() -> isize {
     unsafe { EPOCH_TIME }
 }
 
+// Month and day start at 1
+pub fn date_to_epoch(
+    year: isize,
+    month: isize,
+    day: isize,
+    hour: isize,
+    minute: isize,
+    second: isize,
+) -> isize {
+    let mut yday = MONTH_TO_DAYS[(month - 1) as usize] + day - 1;
+    if is_leap_year(year) && month > 2 {
+        yday += 1;
+    }
+
+    let mut epoch = second + minute * 60 + hour * 3600 + yday * 86400;
+
+    for year in 1970..year {
+        if is_leap_year(year) {
+            epoch += 366 * 86400;
+        } else {
+            epoch += 365 * 86400;
+        }
+    }
+
+    epoch
+}
+
+// Returns (year, month, day, hour, minute, second) with month and day starting at 1
+pub fn epoch_to_date(epoch: isize) -> (isize, isize, isize, isize, isize, isize) {
+    let mut days = epoch.div_euclid(86400);
+    let seconds = epoch.rem_euclid(86400);
+
+    let mut year = 1970;
+    loop {
+        let year_days = if is_leap_year(year) { 366 } else { 365 };
+        if days < 0 {
+            year -= 1;
+            days += if is_leap_year(year) { 366 } else { 365 };
+        } else if days >= year_days {
+            days -= year_days;
+            year += 1;
+        } else {
+            break;
+        }
+    }
+
+    let mut month = 12;
+    while month > 1 {
+        let mut month_start = MONTH_TO_DAYS[month - 1];
+        if is_leap_year(year) && month > 2 {
+            month_start += 1;
+        }
+
+        if days >= month_start {
+            days -= month_start;
+            break;
+        }
+
+        month -= 1;
+    }
+
+    (
+        year,
+        month as isize,
+        days + 1,
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60,
+    )
+}
+
+fn is_leap_year(year: isize) -> bool {
+    if year % 4 == 0 {
+        if year % 100 == 0 {
+            if year % 400 == 0 {
+                true
+            } else {
+                false
+            }
+        } else {
+            true
+        }
+    } else {
+        false
+    }
+}
+
 pub unsafe fn millisecond_tick() {
     SYSTEM_TIME += 1;
 